@@ -8,9 +8,58 @@ use std::fmt;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::Result;
+use crate::{AgentIdError, Result};
+use agentid_crypto::{PublicKey, SigningContext};
 use agentid_types::{AgentCapabilities, AgentId, AgentStatus};
 
+/// What an agent may use a key for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyPurpose {
+    /// Proving control of the agent, e.g. answering challenges
+    Authentication,
+    /// Signing statements on behalf of the agent, e.g. credentials
+    Assertion,
+    /// Receiving encrypted messages for the agent
+    KeyAgreement,
+}
+
+impl KeyPurpose {
+    /// Check if keys with this purpose produce signatures
+    pub fn is_signing(&self) -> bool {
+        matches!(self, Self::Authentication | Self::Assertion)
+    }
+
+    /// Get the purpose a key needs to sign in the given context
+    ///
+    /// Challenge responses prove control of the agent and need an
+    /// authentication key. Every other context, including custom ones, makes
+    /// a statement on behalf of the agent and needs an assertion key.
+    pub fn for_context(context: &SigningContext) -> Self {
+        if context == &SigningContext::CHALLENGE {
+            Self::Authentication
+        } else {
+            Self::Assertion
+        }
+    }
+}
+
+/// A public key bound to an agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentKey {
+    /// The public key
+    pub public_key: PublicKey,
+    /// What the agent may use the key for
+    pub purposes: Vec<KeyPurpose>,
+}
+
+impl AgentKey {
+    /// Check if the key may be used for the given purpose
+    pub fn has_purpose(&self, purpose: KeyPurpose) -> bool {
+        self.purposes.contains(&purpose)
+    }
+}
+
 /// Represents an agent in the ACK ID system
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Agent {
@@ -25,6 +74,9 @@ pub struct Agent {
     /// Additional metadata for this agent
     #[serde(default)]
     metadata: serde_json::Value,
+    /// The public keys bound to this agent
+    #[serde(default)]
+    keys: Vec<AgentKey>,
 }
 
 impl Agent {
@@ -36,6 +88,7 @@ impl Agent {
             status: AgentStatus::default(),
             updated_at: Utc::now(),
             metadata: serde_json::json!({}),
+            keys: Vec::new(),
         })
     }
 
@@ -50,9 +103,30 @@ impl Agent {
             status: AgentStatus::default(),
             updated_at: Utc::now(),
             metadata: serde_json::json!({}),
+            keys: Vec::new(),
         })
     }
 
+    /// Create a new agent with a self-certifying ID derived from its inception key
+    ///
+    /// The key is bound to the agent for authentication and assertions.
+    pub fn from_inception_key(name: impl Into<String>, public_key: PublicKey) -> Result<Self> {
+        let mut agent = Self::new(name)?;
+        agent.id = AgentId::from_public_key(agent.id.name.clone(), public_key.to_bytes());
+        agent.add_key(
+            public_key,
+            vec![KeyPurpose::Authentication, KeyPurpose::Assertion],
+        )?;
+        Ok(agent)
+    }
+
+    /// Check that a presented public key is the inception key of this agent's ID
+    pub fn verify_inception_key(&self, public_key: &PublicKey) -> Result<()> {
+        self.id
+            .verify_key(public_key.to_bytes())
+            .map_err(|e| AgentIdError::InvalidAgentId(e.to_string()))
+    }
+
     /// Get the ID of this agent
     pub fn id(&self) -> &AgentId {
         &self.id
@@ -78,6 +152,56 @@ impl Agent {
         &self.metadata
     }
 
+    /// Get the public keys bound to this agent
+    pub fn keys(&self) -> &[AgentKey] {
+        &self.keys
+    }
+
+    /// Get the public keys the agent may use for the given purpose
+    pub fn keys_for(&self, purpose: KeyPurpose) -> impl Iterator<Item = &PublicKey> {
+        self.keys
+            .iter()
+            .filter(move |key| key.has_purpose(purpose))
+            .map(|key| &key.public_key)
+    }
+
+    /// Bind a public key to this agent
+    ///
+    /// Fails if the key is already bound or no purpose is given.
+    pub fn add_key(&mut self, public_key: PublicKey, purposes: Vec<KeyPurpose>) -> Result<()> {
+        if purposes.is_empty() {
+            return Err(AgentIdError::InvalidIdentityData(
+                "A key needs at least one purpose".into(),
+            ));
+        }
+        if self.keys.iter().any(|key| key.public_key == public_key) {
+            return Err(AgentIdError::InvalidIdentityData(format!(
+                "Key {} is already bound to this agent",
+                public_key.key_id()
+            )));
+        }
+
+        self.keys.push(AgentKey {
+            public_key,
+            purposes,
+        });
+        self.updated_at = Utc::now();
+        Ok(())
+    }
+
+    /// Unbind a public key from this agent
+    ///
+    /// Returns `false` if the key was not bound.
+    pub fn remove_key(&mut self, public_key: &PublicKey) -> Result<bool> {
+        let count = self.keys.len();
+        self.keys.retain(|key| &key.public_key != public_key);
+        if self.keys.len() == count {
+            return Ok(false);
+        }
+        self.updated_at = Utc::now();
+        Ok(true)
+    }
+
     /// Update the capabilities of this agent
     pub fn update_capabilities(&mut self, capabilities: AgentCapabilities) -> Result<()> {
         self.capabilities = capabilities;
@@ -242,4 +366,80 @@ mod tests {
         assert!(display.contains("Active"));
         assert!(display.contains("Updated:"));
     }
+
+    #[test]
+    fn test_agent_keys() {
+        let mut agent = Agent::new("test-agent").unwrap();
+        assert!(agent.keys().is_empty());
+
+        let signing = agentid_crypto::KeyPair::generate().unwrap();
+        let encryption = agentid_crypto::KeyPair::generate().unwrap();
+        agent
+            .add_key(
+                signing.public_key().clone(),
+                vec![KeyPurpose::Authentication, KeyPurpose::Assertion],
+            )
+            .unwrap();
+        agent
+            .add_key(
+                encryption.public_key().clone(),
+                vec![KeyPurpose::KeyAgreement],
+            )
+            .unwrap();
+
+        assert_eq!(
+            agent.keys_for(KeyPurpose::Assertion).collect::<Vec<_>>(),
+            vec![signing.public_key()]
+        );
+        assert_eq!(
+            agent.keys_for(KeyPurpose::KeyAgreement).collect::<Vec<_>>(),
+            vec![encryption.public_key()]
+        );
+
+        // Keys are bound once and need a purpose
+        assert!(agent
+            .add_key(signing.public_key().clone(), vec![KeyPurpose::Assertion])
+            .is_err());
+        let other = agentid_crypto::KeyPair::generate().unwrap();
+        assert!(agent.add_key(other.public_key().clone(), vec![]).is_err());
+
+        assert!(agent.remove_key(signing.public_key()).unwrap());
+        assert!(!agent.remove_key(signing.public_key()).unwrap());
+        assert_eq!(agent.keys().len(), 1);
+    }
+
+    #[test]
+    fn test_agent_without_keys_deserializes() {
+        let agent = Agent::new("test-agent").unwrap();
+        let mut json = serde_json::to_value(&agent).unwrap();
+        json.as_object_mut().unwrap().remove("keys");
+
+        let agent: Agent = serde_json::from_value(json).unwrap();
+        assert!(agent.keys().is_empty());
+    }
+
+    #[test]
+    fn test_agent_from_inception_key() {
+        let key_pair = agentid_crypto::KeyPair::generate().unwrap();
+        let agent = Agent::from_inception_key("test-agent", key_pair.public_key().clone()).unwrap();
+        assert!(agent.id().is_self_certifying());
+        assert_eq!(agent.id().name(), "test-agent");
+        assert_eq!(
+            agent
+                .keys_for(KeyPurpose::Authentication)
+                .collect::<Vec<_>>(),
+            vec![key_pair.public_key()]
+        );
+        agent.verify_inception_key(key_pair.public_key()).unwrap();
+
+        // Another host derives the same ID from the same key
+        let parsed: AgentId = agent.id().to_string().parse().unwrap();
+        assert_eq!(&parsed, agent.id());
+
+        let other = agentid_crypto::KeyPair::generate().unwrap();
+        assert!(matches!(
+            agent.verify_inception_key(other.public_key()),
+            Err(AgentIdError::InvalidAgentId(_))
+        ));
+    }
 }
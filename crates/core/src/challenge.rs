@@ -0,0 +1,433 @@
+//! Challenge-response proof of possession for the ACK ID protocol.
+//!
+//! A [`ChallengeVerifier`] issues a [`Challenge`] naming itself, the agent
+//! being challenged and an expiry. The agent signs the challenge in the
+//! [`SigningContext::CHALLENGE`] context and sends back a
+//! [`ChallengeResponse`]. The verifier checks the signature against the
+//! agent's public key and consumes the challenge from a [`NonceStore`], which
+//! keeps every issued challenge by nonce. A response is only accepted for the
+//! exact challenge that was issued, and at most once.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use agentid_crypto::{
+    sign_canonical, verify_canonical, DetachedSignature, PublicKey, Signer, SigningContext,
+};
+use agentid_types::AgentId;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{AgentIdError, Result};
+
+/// Default time an agent has to answer a challenge
+pub const DEFAULT_CHALLENGE_TTL_SECONDS: i64 = 300;
+
+/// A challenge issued by a verifier to an agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Challenge {
+    /// Random value identifying this challenge
+    pub nonce: String,
+    /// The agent that issued the challenge
+    pub verifier_id: AgentId,
+    /// The agent that has to answer the challenge
+    pub agent_id: AgentId,
+    /// When the challenge was issued
+    pub issued_at: DateTime<Utc>,
+    /// When the challenge stops being accepted
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Challenge {
+    /// Check if the challenge has expired at the given time
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Answer the challenge by signing it with the agent's key
+    pub async fn respond(&self, signer: &dyn Signer) -> Result<ChallengeResponse> {
+        let signature = sign_canonical(&SigningContext::CHALLENGE, self, signer)
+            .await
+            .map_err(|e| AgentIdError::Internal(format!("Failed to sign challenge: {}", e)))?;
+
+        Ok(ChallengeResponse {
+            challenge: self.clone(),
+            signature,
+        })
+    }
+}
+
+/// An agent's signed answer to a [`Challenge`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    /// The challenge being answered
+    pub challenge: Challenge,
+    /// The agent's signature over the challenge
+    pub signature: DetachedSignature,
+}
+
+/// Storage for outstanding challenges, keyed by nonce
+///
+/// Implementations shared between several verifier instances must make
+/// [`NonceStore::consume`] atomic, so that a nonce is only ever consumed once.
+#[async_trait]
+pub trait NonceStore: Send + Sync {
+    /// Record a newly issued challenge that can be consumed until it expires
+    async fn insert(&self, challenge: &Challenge) -> Result<()>;
+
+    /// Consume the challenge with the same nonce as `challenge`
+    ///
+    /// Returns `false` if the nonce was never issued, has already been
+    /// consumed or expired before `now`, or if the issued challenge differs
+    /// from `challenge` in any field. A differing challenge is left in the
+    /// store, so that it can still be answered by the agent it was issued to.
+    async fn consume(&self, challenge: &Challenge, now: DateTime<Utc>) -> Result<bool>;
+}
+
+/// Nonce store kept in process memory
+///
+/// Expired challenges are pruned whenever a new challenge is inserted.
+#[derive(Debug, Default)]
+pub struct InMemoryNonceStore {
+    challenges: Mutex<HashMap<String, Challenge>>,
+}
+
+impl InMemoryNonceStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of outstanding nonces, including expired ones not yet pruned
+    pub fn len(&self) -> usize {
+        self.lock()
+            .map(|challenges| challenges.len())
+            .unwrap_or_default()
+    }
+
+    /// Check if there are no outstanding nonces
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, Challenge>>> {
+        self.challenges
+            .lock()
+            .map_err(|_| AgentIdError::Internal("Nonce store lock poisoned".into()))
+    }
+}
+
+#[async_trait]
+impl NonceStore for InMemoryNonceStore {
+    async fn insert(&self, challenge: &Challenge) -> Result<()> {
+        let mut challenges = self.lock()?;
+        let now = Utc::now();
+        challenges.retain(|_, issued| !issued.is_expired_at(now));
+
+        if challenges
+            .insert(challenge.nonce.clone(), challenge.clone())
+            .is_some()
+        {
+            return Err(AgentIdError::Internal(format!(
+                "Nonce issued twice: {}",
+                challenge.nonce
+            )));
+        }
+        Ok(())
+    }
+
+    async fn consume(&self, challenge: &Challenge, now: DateTime<Utc>) -> Result<bool> {
+        let mut challenges = self.lock()?;
+        if challenges.get(&challenge.nonce) != Some(challenge) {
+            return Ok(false);
+        }
+        challenges.remove(&challenge.nonce);
+        Ok(!challenge.is_expired_at(now))
+    }
+}
+
+/// Issues challenges and verifies the responses
+pub struct ChallengeVerifier<S: NonceStore = InMemoryNonceStore> {
+    verifier_id: AgentId,
+    nonces: S,
+    ttl: Duration,
+}
+
+impl<S: NonceStore> ChallengeVerifier<S> {
+    /// Create a verifier acting as the given agent and keeping nonces in `nonces`
+    pub fn new(verifier_id: AgentId, nonces: S) -> Self {
+        Self {
+            verifier_id,
+            nonces,
+            ttl: Duration::seconds(DEFAULT_CHALLENGE_TTL_SECONDS),
+        }
+    }
+
+    /// Set how long issued challenges remain valid
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Get the ID of the verifying agent
+    pub fn verifier_id(&self) -> &AgentId {
+        &self.verifier_id
+    }
+
+    /// Get the nonce store
+    pub fn nonces(&self) -> &S {
+        &self.nonces
+    }
+
+    /// Issue a new challenge to the given agent
+    pub async fn issue(&self, agent_id: &AgentId) -> Result<Challenge> {
+        let issued_at = Utc::now();
+        let challenge = Challenge {
+            nonce: Uuid::new_v4().simple().to_string(),
+            verifier_id: self.verifier_id.clone(),
+            agent_id: agent_id.clone(),
+            issued_at,
+            expires_at: issued_at + self.ttl,
+        };
+
+        self.nonces.insert(&challenge).await?;
+        Ok(challenge)
+    }
+
+    /// Verify a response from the given agent and consume its challenge
+    ///
+    /// The challenge must have been issued by this verifier to `agent_id`,
+    /// must not have expired or been answered before, and must be signed in
+    /// the challenge context by `public_key`.
+    pub async fn verify(
+        &self,
+        response: &ChallengeResponse,
+        agent_id: &AgentId,
+        public_key: &PublicKey,
+    ) -> Result<()> {
+        let challenge = &response.challenge;
+        if challenge.verifier_id != self.verifier_id {
+            return Err(AgentIdError::VerificationFailed(
+                "Challenge was issued by another verifier".into(),
+            ));
+        }
+        if &challenge.agent_id != agent_id {
+            return Err(AgentIdError::VerificationFailed(
+                "Challenge was issued to another agent".into(),
+            ));
+        }
+
+        let now = Utc::now();
+        if challenge.is_expired_at(now) {
+            return Err(AgentIdError::ChallengeExpired(challenge.expires_at));
+        }
+
+        verify_canonical(
+            &SigningContext::CHALLENGE,
+            challenge,
+            &response.signature,
+            public_key,
+        )
+        .map_err(|e| AgentIdError::VerificationFailed(e.to_string()))?;
+
+        // Only consume the nonce for a valid signature over the challenge as
+        // issued, so that forged responses cannot burn the agent's challenge
+        if !self.nonces.consume(challenge, now).await? {
+            return Err(AgentIdError::ChallengeReplayed(challenge.nonce.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentid_crypto::{InMemorySigner, KeyPair};
+
+    struct Setup {
+        verifier: ChallengeVerifier,
+        agent_id: AgentId,
+        key_pair: KeyPair,
+        signer: InMemorySigner,
+    }
+
+    fn setup() -> Setup {
+        let key_pair = KeyPair::generate().unwrap();
+        Setup {
+            verifier: ChallengeVerifier::new(AgentId::new("verifier"), InMemoryNonceStore::new()),
+            agent_id: AgentId::new("agent"),
+            signer: InMemorySigner::new(key_pair.clone()),
+            key_pair,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_valid_response_is_accepted_once() {
+        let setup = setup();
+        let challenge = setup.verifier.issue(&setup.agent_id).await.unwrap();
+        assert_eq!(setup.verifier.nonces().len(), 1);
+
+        let response = challenge.respond(&setup.signer).await.unwrap();
+        setup
+            .verifier
+            .verify(&response, &setup.agent_id, setup.key_pair.public_key())
+            .await
+            .unwrap();
+        assert!(setup.verifier.nonces().is_empty());
+
+        // Replaying the same response fails
+        assert!(matches!(
+            setup
+                .verifier
+                .verify(&response, &setup.agent_id, setup.key_pair.public_key())
+                .await,
+            Err(AgentIdError::ChallengeReplayed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_expired_challenge_is_rejected() {
+        let setup = setup();
+        let verifier = setup.verifier.with_ttl(Duration::zero());
+        let challenge = verifier.issue(&setup.agent_id).await.unwrap();
+        let response = challenge.respond(&setup.signer).await.unwrap();
+
+        assert!(matches!(
+            verifier
+                .verify(&response, &setup.agent_id, setup.key_pair.public_key())
+                .await,
+            Err(AgentIdError::ChallengeExpired(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_extended_expiry_is_not_honored() {
+        let setup = setup();
+        let verifier = setup.verifier.with_ttl(Duration::zero());
+        let mut challenge = verifier.issue(&setup.agent_id).await.unwrap();
+
+        // The agent signs a copy with a later expiry, but the store keeps
+        // the expiry the verifier issued
+        challenge.expires_at = Utc::now() + Duration::hours(1);
+        let response = challenge.respond(&setup.signer).await.unwrap();
+        assert!(matches!(
+            verifier
+                .verify(&response, &setup.agent_id, setup.key_pair.public_key())
+                .await,
+            Err(AgentIdError::ChallengeReplayed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unissued_challenge_is_rejected() {
+        let setup = setup();
+        let challenge = Challenge {
+            nonce: "made-up".into(),
+            verifier_id: setup.verifier.verifier_id().clone(),
+            agent_id: setup.agent_id.clone(),
+            issued_at: Utc::now(),
+            expires_at: Utc::now() + Duration::minutes(1),
+        };
+        let response = challenge.respond(&setup.signer).await.unwrap();
+
+        assert!(matches!(
+            setup
+                .verifier
+                .verify(&response, &setup.agent_id, setup.key_pair.public_key())
+                .await,
+            Err(AgentIdError::ChallengeReplayed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_challenge_cannot_be_taken_over_by_another_agent() {
+        let setup = setup();
+        let challenge = setup.verifier.issue(&setup.agent_id).await.unwrap();
+
+        // Another agent copies the challenge, names itself and signs it
+        let other_agent = AgentId::new("other");
+        let other_key_pair = KeyPair::generate().unwrap();
+        let mut copied = challenge.clone();
+        copied.agent_id = other_agent.clone();
+        let response = copied
+            .respond(&InMemorySigner::new(other_key_pair.clone()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            setup
+                .verifier
+                .verify(&response, &other_agent, other_key_pair.public_key())
+                .await,
+            Err(AgentIdError::ChallengeReplayed(_))
+        ));
+
+        // The challenge is not burned for the agent it was issued to
+        let response = challenge.respond(&setup.signer).await.unwrap();
+        setup
+            .verifier
+            .verify(&response, &setup.agent_id, setup.key_pair.public_key())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wrong_key_does_not_consume_nonce() {
+        let setup = setup();
+        let challenge = setup.verifier.issue(&setup.agent_id).await.unwrap();
+        let impostor = InMemorySigner::new(KeyPair::generate().unwrap());
+
+        let forged = challenge.respond(&impostor).await.unwrap();
+        assert!(matches!(
+            setup
+                .verifier
+                .verify(&forged, &setup.agent_id, setup.key_pair.public_key())
+                .await,
+            Err(AgentIdError::VerificationFailed(_))
+        ));
+
+        // The genuine agent can still answer
+        let response = challenge.respond(&setup.signer).await.unwrap();
+        setup
+            .verifier
+            .verify(&response, &setup.agent_id, setup.key_pair.public_key())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_response_is_bound_to_agent_and_verifier() {
+        let setup = setup();
+        let challenge = setup.verifier.issue(&setup.agent_id).await.unwrap();
+        let response = challenge.respond(&setup.signer).await.unwrap();
+
+        let other_agent = AgentId::new("agent");
+        assert!(setup
+            .verifier
+            .verify(&response, &other_agent, setup.key_pair.public_key())
+            .await
+            .is_err());
+
+        let other_verifier =
+            ChallengeVerifier::new(AgentId::new("verifier"), InMemoryNonceStore::new());
+        assert!(other_verifier
+            .verify(&response, &setup.agent_id, setup.key_pair.public_key())
+            .await
+            .is_err());
+
+        // A signature in another context does not answer the challenge
+        let mut wrong_context = response.clone();
+        wrong_context.signature =
+            sign_canonical(&SigningContext::ATTESTATION, &challenge, &setup.signer)
+                .await
+                .unwrap();
+        assert!(matches!(
+            setup
+                .verifier
+                .verify(&wrong_context, &setup.agent_id, setup.key_pair.public_key())
+                .await,
+            Err(AgentIdError::VerificationFailed(_))
+        ));
+    }
+}
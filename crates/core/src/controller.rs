@@ -0,0 +1,291 @@
+//! Controller credentials linking agents to their owners.
+//!
+//! In ACK ID an owner, a person or organisation, vouches for an agent by
+//! issuing a `ControllerCredential` whose subject is the agent's DID and
+//! whose `controller` claim is the owner's DID. Owners can themselves be
+//! vouched for, up to a trust anchor: a [`ControllerChainVerifier`] follows
+//! these links from an agent until it reaches one of its anchors.
+
+use std::collections::HashSet;
+
+use agentid_crypto::{PublicKey, Signer};
+use agentid_types::AgentId;
+use chrono::{DateTime, Utc};
+
+use crate::credentials::{
+    CredentialError, CredentialResult, CredentialSubject, VerifiableCredential,
+};
+use crate::did::DidResolver;
+
+/// Credential type of controller credentials
+pub const CONTROLLER_CREDENTIAL_TYPE: &str = "ControllerCredential";
+
+/// Claim naming the owner in the credential subject
+const CONTROLLER_CLAIM: &str = "controller";
+
+/// Default number of links a controller chain may have
+pub const DEFAULT_MAX_CHAIN_LENGTH: usize = 8;
+
+/// A verified link from an agent to its owner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerClaim {
+    /// The agent's DID
+    pub agent: String,
+    /// The owner's DID
+    pub owner: String,
+    /// The owner's key that signed the credential
+    pub owner_key: PublicKey,
+}
+
+impl ControllerClaim {
+    /// Get the self-certifying ID of the owner, derived from the signing key
+    pub fn owner_id(&self) -> AgentId {
+        AgentId::from_public_key(self.owner.clone(), self.owner_key.to_bytes())
+    }
+}
+
+/// Create an unsigned controller credential from `owner` to `agent`
+pub fn controller_credential(owner: &str, agent: &str) -> VerifiableCredential {
+    VerifiableCredential::new(
+        owner,
+        CredentialSubject::new(agent).with_claim(CONTROLLER_CLAIM, owner.into()),
+    )
+    .with_type(CONTROLLER_CREDENTIAL_TYPE)
+}
+
+/// Issue a controller credential from `owner` to `agent`
+///
+/// `verification_method` is the DID URL of the owner's key used by `signer`.
+pub async fn issue_controller_credential(
+    owner: &str,
+    verification_method: impl Into<String>,
+    agent: &str,
+    signer: &dyn Signer,
+) -> CredentialResult<VerifiableCredential> {
+    controller_credential(owner, agent)
+        .issue(verification_method, signer)
+        .await
+}
+
+/// Verify a controller credential as of `now`
+///
+/// On top of [`VerifiableCredential::verify_at`], the credential must have
+/// the controller type, a subject DID, and name its issuer as controller.
+pub async fn verify_controller_credential(
+    credential: &VerifiableCredential,
+    resolver: &dyn DidResolver,
+    now: DateTime<Utc>,
+) -> CredentialResult<ControllerClaim> {
+    let not_controller = |reason: &str| CredentialError::NotAControllerCredential(reason.into());
+
+    if !credential.has_type(CONTROLLER_CREDENTIAL_TYPE) {
+        return Err(not_controller("Missing ControllerCredential type"));
+    }
+    let agent = credential
+        .credential_subject
+        .id
+        .clone()
+        .ok_or_else(|| not_controller("The subject has no id"))?;
+    let owner = credential.issuer.id();
+    let controller = credential.credential_subject.claims.get(CONTROLLER_CLAIM);
+    if controller.and_then(|c| c.as_str()) != Some(owner) {
+        return Err(not_controller("The controller is not the issuer"));
+    }
+    if agent == owner {
+        return Err(not_controller("The agent controls itself"));
+    }
+
+    let owner_key = credential.verify_at(resolver, now).await?;
+    Ok(ControllerClaim {
+        agent,
+        owner: owner.to_string(),
+        owner_key,
+    })
+}
+
+/// Follows controller credentials from an agent to a trust anchor
+#[derive(Debug, Clone)]
+pub struct ControllerChainVerifier {
+    anchors: HashSet<String>,
+    max_length: usize,
+}
+
+impl ControllerChainVerifier {
+    /// Create a verifier trusting the given owner DIDs
+    pub fn new(anchors: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            anchors: anchors.into_iter().map(Into::into).collect(),
+            max_length: DEFAULT_MAX_CHAIN_LENGTH,
+        }
+    }
+
+    /// Set the maximum number of links between an agent and its anchor
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Check if a DID is a trust anchor
+    pub fn is_anchor(&self, did: &str) -> bool {
+        self.anchors.contains(did)
+    }
+
+    /// Walk from `agent` to a trust anchor using the given credentials
+    ///
+    /// Returns the verified links of the shortest chain, from the agent's
+    /// owner to the anchor. Credentials that do not verify are ignored, so a
+    /// chain is only found if every link has a valid credential.
+    pub async fn verify(
+        &self,
+        agent: &str,
+        credentials: &[VerifiableCredential],
+        resolver: &dyn DidResolver,
+    ) -> CredentialResult<Vec<ControllerClaim>> {
+        let now = Utc::now();
+        let mut links = Vec::new();
+        for credential in credentials {
+            if let Ok(claim) = verify_controller_credential(credential, resolver, now).await {
+                links.push(claim);
+            }
+        }
+
+        // Breadth-first from the agent, never revisiting a DID
+        let mut visited = HashSet::from([agent]);
+        let mut frontier: Vec<(&str, Vec<&ControllerClaim>)> = vec![(agent, Vec::new())];
+        for _ in 0..self.max_length {
+            let mut next = Vec::new();
+            for (did, path) in &frontier {
+                for claim in links.iter().filter(|claim| claim.agent == *did) {
+                    if !visited.insert(claim.owner.as_str()) {
+                        continue;
+                    }
+                    let mut path = path.clone();
+                    path.push(claim);
+                    if self.is_anchor(&claim.owner) {
+                        return Ok(path.into_iter().cloned().collect());
+                    }
+                    next.push((claim.owner.as_str(), path));
+                }
+            }
+            frontier = next;
+        }
+
+        Err(CredentialError::UntrustedController(agent.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::DidKeyResolver;
+    use crate::test_utils::{party, vouch};
+
+    #[tokio::test]
+    async fn test_controller_credential() {
+        let (owner, agent) = (party(), party());
+        let credential = vouch(&owner, &agent).await;
+
+        let claim = verify_controller_credential(&credential, &DidKeyResolver, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(claim.agent, agent.did.as_str());
+        assert_eq!(claim.owner, owner.did.as_str());
+        assert_eq!(&claim.owner_key, owner.did.public_key());
+        assert!(claim
+            .owner_id()
+            .verify_key(owner.did.public_key().to_bytes())
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_controller_must_be_issuer() {
+        let (owner, agent, other) = (party(), party(), party());
+        let credential = controller_credential(owner.did.as_str(), agent.did.as_str());
+
+        let mut wrong_controller = credential.clone();
+        wrong_controller
+            .credential_subject
+            .claims
+            .insert(CONTROLLER_CLAIM.into(), other.did.as_str().into());
+        let wrong_controller = wrong_controller
+            .issue(owner.did.verification_method_id(), &owner.signer)
+            .await
+            .unwrap();
+
+        let mut untyped = credential;
+        untyped.types.retain(|t| t != CONTROLLER_CREDENTIAL_TYPE);
+        let untyped = untyped
+            .issue(owner.did.verification_method_id(), &owner.signer)
+            .await
+            .unwrap();
+
+        for credential in [wrong_controller, untyped] {
+            assert!(matches!(
+                verify_controller_credential(&credential, &DidKeyResolver, Utc::now()).await,
+                Err(CredentialError::NotAControllerCredential(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chain_to_trust_anchor() {
+        let (agent, owner, anchor) = (party(), party(), party());
+        let credentials = vec![vouch(&anchor, &owner).await, vouch(&owner, &agent).await];
+
+        let verifier = ControllerChainVerifier::new([anchor.did.as_str()]);
+        let chain = verifier
+            .verify(agent.did.as_str(), &credentials, &DidKeyResolver)
+            .await
+            .unwrap();
+        let owners: Vec<_> = chain.iter().map(|claim| claim.owner.as_str()).collect();
+        assert_eq!(owners, vec![owner.did.as_str(), anchor.did.as_str()]);
+
+        // Too long for the limit
+        assert!(matches!(
+            verifier
+                .clone()
+                .with_max_length(1)
+                .verify(agent.did.as_str(), &credentials, &DidKeyResolver)
+                .await,
+            Err(CredentialError::UntrustedController(_))
+        ));
+
+        // Not anchored
+        let untrusted = ControllerChainVerifier::new([party().did.as_str()]);
+        assert!(untrusted
+            .verify(agent.did.as_str(), &credentials, &DidKeyResolver)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chain_skips_invalid_links() {
+        let (agent, owner, anchor) = (party(), party(), party());
+
+        // A cycle between the agent and the owner never reaches the anchor
+        let cycle = vec![vouch(&owner, &agent).await, vouch(&agent, &owner).await];
+        let verifier = ControllerChainVerifier::new([anchor.did.as_str()]);
+        assert!(verifier
+            .verify(agent.did.as_str(), &cycle, &DidKeyResolver)
+            .await
+            .is_err());
+
+        // A forged shortcut is ignored in favour of the valid chain
+        let mut forged = vouch(&anchor, &agent).await;
+        forged
+            .credential_subject
+            .claims
+            .insert("note".into(), "forged".into());
+        let credentials = vec![
+            forged,
+            vouch(&owner, &agent).await,
+            vouch(&anchor, &owner).await,
+        ];
+        let chain = verifier
+            .verify(agent.did.as_str(), &credentials, &DidKeyResolver)
+            .await
+            .unwrap();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].owner, owner.did.as_str());
+    }
+}
@@ -0,0 +1,707 @@
+//! W3C Verifiable Credentials (Data Model 2.0).
+//!
+//! A [`VerifiableCredential`] states claims about a [`CredentialSubject`] on
+//! behalf of an issuer DID. [`VerifiableCredential::issue`] secures it with a
+//! [`DataIntegrityProof`] using the `eddsa-jcs-2022` cryptosuite: the
+//! credential and the proof options are canonicalized with RFC 8785 (JCS),
+//! hashed with SHA-256 and signed with the issuer's Ed25519 key.
+//!
+//! [`VerifiableCredential::verify`] resolves the issuer's DID Document,
+//! checks that the signing key is one of the issuer's assertion methods,
+//! then checks the signature and the validity window. Every failure is a
+//! distinct [`CredentialError`].
+//!
+//! `credentialStatus` is carried but not checked here; consult the issuer's
+//! status list before relying on a credential that has one.
+
+use agentid_crypto::{canonicalize, PublicKey, Signature, Signer};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::did::DidResolver;
+
+/// JSON-LD context of Verifiable Credentials 2.0
+pub const CREDENTIALS_V2_CONTEXT: &str = "https://www.w3.org/ns/credentials/v2";
+
+/// Base type of every credential
+pub const VERIFIABLE_CREDENTIAL_TYPE: &str = "VerifiableCredential";
+
+/// Proof type of Data Integrity proofs
+pub const DATA_INTEGRITY_PROOF_TYPE: &str = "DataIntegrityProof";
+
+/// The Data Integrity cryptosuite used for proofs
+pub const EDDSA_JCS_2022: &str = "eddsa-jcs-2022";
+
+/// Proof purpose of statements made by the issuer
+pub const ASSERTION_METHOD_PURPOSE: &str = "assertionMethod";
+
+/// Proof purpose of proving control of a DID
+pub const AUTHENTICATION_PURPOSE: &str = "authentication";
+
+/// Errors that can occur when issuing or verifying credentials
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CredentialError {
+    #[error("Credential does not conform to the data model: {0}")]
+    InvalidSchema(String),
+    #[error("Credential has no proof")]
+    MissingProof,
+    #[error("Unsupported proof: {0}")]
+    UnsupportedProof(String),
+    #[error("Verification method {verification_method} is not controlled by {controller}")]
+    ControllerMismatch {
+        verification_method: String,
+        controller: String,
+    },
+    #[error("Failed to resolve DID: {0}")]
+    DidResolution(String),
+    #[error("Verification method {0} is not authorized for this proof purpose")]
+    UnauthorizedVerificationMethod(String),
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Credential is not valid before {0}")]
+    NotYetValid(DateTime<Utc>),
+    #[error("Credential expired at {0}")]
+    Expired(DateTime<Utc>),
+    #[error("Failed to sign: {0}")]
+    Signing(String),
+    #[error("Credential is not about the holder {0}")]
+    NotAboutHolder(String),
+    #[error("Proof does not answer the expected challenge")]
+    ChallengeMismatch,
+    #[error("Proof is not for the expected domain")]
+    DomainMismatch,
+    #[error("Not a controller credential: {0}")]
+    NotAControllerCredential(String),
+    #[error("No trusted controller chain for {0}")]
+    UntrustedController(String),
+}
+
+/// Result type for credential operations
+pub type CredentialResult<T> = std::result::Result<T, CredentialError>;
+
+/// The issuer of a credential: a bare DID or an object with an `id`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Issuer {
+    /// The issuer's DID
+    Id(String),
+    /// The issuer's DID with a display name
+    Object {
+        /// The issuer's DID
+        id: String,
+        /// A display name of the issuer
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+}
+
+impl Issuer {
+    /// Get the issuer's DID
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Id(id) | Self::Object { id, .. } => id,
+        }
+    }
+}
+
+impl From<&str> for Issuer {
+    fn from(id: &str) -> Self {
+        Self::Id(id.to_string())
+    }
+}
+
+impl From<String> for Issuer {
+    fn from(id: String) -> Self {
+        Self::Id(id)
+    }
+}
+
+/// The entity a credential makes claims about
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CredentialSubject {
+    /// The subject's DID, if the subject has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The claims about the subject
+    #[serde(flatten)]
+    pub claims: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CredentialSubject {
+    /// Create a subject identified by a DID, without claims
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: Some(id.into()),
+            claims: serde_json::Map::new(),
+        }
+    }
+
+    /// Add a claim
+    pub fn with_claim(mut self, name: impl Into<String>, value: serde_json::Value) -> Self {
+        self.claims.insert(name.into(), value);
+        self
+    }
+}
+
+/// Where to look up whether a credential has been revoked or suspended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CredentialStatus {
+    /// The URL of this status entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The status mechanism, e.g. `BitstringStatusListEntry`
+    #[serde(rename = "type")]
+    pub status_type: String,
+    /// Mechanism-specific properties, e.g. `statusListIndex`
+    #[serde(flatten)]
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A schema the credential conforms to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CredentialSchema {
+    /// The URL of the schema
+    pub id: String,
+    /// The schema language, e.g. `JsonSchema`
+    #[serde(rename = "type")]
+    pub schema_type: String,
+}
+
+/// A Data Integrity proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataIntegrityProof {
+    /// Always `DataIntegrityProof`
+    #[serde(rename = "type")]
+    pub proof_type: String,
+    /// The cryptosuite, `eddsa-jcs-2022`
+    pub cryptosuite: String,
+    /// When the proof was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+    /// The DID URL of the signing key
+    pub verification_method: String,
+    /// Why the proof was made, e.g. `assertionMethod`
+    pub proof_purpose: String,
+    /// The verifier-supplied challenge the proof answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+    /// The domain the proof is meant for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// The multibase-encoded signature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof_value: Option<String>,
+}
+
+impl DataIntegrityProof {
+    /// Create unsigned proof options for the given key and purpose
+    pub fn new(verification_method: impl Into<String>, proof_purpose: impl Into<String>) -> Self {
+        Self {
+            proof_type: DATA_INTEGRITY_PROOF_TYPE.into(),
+            cryptosuite: EDDSA_JCS_2022.into(),
+            created: Some(Utc::now()),
+            verification_method: verification_method.into(),
+            proof_purpose: proof_purpose.into(),
+            challenge: None,
+            domain: None,
+            proof_value: None,
+        }
+    }
+
+    /// Get the DID controlling the signing key
+    pub fn controller(&self) -> &str {
+        self.verification_method
+            .split_once('#')
+            .map_or(self.verification_method.as_str(), |(did, _)| did)
+    }
+
+    /// Sign a document, returning the proof with its `proofValue` set
+    pub(crate) async fn sign<T: Serialize>(
+        mut self,
+        document: &T,
+        signer: &dyn Signer,
+    ) -> CredentialResult<Self> {
+        self.proof_value = None;
+        let signature = signer
+            .sign(&self.hash_data(document)?)
+            .await
+            .map_err(|e| CredentialError::Signing(e.to_string()))?;
+
+        self.proof_value = Some(format!(
+            "z{}",
+            bs58::encode(signature.as_bytes()).into_string()
+        ));
+        Ok(self)
+    }
+
+    /// Check that this proof over `document` was made by `controller` for
+    /// `purpose`, resolving the signing key with `resolver`
+    ///
+    /// Returns the key that made the proof.
+    pub(crate) async fn verify<T: Serialize>(
+        &self,
+        document: &T,
+        controller: &str,
+        purpose: &str,
+        resolver: &dyn DidResolver,
+    ) -> CredentialResult<PublicKey> {
+        if self.proof_type != DATA_INTEGRITY_PROOF_TYPE || self.cryptosuite != EDDSA_JCS_2022 {
+            return Err(CredentialError::UnsupportedProof(format!(
+                "{} with {}",
+                self.proof_type, self.cryptosuite
+            )));
+        }
+        if self.proof_purpose != purpose {
+            return Err(CredentialError::UnsupportedProof(format!(
+                "Proof purpose is {} instead of {}",
+                self.proof_purpose, purpose
+            )));
+        }
+
+        let public_key = self.resolve_key(controller, purpose, resolver).await?;
+        let signature = self
+            .proof_value
+            .as_deref()
+            .and_then(|value| value.strip_prefix('z'))
+            .and_then(|value| bs58::decode(value).into_vec().ok())
+            .and_then(|bytes| Signature::from_bytes(&bytes).ok())
+            .ok_or(CredentialError::InvalidSignature)?;
+
+        match signature.verify(&self.hash_data(document)?, &public_key) {
+            Ok(true) => Ok(public_key),
+            _ => Err(CredentialError::InvalidSignature),
+        }
+    }
+
+    /// Find the signing key among the controller's keys for `purpose`
+    async fn resolve_key(
+        &self,
+        controller: &str,
+        purpose: &str,
+        resolver: &dyn DidResolver,
+    ) -> CredentialResult<PublicKey> {
+        if self.controller() != controller {
+            return Err(CredentialError::ControllerMismatch {
+                verification_method: self.verification_method.clone(),
+                controller: controller.to_string(),
+            });
+        }
+
+        let document = resolver
+            .resolve(controller)
+            .await
+            .map_err(|e| CredentialError::DidResolution(e.to_string()))?;
+        let relationship = match purpose {
+            ASSERTION_METHOD_PURPOSE => &document.assertion_method,
+            AUTHENTICATION_PURPOSE => &document.authentication,
+            _ => return Err(CredentialError::UnsupportedProof(purpose.to_string())),
+        };
+
+        document
+            .authorized_method(relationship, &self.verification_method)
+            .and_then(|method| method.public_key().ok())
+            .ok_or_else(|| {
+                CredentialError::UnauthorizedVerificationMethod(self.verification_method.clone())
+            })
+    }
+
+    /// Hash the proof options and the document as `eddsa-jcs-2022` specifies
+    fn hash_data<T: Serialize>(&self, document: &T) -> CredentialResult<Vec<u8>> {
+        let to_object = |value: serde_json::Result<serde_json::Value>| match value {
+            Ok(serde_json::Value::Object(object)) => Ok(object),
+            _ => Err(CredentialError::InvalidSchema(
+                "Document is not a JSON object".into(),
+            )),
+        };
+
+        let mut unsecured = to_object(serde_json::to_value(document))?;
+        unsecured.remove("proof");
+        let mut config = to_object(serde_json::to_value(self))?;
+        config.remove("proofValue");
+        if let Some(context) = unsecured.get("@context") {
+            config.insert("@context".into(), context.clone());
+        }
+
+        let canonical = |object| {
+            canonicalize(&serde_json::Value::Object(object))
+                .map_err(|e| CredentialError::InvalidSchema(e.to_string()))
+        };
+        let mut hash = Sha256::digest(canonical(config)?).to_vec();
+        hash.extend_from_slice(&Sha256::digest(canonical(unsecured)?));
+        Ok(hash)
+    }
+}
+
+/// A Verifiable Credential
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiableCredential {
+    /// The JSON-LD contexts, starting with [`CREDENTIALS_V2_CONTEXT`]
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    /// The credential's identifier
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The credential types, including [`VERIFIABLE_CREDENTIAL_TYPE`]
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+    /// Who issued the credential
+    pub issuer: Issuer,
+    /// When the credential becomes valid
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// When the credential stops being valid
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<DateTime<Utc>>,
+    /// The claims
+    pub credential_subject: CredentialSubject,
+    /// Where to check for revocation or suspension
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_status: Option<CredentialStatus>,
+    /// The schema of the claims
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_schema: Option<CredentialSchema>,
+    /// The issuer's proof, set by [`VerifiableCredential::issue`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof: Option<DataIntegrityProof>,
+}
+
+impl VerifiableCredential {
+    /// Create an unsigned credential, valid from now
+    pub fn new(issuer: impl Into<Issuer>, subject: CredentialSubject) -> Self {
+        Self {
+            context: vec![CREDENTIALS_V2_CONTEXT.into()],
+            id: Some(format!("urn:uuid:{}", Uuid::new_v4())),
+            types: vec![VERIFIABLE_CREDENTIAL_TYPE.into()],
+            issuer: issuer.into(),
+            valid_from: Some(Utc::now()),
+            valid_until: None,
+            credential_subject: subject,
+            credential_status: None,
+            credential_schema: None,
+            proof: None,
+        }
+    }
+
+    /// Add a credential type
+    pub fn with_type(mut self, credential_type: impl Into<String>) -> Self {
+        self.types.push(credential_type.into());
+        self
+    }
+
+    /// Set when the credential becomes valid
+    pub fn with_valid_from(mut self, valid_from: DateTime<Utc>) -> Self {
+        self.valid_from = Some(valid_from);
+        self
+    }
+
+    /// Set when the credential stops being valid
+    pub fn with_valid_until(mut self, valid_until: DateTime<Utc>) -> Self {
+        self.valid_until = Some(valid_until);
+        self
+    }
+
+    /// Set where to check for revocation or suspension
+    pub fn with_status(mut self, status: CredentialStatus) -> Self {
+        self.credential_status = Some(status);
+        self
+    }
+
+    /// Set the schema of the claims
+    pub fn with_schema(mut self, schema: CredentialSchema) -> Self {
+        self.credential_schema = Some(schema);
+        self
+    }
+
+    /// Check if the credential has the given type
+    pub fn has_type(&self, credential_type: &str) -> bool {
+        self.types.iter().any(|t| t == credential_type)
+    }
+
+    /// Sign the credential with the issuer's key
+    ///
+    /// `verification_method` is the DID URL of the key used by `signer` and
+    /// must belong to the issuer. Any previous proof is replaced.
+    pub async fn issue(
+        mut self,
+        verification_method: impl Into<String>,
+        signer: &dyn Signer,
+    ) -> CredentialResult<Self> {
+        self.check_schema()?;
+        let options = DataIntegrityProof::new(verification_method, ASSERTION_METHOD_PURPOSE);
+        if options.controller() != self.issuer.id() {
+            return Err(CredentialError::ControllerMismatch {
+                verification_method: options.verification_method,
+                controller: self.issuer.id().to_string(),
+            });
+        }
+
+        self.proof = None;
+        self.proof = Some(options.sign(&self, signer).await?);
+        Ok(self)
+    }
+
+    /// Verify the credential now, returning the issuer's key that signed it
+    pub async fn verify(&self, resolver: &dyn DidResolver) -> CredentialResult<PublicKey> {
+        self.verify_at(resolver, Utc::now()).await
+    }
+
+    /// Verify the credential as of `now`, returning the issuer's key that signed it
+    ///
+    /// Checks, in order: conformance to the data model, that the proof was
+    /// made by one of the issuer's assertion methods, the signature, and
+    /// the validity window.
+    pub async fn verify_at(
+        &self,
+        resolver: &dyn DidResolver,
+        now: DateTime<Utc>,
+    ) -> CredentialResult<PublicKey> {
+        self.check_schema()?;
+        let proof = self.proof.as_ref().ok_or(CredentialError::MissingProof)?;
+        let issuer_key = proof
+            .verify(self, self.issuer.id(), ASSERTION_METHOD_PURPOSE, resolver)
+            .await?;
+
+        if let Some(valid_from) = self.valid_from {
+            if now < valid_from {
+                return Err(CredentialError::NotYetValid(valid_from));
+            }
+        }
+        if let Some(valid_until) = self.valid_until {
+            if now >= valid_until {
+                return Err(CredentialError::Expired(valid_until));
+            }
+        }
+        Ok(issuer_key)
+    }
+
+    /// Check the credential against the data model's structural requirements
+    fn check_schema(&self) -> CredentialResult<()> {
+        let invalid = |reason: &str| Err(CredentialError::InvalidSchema(reason.into()));
+
+        if self.context.first().map(String::as_str) != Some(CREDENTIALS_V2_CONTEXT) {
+            return invalid("The first @context must be the credentials v2 context");
+        }
+        if !self.has_type(VERIFIABLE_CREDENTIAL_TYPE) {
+            return invalid("The type must include VerifiableCredential");
+        }
+        if !self.issuer.id().contains(':') {
+            return invalid("The issuer must be a URL");
+        }
+        if self.credential_subject.id.is_none() && self.credential_subject.claims.is_empty() {
+            return invalid("The credential subject must have an id or claims");
+        }
+        if let (Some(valid_from), Some(valid_until)) = (self.valid_from, self.valid_until) {
+            if valid_until < valid_from {
+                return invalid("validUntil is before validFrom");
+            }
+        }
+        if self
+            .credential_status
+            .as_ref()
+            .is_some_and(|status| status.status_type.is_empty())
+        {
+            return invalid("The credential status must have a type");
+        }
+        if self
+            .credential_schema
+            .as_ref()
+            .is_some_and(|schema| schema.id.is_empty() || schema.schema_type.is_empty())
+        {
+            return invalid("The credential schema must have an id and a type");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::DidKeyResolver;
+    use crate::did_web::{DidWeb, DidWebResolver, FixtureFetcher, HttpResponse};
+    use crate::test_utils::{party, Party};
+    use chrono::Duration;
+
+    fn credential(issuer: &Party) -> VerifiableCredential {
+        VerifiableCredential::new(
+            issuer.did.as_str(),
+            CredentialSubject::new("did:web:shop.example:agents:buyer").with_claim(
+                "spendingLimit",
+                serde_json::json!({"amount": 500, "currency": "USD"}),
+            ),
+        )
+        .with_type("AgentSpendingCredential")
+    }
+
+    #[tokio::test]
+    async fn test_issue_and_verify() {
+        let issuer = party();
+        let credential = credential(&issuer)
+            .with_valid_until(Utc::now() + Duration::days(30))
+            .issue(issuer.did.verification_method_id(), &issuer.signer)
+            .await
+            .unwrap();
+        credential.verify(&DidKeyResolver).await.unwrap();
+
+        let json = serde_json::to_value(&credential).unwrap();
+        assert_eq!(json["@context"][0], CREDENTIALS_V2_CONTEXT);
+        assert_eq!(json["issuer"], issuer.did.as_str());
+        assert_eq!(json["credentialSubject"]["spendingLimit"]["amount"], 500);
+        assert_eq!(json["proof"]["cryptosuite"], EDDSA_JCS_2022);
+        assert_eq!(json["proof"]["proofPurpose"], "assertionMethod");
+        assert!(json["proof"]["proofValue"]
+            .as_str()
+            .unwrap()
+            .starts_with('z'));
+
+        // The proof survives a round trip through JSON
+        let parsed: VerifiableCredential = serde_json::from_value(json).unwrap();
+        parsed.verify(&DidKeyResolver).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tampered_credential_is_rejected() {
+        let issuer = party();
+        let credential = credential(&issuer)
+            .issue(issuer.did.verification_method_id(), &issuer.signer)
+            .await
+            .unwrap();
+
+        let mut tampered = credential.clone();
+        tampered
+            .credential_subject
+            .claims
+            .insert("spendingLimit".into(), serde_json::json!({"amount": 50000}));
+        assert_eq!(
+            tampered.verify(&DidKeyResolver).await,
+            Err(CredentialError::InvalidSignature)
+        );
+
+        let mut extended = credential.clone();
+        extended.valid_until = Some(Utc::now() + Duration::days(3650));
+        assert_eq!(
+            extended.verify(&DidKeyResolver).await,
+            Err(CredentialError::InvalidSignature)
+        );
+
+        let mut unsigned = credential;
+        unsigned.proof = None;
+        assert_eq!(
+            unsigned.verify(&DidKeyResolver).await,
+            Err(CredentialError::MissingProof)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validity_window_is_enforced() {
+        let issuer = party();
+        let now = Utc::now();
+        let credential = credential(&issuer)
+            .with_valid_from(now + Duration::days(1))
+            .with_valid_until(now + Duration::days(2))
+            .issue(issuer.did.verification_method_id(), &issuer.signer)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            credential.verify_at(&DidKeyResolver, now).await,
+            Err(CredentialError::NotYetValid(_))
+        ));
+        credential
+            .verify_at(&DidKeyResolver, now + Duration::hours(36))
+            .await
+            .unwrap();
+        assert!(matches!(
+            credential
+                .verify_at(&DidKeyResolver, now + Duration::days(2))
+                .await,
+            Err(CredentialError::Expired(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_issuer_key_is_checked() {
+        let issuer = party();
+        let impostor = party();
+
+        // Signing with someone else's key for the issuer
+        let result = credential(&issuer)
+            .issue(impostor.did.verification_method_id(), &impostor.signer)
+            .await;
+        assert!(matches!(
+            result,
+            Err(CredentialError::ControllerMismatch { .. })
+        ));
+
+        // Naming the issuer's key but signing with another
+        let forged = credential(&issuer)
+            .issue(issuer.did.verification_method_id(), &impostor.signer)
+            .await
+            .unwrap();
+        assert_eq!(
+            forged.verify(&DidKeyResolver).await,
+            Err(CredentialError::InvalidSignature)
+        );
+
+        // A did:web issuer whose key is only listed for authentication
+        let did: DidWeb = "did:web:owner.example".parse().unwrap();
+        let mut document = issuer.did.document();
+        document.id = did.to_string();
+        document.verification_method[0].id = format!("{}#key-1", did);
+        document.verification_method[0].controller = did.to_string();
+        document.authentication = vec![crate::did::VerificationRelationship::Reference(
+            "#key-1".into(),
+        )];
+        document.assertion_method.clear();
+        document.capability_invocation.clear();
+        document.capability_delegation.clear();
+        let fetcher = FixtureFetcher::new();
+        fetcher.insert(
+            did.document_url().clone(),
+            HttpResponse::ok(serde_json::to_vec(&document).unwrap()),
+        );
+
+        let credential =
+            VerifiableCredential::new(did.as_str(), CredentialSubject::new(impostor.did.as_str()))
+                .issue(format!("{}#key-1", did), &issuer.signer)
+                .await
+                .unwrap();
+        assert!(matches!(
+            credential.verify(&DidWebResolver::new(fetcher)).await,
+            Err(CredentialError::UnauthorizedVerificationMethod(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_schema_is_checked() {
+        let issuer = party();
+        let now = Utc::now();
+
+        let mut wrong_context = credential(&issuer);
+        wrong_context.context = vec!["https://www.w3.org/2018/credentials/v1".into()];
+        let backwards = credential(&issuer)
+            .with_valid_from(now)
+            .with_valid_until(now - Duration::days(1));
+        let mut untyped = credential(&issuer);
+        untyped.types = vec!["AgentSpendingCredential".into()];
+        let empty_subject = VerifiableCredential::new(
+            issuer.did.as_str(),
+            CredentialSubject {
+                id: None,
+                claims: serde_json::Map::new(),
+            },
+        );
+
+        for credential in [wrong_context, backwards, untyped, empty_subject] {
+            assert!(matches!(
+                credential
+                    .issue(issuer.did.verification_method_id(), &issuer.signer)
+                    .await,
+                Err(CredentialError::InvalidSchema(_))
+            ));
+        }
+    }
+}
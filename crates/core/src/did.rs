@@ -0,0 +1,576 @@
+//! Decentralized identifiers (DIDs) for agents.
+//!
+//! A [`DidKey`] encodes an Ed25519 [`PublicKey`] directly in the identifier
+//! (`did:key:z6Mk...`), so any counterparty can resolve it to a
+//! [`DidDocument`] without a registry. The key is the multicodec
+//! `ed25519-pub` prefix followed by the raw key, encoded as multibase
+//! base58btc.
+
+use std::fmt;
+use std::str::FromStr;
+
+use agentid_crypto::{KeyAlgorithm, PublicKey};
+use async_trait::async_trait;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{AgentIdError, Result};
+
+/// Prefix of every `did:key` identifier
+pub const DID_KEY_PREFIX: &str = "did:key:";
+
+/// JSON-LD context of DID Documents
+pub const DID_CONTEXT: &str = "https://www.w3.org/ns/did/v1";
+
+/// JSON-LD context of `Ed25519VerificationKey2020` verification methods
+pub const ED25519_2020_CONTEXT: &str = "https://w3id.org/security/suites/ed25519-2020/v1";
+
+/// Verification method type of multibase-encoded Ed25519 keys
+pub const ED25519_VERIFICATION_KEY_2020: &str = "Ed25519VerificationKey2020";
+
+/// Multibase prefix of base58btc
+const MULTIBASE_BASE58BTC: char = 'z';
+
+/// Multicodec `ed25519-pub` (0xed) as an unsigned varint
+const MULTICODEC_ED25519_PUB: [u8; 2] = [0xed, 0x01];
+
+/// Encode an Ed25519 public key as a multibase string (`z6Mk...`)
+pub fn encode_multibase_key(public_key: &PublicKey) -> Result<String> {
+    if public_key.algorithm() != KeyAlgorithm::Ed25519 {
+        return Err(AgentIdError::InvalidDid(format!(
+            "Only Ed25519 keys can be multibase encoded, got {}",
+            public_key.algorithm()
+        )));
+    }
+
+    let bytes = [&MULTICODEC_ED25519_PUB[..], public_key.to_bytes()].concat();
+    Ok(format!(
+        "{}{}",
+        MULTIBASE_BASE58BTC,
+        bs58::encode(bytes).into_string()
+    ))
+}
+
+/// Decode a multibase string (`z6Mk...`) into an Ed25519 public key
+pub fn decode_multibase_key(encoded: &str) -> Result<PublicKey> {
+    let base58 = encoded.strip_prefix(MULTIBASE_BASE58BTC).ok_or_else(|| {
+        AgentIdError::InvalidDid(format!("Key is not multibase base58btc: {}", encoded))
+    })?;
+    let bytes = bs58::decode(base58)
+        .into_vec()
+        .map_err(|e| AgentIdError::InvalidDid(format!("Invalid base58btc key: {}", e)))?;
+
+    let key = bytes
+        .strip_prefix(&MULTICODEC_ED25519_PUB[..])
+        .ok_or_else(|| AgentIdError::InvalidDid("Key is not an ed25519-pub multicodec".into()))?;
+    let key: &[u8; 32] = key
+        .try_into()
+        .map_err(|_| AgentIdError::InvalidDid("Invalid Ed25519 key length".into()))?;
+    PublicKey::from_bytes(key).map_err(|e| AgentIdError::InvalidDid(e.to_string()))
+}
+
+/// A `did:key` identifier for an Ed25519 public key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DidKey {
+    did: String,
+    public_key: PublicKey,
+}
+
+impl DidKey {
+    /// Get the `did:key` identifier of an Ed25519 public key
+    pub fn from_public_key(public_key: &PublicKey) -> Result<Self> {
+        Ok(Self {
+            did: format!("{}{}", DID_KEY_PREFIX, encode_multibase_key(public_key)?),
+            public_key: public_key.clone(),
+        })
+    }
+
+    /// Get the DID as a string
+    pub fn as_str(&self) -> &str {
+        &self.did
+    }
+
+    /// Get the public key encoded in the DID
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Get the ID of the DID's only verification method (`did:key:z...#z...`)
+    pub fn verification_method_id(&self) -> String {
+        format!("{}#{}", self.did, &self.did[DID_KEY_PREFIX.len()..])
+    }
+
+    /// Expand the DID into its DID Document
+    ///
+    /// The single verification method is usable for authentication,
+    /// assertions and capability invocation and delegation.
+    pub fn document(&self) -> DidDocument {
+        let method_id = self.verification_method_id();
+        let reference = || vec![VerificationRelationship::Reference(method_id.clone())];
+
+        DidDocument {
+            context: vec![DID_CONTEXT.into(), ED25519_2020_CONTEXT.into()],
+            id: self.did.clone(),
+            verification_method: vec![VerificationMethod {
+                id: method_id.clone(),
+                method_type: ED25519_VERIFICATION_KEY_2020.into(),
+                controller: self.did.clone(),
+                public_key_multibase: Some(self.did[DID_KEY_PREFIX.len()..].to_string()),
+                public_key_jwk: None,
+                public_key_base58: None,
+            }],
+            authentication: reference(),
+            assertion_method: reference(),
+            capability_invocation: reference(),
+            capability_delegation: reference(),
+            key_agreement: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for DidKey {
+    type Err = AgentIdError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let encoded = s
+            .strip_prefix(DID_KEY_PREFIX)
+            .ok_or_else(|| AgentIdError::InvalidDid(format!("Not a did:key: {}", s)))?;
+        let public_key = decode_multibase_key(encoded)?;
+
+        // Reject alternative encodings of the same key
+        let did = Self::from_public_key(&public_key)?;
+        if did.did != s {
+            return Err(AgentIdError::InvalidDid(format!(
+                "Non-canonical did:key: {}",
+                s
+            )));
+        }
+        Ok(did)
+    }
+}
+
+impl fmt::Display for DidKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.did)
+    }
+}
+
+impl Serialize for DidKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.did)
+    }
+}
+
+impl<'de> Deserialize<'de> for DidKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// A W3C DID Document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidDocument {
+    /// The JSON-LD contexts
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    /// The DID this document describes
+    pub id: String,
+    /// The keys controlled by the DID subject
+    #[serde(default)]
+    pub verification_method: Vec<VerificationMethod>,
+    /// Keys that authenticate as the DID subject
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authentication: Vec<VerificationRelationship>,
+    /// Keys that issue claims, such as credentials, on behalf of the subject
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertion_method: Vec<VerificationRelationship>,
+    /// Keys that invoke capabilities on behalf of the subject
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capability_invocation: Vec<VerificationRelationship>,
+    /// Keys that delegate capabilities on behalf of the subject
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capability_delegation: Vec<VerificationRelationship>,
+    /// Keys used to agree on encryption keys with the subject
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_agreement: Vec<VerificationRelationship>,
+}
+
+impl DidDocument {
+    /// Find a verification method by its ID, which may be relative (`#key-1`)
+    pub fn verification_method(&self, id: &str) -> Option<&VerificationMethod> {
+        let id = self.absolute_id(id);
+        self.verification_method
+            .iter()
+            .chain(
+                self.relationships()
+                    .filter_map(VerificationRelationship::embedded),
+            )
+            .find(|method| self.absolute_id(&method.id) == id)
+    }
+
+    /// Find a verification method listed in a verification relationship
+    ///
+    /// Returns `None` if the method is unknown or not authorized for the
+    /// relationship, e.g. `document.authorized_method(&document.assertion_method, id)`.
+    pub fn authorized_method<'a>(
+        &'a self,
+        relationship: &'a [VerificationRelationship],
+        id: &str,
+    ) -> Option<&'a VerificationMethod> {
+        let id = self.absolute_id(id);
+        relationship.iter().find_map(|entry| {
+            let method = match entry {
+                VerificationRelationship::Embedded(method) => method.as_ref(),
+                VerificationRelationship::Reference(reference) => {
+                    self.verification_method(reference)?
+                }
+            };
+            (self.absolute_id(&method.id) == id).then_some(method)
+        })
+    }
+
+    /// Get the keys usable for authentication
+    pub fn authentication_keys(&self) -> Result<Vec<PublicKey>> {
+        self.keys(&self.authentication)
+    }
+
+    /// Get the keys usable for assertions
+    pub fn assertion_keys(&self) -> Result<Vec<PublicKey>> {
+        self.keys(&self.assertion_method)
+    }
+
+    /// Get the keys of all verification methods, in document order
+    pub fn public_keys(&self) -> Result<Vec<PublicKey>> {
+        self.verification_method
+            .iter()
+            .map(VerificationMethod::public_key)
+            .collect()
+    }
+
+    /// Check that the document describes `did` and is well formed
+    ///
+    /// Every verification method must belong to the document and hold a
+    /// decodable key, and every reference must point to one of them.
+    pub fn validate(&self, did: &str) -> Result<()> {
+        if self.id != did {
+            return Err(AgentIdError::InvalidDid(format!(
+                "Document describes {} instead of {}",
+                self.id, did
+            )));
+        }
+
+        let fragment_prefix = format!("{}#", self.id);
+        let embedded = self
+            .relationships()
+            .filter_map(VerificationRelationship::embedded);
+        for method in self.verification_method.iter().chain(embedded) {
+            if !self.absolute_id(&method.id).starts_with(&fragment_prefix) {
+                return Err(AgentIdError::InvalidDid(format!(
+                    "Verification method {} does not belong to {}",
+                    method.id, self.id
+                )));
+            }
+            method.public_key()?;
+        }
+
+        for entry in self.relationships() {
+            if let VerificationRelationship::Reference(id) = entry {
+                if self.verification_method(id).is_none() {
+                    return Err(AgentIdError::InvalidDid(format!(
+                        "Unknown verification method: {}",
+                        id
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve the public keys of a verification relationship
+    fn keys(&self, relationship: &[VerificationRelationship]) -> Result<Vec<PublicKey>> {
+        relationship
+            .iter()
+            .map(|entry| match entry {
+                VerificationRelationship::Embedded(method) => method.public_key(),
+                VerificationRelationship::Reference(id) => self
+                    .verification_method(id)
+                    .ok_or_else(|| {
+                        AgentIdError::InvalidDid(format!("Unknown verification method: {}", id))
+                    })?
+                    .public_key(),
+            })
+            .collect()
+    }
+
+    /// Iterate over the entries of all verification relationships
+    fn relationships(&self) -> impl Iterator<Item = &VerificationRelationship> {
+        self.authentication
+            .iter()
+            .chain(&self.assertion_method)
+            .chain(&self.capability_invocation)
+            .chain(&self.capability_delegation)
+            .chain(&self.key_agreement)
+    }
+
+    /// Resolve a relative DID URL against the document's DID
+    fn absolute_id(&self, id: &str) -> String {
+        if id.starts_with('#') {
+            format!("{}{}", self.id, id)
+        } else {
+            id.to_string()
+        }
+    }
+}
+
+/// Resolves DIDs to their DID Documents
+#[async_trait]
+pub trait DidResolver: Send + Sync {
+    /// Resolve a DID to its validated DID Document
+    async fn resolve(&self, did: &str) -> Result<DidDocument>;
+}
+
+/// Resolver for `did:key` DIDs, which are expanded without any lookup
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DidKeyResolver;
+
+#[async_trait]
+impl DidResolver for DidKeyResolver {
+    async fn resolve(&self, did: &str) -> Result<DidDocument> {
+        Ok(did.parse::<DidKey>()?.document())
+    }
+}
+
+/// An entry of a verification relationship: a reference to a verification
+/// method or an embedded one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum VerificationRelationship {
+    /// The ID of a verification method
+    Reference(String),
+    /// A verification method only usable for this relationship
+    Embedded(Box<VerificationMethod>),
+}
+
+impl VerificationRelationship {
+    /// Get the embedded verification method, if any
+    fn embedded(&self) -> Option<&VerificationMethod> {
+        match self {
+            Self::Embedded(method) => Some(method.as_ref()),
+            Self::Reference(_) => None,
+        }
+    }
+}
+
+/// A public key in a DID Document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationMethod {
+    /// The DID URL identifying this method
+    pub id: String,
+    /// The verification method type, such as `Ed25519VerificationKey2020`
+    #[serde(rename = "type")]
+    pub method_type: String,
+    /// The DID controlling this key
+    pub controller: String,
+    /// The key as a multibase string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key_multibase: Option<String>,
+    /// The key as a JSON Web Key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key_jwk: Option<agentid_crypto::Jwk>,
+    /// The raw Ed25519 key in base58 (`Ed25519VerificationKey2018`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key_base58: Option<String>,
+}
+
+impl VerificationMethod {
+    /// Decode the public key of this method
+    pub fn public_key(&self) -> Result<PublicKey> {
+        if let Some(multibase) = &self.public_key_multibase {
+            return decode_multibase_key(multibase);
+        }
+        if let Some(jwk) = &self.public_key_jwk {
+            return PublicKey::from_jwk(jwk).map_err(|e| AgentIdError::InvalidDid(e.to_string()));
+        }
+        if let Some(base58) = &self.public_key_base58 {
+            let bytes: [u8; 32] = bs58::decode(base58)
+                .into_vec()
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| AgentIdError::InvalidDid("Invalid base58 Ed25519 key".into()))?;
+            return PublicKey::from_bytes(&bytes)
+                .map_err(|e| AgentIdError::InvalidDid(e.to_string()));
+        }
+        Err(AgentIdError::InvalidDid(format!(
+            "Verification method has no public key: {}",
+            self.id
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentid_crypto::{KeyPair, PrivateKey};
+
+    fn key_from_seed(seed: &str) -> PublicKey {
+        let seed: [u8; 32] = (0..32)
+            .map(|i| u8::from_str_radix(&seed[2 * i..2 * i + 2], 16).unwrap())
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        KeyPair::from_private_key(PrivateKey::from_bytes(&seed).unwrap())
+            .public_key()
+            .clone()
+    }
+
+    // did:key specification test vectors (ed25519-x25519.json)
+    #[test]
+    fn test_did_key_spec_vectors() {
+        let vectors = [
+            (
+                "0000000000000000000000000000000000000000000000000000000000000000",
+                "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+                "O2onvM62pC1io6jQKm8Nc2UyFXcd4kOmOsBIoYtZ2ik",
+            ),
+            (
+                "0000000000000000000000000000000000000000000000000000000000000001",
+                "did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG",
+                "TLWr9q15-_WrvMr8wmnYXNJlHtS4hbWGnyQa7fCluik",
+            ),
+        ];
+
+        for (seed, did, jwk_x) in vectors {
+            let public_key = key_from_seed(seed);
+            let did_key = DidKey::from_public_key(&public_key).unwrap();
+            assert_eq!(did_key.as_str(), did);
+            assert_eq!(public_key.to_jwk().x, jwk_x);
+
+            let parsed: DidKey = did.parse().unwrap();
+            assert_eq!(parsed.public_key(), &public_key);
+            assert_eq!(parsed, did_key);
+        }
+    }
+
+    #[test]
+    fn test_did_key_document() {
+        let did: DidKey = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp"
+            .parse()
+            .unwrap();
+        let document = did.document();
+        let method_id = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp#z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp";
+
+        assert_eq!(
+            serde_json::to_value(&document).unwrap(),
+            serde_json::json!({
+                "@context": [DID_CONTEXT, ED25519_2020_CONTEXT],
+                "id": did.as_str(),
+                "verificationMethod": [{
+                    "id": method_id,
+                    "type": "Ed25519VerificationKey2020",
+                    "controller": did.as_str(),
+                    "publicKeyMultibase": "z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp"
+                }],
+                "authentication": [method_id],
+                "assertionMethod": [method_id],
+                "capabilityInvocation": [method_id],
+                "capabilityDelegation": [method_id]
+            })
+        );
+        assert_eq!(
+            document.authentication_keys().unwrap(),
+            vec![did.public_key().clone()]
+        );
+        assert_eq!(
+            document.assertion_keys().unwrap(),
+            vec![did.public_key().clone()]
+        );
+    }
+
+    #[test]
+    fn test_invalid_did_keys_are_rejected() {
+        for invalid in [
+            "did:web:example.com",
+            "did:key:6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+            "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooW0",
+            "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDoo",
+            // A secp256k1 did:key
+            "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme",
+        ] {
+            assert!(invalid.parse::<DidKey>().is_err(), "accepted {}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_did_key_serde_roundtrip() {
+        let did = DidKey::from_public_key(KeyPair::generate().unwrap().public_key()).unwrap();
+        let json = serde_json::to_string(&did).unwrap();
+        assert_eq!(json, format!("\"{}\"", did));
+        assert_eq!(serde_json::from_str::<DidKey>(&json).unwrap(), did);
+    }
+
+    #[test]
+    fn test_verification_method_key_formats() {
+        let public_key =
+            key_from_seed("0000000000000000000000000000000000000000000000000000000000000000");
+        let method = |jwk, base58| VerificationMethod {
+            id: "#key-1".into(),
+            method_type: "JsonWebKey2020".into(),
+            controller: "did:web:example.com".into(),
+            public_key_multibase: None,
+            public_key_jwk: jwk,
+            public_key_base58: base58,
+        };
+
+        let jwk = method(Some(public_key.to_jwk()), None);
+        assert_eq!(jwk.public_key().unwrap(), public_key);
+
+        let base58 = method(
+            None,
+            Some(bs58::encode(public_key.to_bytes()).into_string()),
+        );
+        assert_eq!(base58.public_key().unwrap(), public_key);
+
+        assert!(method(None, None).public_key().is_err());
+    }
+
+    #[test]
+    fn test_document_validation() {
+        let did = DidKey::from_public_key(KeyPair::generate().unwrap().public_key()).unwrap();
+        let document = did.document();
+        document.validate(did.as_str()).unwrap();
+        assert!(document.validate("did:web:example.com").is_err());
+
+        let mut dangling = document.clone();
+        dangling
+            .assertion_method
+            .push(VerificationRelationship::Reference("#missing".into()));
+        assert!(dangling.validate(did.as_str()).is_err());
+
+        let mut foreign = document;
+        foreign.verification_method[0].id = "did:web:example.com#key-1".into();
+        foreign.authentication.clear();
+        foreign.assertion_method.clear();
+        foreign.capability_invocation.clear();
+        foreign.capability_delegation.clear();
+        assert!(foreign.validate(did.as_str()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_did_key_resolver() {
+        let did = DidKey::from_public_key(KeyPair::generate().unwrap().public_key()).unwrap();
+        let document = DidKeyResolver.resolve(did.as_str()).await.unwrap();
+        assert_eq!(document, did.document());
+
+        let method_id = did.verification_method_id();
+        assert!(document
+            .authorized_method(&document.assertion_method, &method_id)
+            .is_some());
+        assert!(document
+            .authorized_method(&document.key_agreement, &method_id)
+            .is_none());
+        assert!(DidKeyResolver.resolve("did:web:example.com").await.is_err());
+    }
+}
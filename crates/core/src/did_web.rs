@@ -0,0 +1,697 @@
+//! `did:web` resolution.
+//!
+//! A [`DidWeb`] names a DID Document published over HTTPS under a domain the
+//! DID controller owns: `did:web:example.com` is served at
+//! `https://example.com/.well-known/did.json` and
+//! `did:web:example.com:agents:shop` at
+//! `https://example.com/agents/shop/did.json`.
+//!
+//! [`DidWebResolver`] fetches documents through an [`HttpFetcher`], checks
+//! that they describe the requested DID and caches them for as long as the
+//! response's `Cache-Control` or `Expires` headers allow. Enable the `http`
+//! feature for [`ReqwestFetcher`]; tests can use [`FixtureFetcher`] or any
+//! other implementation of the trait.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use agentid_crypto::PublicKey;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use url::Url;
+
+use crate::did::{DidDocument, DidKey, DidResolver, DID_KEY_PREFIX};
+use crate::{AgentIdError, Result};
+
+/// Prefix of every `did:web` identifier
+pub const DID_WEB_PREFIX: &str = "did:web:";
+
+/// Largest DID Document accepted, in bytes
+pub const MAX_DOCUMENT_SIZE: usize = 1024 * 1024;
+
+/// Longest time a fetched DID Document is cached, in seconds
+pub const MAX_CACHE_SECONDS: i64 = 24 * 60 * 60;
+
+/// Path of the DID Document of a DID without path segments
+const WELL_KNOWN_PATH: &str = ".well-known";
+
+/// File name of DID Documents
+const DOCUMENT_FILE: &str = "did.json";
+
+/// A `did:web` identifier
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DidWeb {
+    did: String,
+    url: Url,
+}
+
+impl DidWeb {
+    /// Get the DID as a string
+    pub fn as_str(&self) -> &str {
+        &self.did
+    }
+
+    /// Get the URL the DID Document is published at
+    pub fn document_url(&self) -> &Url {
+        &self.url
+    }
+}
+
+impl FromStr for DidWeb {
+    type Err = AgentIdError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| AgentIdError::InvalidDid(format!("{}: {}", reason, s));
+
+        let method_specific_id = s
+            .strip_prefix(DID_WEB_PREFIX)
+            .ok_or_else(|| invalid("Not a did:web"))?;
+        let mut segments = method_specific_id.split(':');
+        // A port is percent-encoded so that it is not read as a path separator
+        let host = segments
+            .next()
+            .unwrap_or_default()
+            .replace("%3A", ":")
+            .replace("%3a", ":");
+        let path: Vec<&str> = segments.collect();
+
+        if host.is_empty() || host.contains(['%', '/', '?', '#', '@']) {
+            return Err(invalid("Invalid did:web domain"));
+        }
+        if path.iter().any(|segment| {
+            segment.is_empty()
+                || matches!(*segment, "." | "..")
+                || segment.contains(['/', '?', '#'])
+        }) {
+            return Err(invalid("Invalid did:web path"));
+        }
+
+        let mut url = Url::parse(&format!("https://{}/", host))
+            .map_err(|e| invalid(&format!("Invalid did:web domain ({})", e)))?;
+        if url.host_str().is_none() {
+            return Err(invalid("Invalid did:web domain"));
+        }
+        {
+            let mut url_path = url
+                .path_segments_mut()
+                .map_err(|_| invalid("Invalid did:web domain"))?;
+            url_path.clear();
+            if path.is_empty() {
+                url_path.push(WELL_KNOWN_PATH);
+            } else {
+                url_path.extend(&path);
+            }
+            url_path.push(DOCUMENT_FILE);
+        }
+
+        Ok(Self {
+            did: s.to_string(),
+            url,
+        })
+    }
+}
+
+impl fmt::Display for DidWeb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.did)
+    }
+}
+
+/// A response returned by an [`HttpFetcher`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// The HTTP status code
+    pub status: u16,
+    /// The response headers, with lowercase names
+    pub headers: HashMap<String, String>,
+    /// The response body
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Create a `200 OK` response with the given body
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            headers: HashMap::new(),
+            body: body.into(),
+        }
+    }
+
+    /// Add a header
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.insert(name.to_ascii_lowercase(), value.into());
+        self
+    }
+
+    /// Get a header by its case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Get the time until which the response may be reused, if at all
+    ///
+    /// `no-store` and `no-cache` forbid reuse without revalidation.
+    /// Otherwise `max-age`, less the `Age` the response already has, takes
+    /// precedence over `Expires`. Responses are never reused for longer than
+    /// [`MAX_CACHE_SECONDS`], whatever the headers say.
+    pub fn cache_expiry(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let latest = now.checked_add_signed(Duration::seconds(MAX_CACHE_SECONDS))?;
+
+        if let Some(cache_control) = self.header("cache-control") {
+            let mut max_age = None;
+            for directive in cache_control.split(',').map(str::trim) {
+                let (name, value) = directive
+                    .split_once('=')
+                    .map_or((directive, None), |(name, value)| {
+                        (name.trim(), Some(value.trim().trim_matches('"')))
+                    });
+                match name.to_ascii_lowercase().as_str() {
+                    "no-store" | "no-cache" => return None,
+                    "max-age" => max_age = value.and_then(|v| v.parse::<u64>().ok()),
+                    _ => {}
+                }
+            }
+
+            if let Some(max_age) = max_age {
+                let age = self
+                    .header("age")
+                    .and_then(|age| age.trim().parse::<u64>().ok())
+                    .unwrap_or(0);
+                let seconds = max_age.saturating_sub(age).min(MAX_CACHE_SECONDS as u64);
+                let expiry = now.checked_add_signed(Duration::try_seconds(seconds as i64)?)?;
+                return (expiry > now).then_some(expiry);
+            }
+        }
+
+        let expires = self.header("expires")?;
+        let expiry: DateTime<Utc> = httpdate::parse_http_date(expires).ok()?.into();
+        (expiry > now).then_some(expiry.min(latest))
+    }
+}
+
+/// Performs the HTTPS requests needed to resolve DIDs
+#[async_trait]
+pub trait HttpFetcher: Send + Sync {
+    /// Fetch the resource at `url` with a GET request
+    ///
+    /// Non-success statuses are returned as responses, not errors.
+    /// Implementations reading from the network should fail rather than
+    /// buffer bodies larger than [`MAX_DOCUMENT_SIZE`].
+    async fn get(&self, url: &Url) -> Result<HttpResponse>;
+}
+
+/// Fetcher answering from a fixed map of URLs, for tests and offline use
+///
+/// Unknown URLs get a `404 Not Found` response.
+#[derive(Debug, Default)]
+pub struct FixtureFetcher {
+    responses: Mutex<HashMap<Url, HttpResponse>>,
+    requests: Mutex<Vec<Url>>,
+}
+
+impl FixtureFetcher {
+    /// Create a fetcher without any responses
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the response returned for `url`
+    pub fn insert(&self, url: Url, response: HttpResponse) {
+        if let Ok(mut responses) = self.responses.lock() {
+            responses.insert(url, response);
+        }
+    }
+
+    /// Get every URL requested so far, in order
+    pub fn requests(&self) -> Vec<Url> {
+        self.requests
+            .lock()
+            .map(|requests| requests.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl HttpFetcher for FixtureFetcher {
+    async fn get(&self, url: &Url) -> Result<HttpResponse> {
+        self.requests
+            .lock()
+            .map_err(|_| AgentIdError::Internal("Fixture fetcher lock poisoned".into()))?
+            .push(url.clone());
+
+        Ok(self
+            .responses
+            .lock()
+            .map_err(|_| AgentIdError::Internal("Fixture fetcher lock poisoned".into()))?
+            .get(url)
+            .cloned()
+            .unwrap_or(HttpResponse {
+                status: 404,
+                headers: HashMap::new(),
+                body: Vec::new(),
+            }))
+    }
+}
+
+/// Fetcher performing real HTTPS requests with reqwest
+#[cfg(feature = "http")]
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestFetcher {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "http")]
+impl ReqwestFetcher {
+    /// Create a fetcher with a default client
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a fetcher using a preconfigured client
+    pub fn with_client(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "http")]
+#[async_trait]
+impl HttpFetcher for ReqwestFetcher {
+    async fn get(&self, url: &Url) -> Result<HttpResponse> {
+        let mut response = self
+            .client
+            .get(url.as_str())
+            .header("accept", "application/did+json, application/json")
+            .send()
+            .await
+            .map_err(|e| {
+                AgentIdError::DidResolution(format!("Request to {} failed: {}", url, e))
+            })?;
+
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect();
+
+        // Stop reading as soon as the body is known to be too large, so a
+        // hostile server cannot make us buffer an unbounded response
+        let too_large = || {
+            AgentIdError::DidResolution(format!(
+                "DID Document at {} is larger than {} bytes",
+                url, MAX_DOCUMENT_SIZE
+            ))
+        };
+        if response
+            .content_length()
+            .is_some_and(|length| length > MAX_DOCUMENT_SIZE as u64)
+        {
+            return Err(too_large());
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AgentIdError::DidResolution(format!("Reading {} failed: {}", url, e)))?
+        {
+            if body.len() + chunk.len() > MAX_DOCUMENT_SIZE {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// A resolved document and how long it may be reused
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    document: DidDocument,
+    expires_at: DateTime<Utc>,
+}
+
+/// Resolves `did:web` DIDs to their DID Documents
+pub struct DidWebResolver<F: HttpFetcher> {
+    fetcher: F,
+    cache: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl<F: HttpFetcher> DidWebResolver<F> {
+    /// Create a resolver fetching documents with `fetcher`
+    pub fn new(fetcher: F) -> Self {
+        Self {
+            fetcher,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the HTTP fetcher
+    pub fn fetcher(&self) -> &F {
+        &self.fetcher
+    }
+
+    /// Resolve a DID to its validated DID Document
+    ///
+    /// Documents are served from the cache until the expiry allowed by the
+    /// response headers; responses without caching headers are not cached.
+    pub async fn resolve(&self, did: &DidWeb) -> Result<DidDocument> {
+        let now = Utc::now();
+        if let Some(entry) = self.lock()?.get(did.as_str()) {
+            if now < entry.expires_at {
+                return Ok(entry.document.clone());
+            }
+        }
+
+        let url = did.document_url();
+        let response = self.fetcher.get(url).await?;
+        if !(200..300).contains(&response.status) {
+            return Err(AgentIdError::DidResolution(format!(
+                "Fetching {} returned HTTP {}",
+                url, response.status
+            )));
+        }
+        if response.body.len() > MAX_DOCUMENT_SIZE {
+            return Err(AgentIdError::DidResolution(format!(
+                "Document at {} exceeds {} bytes",
+                url, MAX_DOCUMENT_SIZE
+            )));
+        }
+
+        let document: DidDocument = serde_json::from_slice(&response.body).map_err(|e| {
+            AgentIdError::DidResolution(format!("Invalid DID Document at {}: {}", url, e))
+        })?;
+        document.validate(did.as_str())?;
+
+        let mut cache = self.lock()?;
+        match response.cache_expiry(now) {
+            Some(expires_at) => {
+                cache.insert(
+                    did.as_str().to_string(),
+                    CacheEntry {
+                        document: document.clone(),
+                        expires_at,
+                    },
+                );
+            }
+            None => {
+                cache.remove(did.as_str());
+            }
+        }
+        Ok(document)
+    }
+
+    /// Resolve a DID and get the keys of all its verification methods
+    pub async fn resolve_keys(&self, did: &DidWeb) -> Result<Vec<PublicKey>> {
+        self.resolve(did).await?.public_keys()
+    }
+
+    /// Drop the cached document of a DID, forcing the next resolution to fetch it
+    pub fn invalidate(&self, did: &DidWeb) {
+        if let Ok(mut cache) = self.lock() {
+            cache.remove(did.as_str());
+        }
+    }
+
+    /// Drop every cached document
+    pub fn clear_cache(&self) {
+        if let Ok(mut cache) = self.lock() {
+            cache.clear();
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, CacheEntry>>> {
+        self.cache
+            .lock()
+            .map_err(|_| AgentIdError::Internal("DID cache lock poisoned".into()))
+    }
+}
+
+/// Resolves `did:web` DIDs, and `did:key` DIDs without any request
+#[async_trait]
+impl<F: HttpFetcher> DidResolver for DidWebResolver<F> {
+    async fn resolve(&self, did: &str) -> Result<DidDocument> {
+        if did.starts_with(DID_KEY_PREFIX) {
+            return Ok(did.parse::<DidKey>()?.document());
+        }
+        DidWebResolver::resolve(self, &did.parse()?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::{VerificationMethod, VerificationRelationship, DID_CONTEXT};
+    use agentid_crypto::KeyPair;
+
+    const DID: &str = "did:web:example.com:agents:shop";
+
+    fn document(did: &str, public_key: &PublicKey) -> DidDocument {
+        DidDocument {
+            context: vec![DID_CONTEXT.into()],
+            id: did.into(),
+            verification_method: vec![VerificationMethod {
+                id: format!("{}#key-1", did),
+                method_type: "JsonWebKey2020".into(),
+                controller: did.into(),
+                public_key_multibase: None,
+                public_key_jwk: Some(public_key.to_jwk()),
+                public_key_base58: None,
+            }],
+            authentication: vec![VerificationRelationship::Reference("#key-1".into())],
+            assertion_method: vec![VerificationRelationship::Reference("#key-1".into())],
+            capability_invocation: Vec::new(),
+            capability_delegation: Vec::new(),
+            key_agreement: Vec::new(),
+        }
+    }
+
+    fn publish(fetcher: &FixtureFetcher, did: &DidWeb, response: HttpResponse) {
+        fetcher.insert(did.document_url().clone(), response);
+    }
+
+    fn document_response(did: &DidWeb, public_key: &PublicKey) -> HttpResponse {
+        HttpResponse::ok(serde_json::to_vec(&document(did.as_str(), public_key)).unwrap())
+    }
+
+    #[test]
+    fn test_did_to_url() {
+        let cases = [
+            (
+                "did:web:w3c-ccg.github.io",
+                "https://w3c-ccg.github.io/.well-known/did.json",
+            ),
+            (
+                "did:web:w3c-ccg.github.io:user:alice",
+                "https://w3c-ccg.github.io/user/alice/did.json",
+            ),
+            (
+                "did:web:example.com%3A3000:user:alice",
+                "https://example.com:3000/user/alice/did.json",
+            ),
+        ];
+        for (did, url) in cases {
+            let did: DidWeb = did.parse().unwrap();
+            assert_eq!(did.document_url().as_str(), url);
+        }
+    }
+
+    #[test]
+    fn test_invalid_did_webs_are_rejected() {
+        for invalid in [
+            "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+            "did:web:",
+            "did:web:example.com::alice",
+            "did:web:example.com:..:alice",
+            "did:web:user@example.com",
+            "did:web:example.com/alice",
+            "did:web:example.com%2Falice",
+        ] {
+            assert!(invalid.parse::<DidWeb>().is_err(), "accepted {}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_keys() {
+        let did: DidWeb = DID.parse().unwrap();
+        let key_pair = KeyPair::generate().unwrap();
+        let fetcher = FixtureFetcher::new();
+        publish(
+            &fetcher,
+            &did,
+            document_response(&did, key_pair.public_key()),
+        );
+
+        let resolver = DidWebResolver::new(fetcher);
+        let document = resolver.resolve(&did).await.unwrap();
+        assert_eq!(
+            document.authentication_keys().unwrap(),
+            vec![key_pair.public_key().clone()]
+        );
+        assert_eq!(
+            resolver.resolve_keys(&did).await.unwrap(),
+            vec![key_pair.public_key().clone()]
+        );
+        assert_eq!(
+            resolver.fetcher().requests()[0].as_str(),
+            "https://example.com/agents/shop/did.json"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_documents_are_rejected() {
+        let did: DidWeb = DID.parse().unwrap();
+        let key_pair = KeyPair::generate().unwrap();
+        let resolver = DidWebResolver::new(FixtureFetcher::new());
+
+        // Not published
+        assert!(matches!(
+            resolver.resolve(&did).await,
+            Err(AgentIdError::DidResolution(_))
+        ));
+
+        // Not JSON
+        publish(resolver.fetcher(), &did, HttpResponse::ok("<html>"));
+        assert!(matches!(
+            resolver.resolve(&did).await,
+            Err(AgentIdError::DidResolution(_))
+        ));
+
+        // Describes another DID
+        let other: DidWeb = "did:web:attacker.example".parse().unwrap();
+        publish(
+            resolver.fetcher(),
+            &did,
+            HttpResponse::ok(
+                serde_json::to_vec(&document(other.as_str(), key_pair.public_key())).unwrap(),
+            ),
+        );
+        assert!(matches!(
+            resolver.resolve(&did).await,
+            Err(AgentIdError::InvalidDid(_))
+        ));
+
+        // Too large
+        publish(
+            resolver.fetcher(),
+            &did,
+            HttpResponse::ok(vec![b' '; MAX_DOCUMENT_SIZE + 1]),
+        );
+        assert!(resolver.resolve(&did).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cache_honours_headers() {
+        let key_pair = KeyPair::generate().unwrap();
+        let resolver = DidWebResolver::new(FixtureFetcher::new());
+        let expired = httpdate::fmt_http_date(std::time::SystemTime::UNIX_EPOCH);
+        let cases = [
+            (
+                "did:web:cached.example",
+                "cache-control",
+                "public, max-age=3600",
+                1,
+            ),
+            (
+                "did:web:no-store.example",
+                "cache-control",
+                "max-age=3600, no-store",
+                2,
+            ),
+            ("did:web:no-cache.example", "cache-control", "no-cache", 2),
+            ("did:web:zero.example", "cache-control", "max-age=0", 2),
+            ("did:web:expired.example", "expires", expired.as_str(), 2),
+            ("did:web:plain.example", "x-none", "", 2),
+        ];
+
+        for (did, header, value, _) in cases {
+            let did: DidWeb = did.parse().unwrap();
+            publish(
+                resolver.fetcher(),
+                &did,
+                document_response(&did, key_pair.public_key()).with_header(header, value),
+            );
+            resolver.resolve(&did).await.unwrap();
+            resolver.resolve(&did).await.unwrap();
+        }
+
+        let requests = resolver.fetcher().requests();
+        for (did, _, _, fetches) in cases {
+            let did: DidWeb = did.parse().unwrap();
+            let count = requests
+                .iter()
+                .filter(|url| *url == did.document_url())
+                .count();
+            assert_eq!(count, fetches, "{}", did);
+        }
+
+        let cached: DidWeb = "did:web:cached.example".parse().unwrap();
+        resolver.invalidate(&cached);
+        resolver.resolve(&cached).await.unwrap();
+        assert_eq!(resolver.fetcher().requests().len(), requests.len() + 1);
+    }
+
+    #[test]
+    fn test_cache_expiry() {
+        let now = Utc::now();
+        let response = HttpResponse::ok("")
+            .with_header("Cache-Control", "max-age=600")
+            .with_header("Age", "100");
+        assert_eq!(
+            response.cache_expiry(now),
+            Some(now + Duration::seconds(500))
+        );
+
+        // max-age takes precedence over Expires
+        let expires = httpdate::fmt_http_date((now + Duration::days(1)).into());
+        let response = response.with_header("Expires", expires.as_str());
+        assert_eq!(
+            response.cache_expiry(now),
+            Some(now + Duration::seconds(500))
+        );
+
+        let response = HttpResponse::ok("").with_header("Expires", expires.as_str());
+        let expiry = response.cache_expiry(now).unwrap();
+        assert!(expiry > now + Duration::hours(23));
+
+        let response = HttpResponse::ok("").with_header("Expires", "0");
+        assert_eq!(response.cache_expiry(now), None);
+    }
+
+    #[test]
+    fn test_cache_expiry_is_bounded() {
+        let now = Utc::now();
+        let latest = Some(now + Duration::seconds(MAX_CACHE_SECONDS));
+
+        let response =
+            HttpResponse::ok("").with_header("Cache-Control", "max-age=9223372036854775807");
+        assert_eq!(response.cache_expiry(now), latest);
+        let response =
+            HttpResponse::ok("").with_header("Cache-Control", "max-age=99999999999999999999");
+        assert_eq!(response.cache_expiry(now), None);
+
+        // A negative Age cannot extend max-age
+        let response = HttpResponse::ok("")
+            .with_header("Cache-Control", "max-age=600")
+            .with_header("Age", "-9223372036854775808");
+        assert_eq!(
+            response.cache_expiry(now),
+            Some(now + Duration::seconds(600))
+        );
+
+        let expires = httpdate::fmt_http_date((now + Duration::days(365)).into());
+        let response = HttpResponse::ok("").with_header("Expires", expires.as_str());
+        assert_eq!(response.cache_expiry(now), latest);
+    }
+}
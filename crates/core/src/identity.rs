@@ -8,7 +8,12 @@ use std::fmt;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::{Agent, Result};
+use crate::agent::KeyPurpose;
+use crate::controller::{self, ControllerClaim};
+use crate::credentials::VerifiableCredential;
+use crate::did::{DidKey, DidResolver};
+use crate::{Agent, AgentIdError, Result};
+use agentid_crypto::{InMemorySigner, KeyPair, Signature, SigningContext};
 use agentid_types::AgentId;
 
 /// Represents the verification level of an identity
@@ -46,6 +51,23 @@ pub struct VerificationStatus {
     metadata: serde_json::Value,
 }
 
+impl VerificationStatus {
+    /// Get the current verification level
+    pub fn level(&self) -> VerificationLevel {
+        self.level
+    }
+
+    /// Get when this identity was last verified
+    pub fn verified_at(&self) -> Option<DateTime<Utc>> {
+        self.verified_at
+    }
+
+    /// Get the agent that performed the last verification
+    pub fn verified_by(&self) -> Option<&AgentId> {
+        self.verified_by.as_ref()
+    }
+}
+
 impl Default for VerificationStatus {
     fn default() -> Self {
         Self {
@@ -71,6 +93,9 @@ pub struct Identity {
     /// Additional identity metadata
     #[serde(default)]
     metadata: serde_json::Value,
+    /// The key pair used to sign on behalf of the agent, never serialized
+    #[serde(skip)]
+    key_pair: Option<KeyPair>,
 }
 
 impl Identity {
@@ -82,9 +107,36 @@ impl Identity {
             updated_at: Utc::now(),
             metadata: serde_json::json!({}),
             agent,
+            key_pair: None,
         })
     }
 
+    /// Create a new identity that signs on behalf of an agent with `key_pair`
+    ///
+    /// The public key is bound to the agent for authentication and
+    /// assertions unless the agent already holds it.
+    pub fn with_key_pair(mut agent: Agent, key_pair: KeyPair) -> Result<Self> {
+        if !key_pair.is_valid() {
+            return Err(AgentIdError::InvalidIdentityData(
+                "Public key does not match private key".into(),
+            ));
+        }
+        if !agent
+            .keys()
+            .iter()
+            .any(|key| &key.public_key == key_pair.public_key())
+        {
+            agent.add_key(
+                key_pair.public_key().clone(),
+                vec![KeyPurpose::Authentication, KeyPurpose::Assertion],
+            )?;
+        }
+
+        let mut identity = Self::new(agent)?;
+        identity.key_pair = Some(key_pair);
+        Ok(identity)
+    }
+
     /// Get the agent this identity belongs to
     pub fn agent(&self) -> &Agent {
         &self.agent
@@ -110,6 +162,112 @@ impl Identity {
         &self.metadata
     }
 
+    /// Check if this identity holds a key pair to sign with
+    pub fn can_sign(&self) -> bool {
+        self.key_pair.is_some()
+    }
+
+    /// Sign a message on behalf of the agent in the given context
+    ///
+    /// Fails if the identity holds no key pair or its key is not bound to the
+    /// agent for the purpose the context requires, see
+    /// [`KeyPurpose::for_context`].
+    pub fn sign(&self, context: &SigningContext, message: &[u8]) -> Result<Signature> {
+        let key_pair = self.key_pair.as_ref().ok_or_else(|| {
+            AgentIdError::InvalidIdentityData("Identity has no signing key".into())
+        })?;
+        let purpose = KeyPurpose::for_context(context);
+        let bound = self
+            .agent
+            .keys()
+            .iter()
+            .any(|key| &key.public_key == key_pair.public_key() && key.has_purpose(purpose));
+        if !bound {
+            return Err(AgentIdError::InvalidIdentityData(format!(
+                "Signing key is not bound to the agent for {:?} in {}",
+                purpose, context
+            )));
+        }
+
+        context
+            .sign(message, key_pair.private_key())
+            .map_err(|e| AgentIdError::Internal(format!("Failed to sign: {}", e)))
+    }
+
+    /// Verify a signature made on behalf of the agent in the given context
+    ///
+    /// Any key bound to the agent for the purpose the context requires is
+    /// accepted, see [`KeyPurpose::for_context`].
+    pub fn verify(
+        &self,
+        context: &SigningContext,
+        message: &[u8],
+        signature: &Signature,
+    ) -> Result<bool> {
+        Ok(self
+            .agent
+            .keys_for(KeyPurpose::for_context(context))
+            .any(|public_key| {
+                context
+                    .verify(message, signature, public_key)
+                    .unwrap_or(false)
+            }))
+    }
+
+    /// Get the `did:key` of the key pair this identity signs with
+    pub fn did(&self) -> Option<DidKey> {
+        DidKey::from_public_key(self.key_pair.as_ref()?.public_key()).ok()
+    }
+
+    /// Vouch for an agent as its owner by issuing a controller credential
+    ///
+    /// The credential is issued by this identity's `did:key`.
+    pub async fn issue_controller_credential(&self, agent: &str) -> Result<VerifiableCredential> {
+        let (did, key_pair) = self.did().zip(self.key_pair.clone()).ok_or_else(|| {
+            AgentIdError::InvalidIdentityData("Identity has no Ed25519 signing key".into())
+        })?;
+
+        Ok(controller::issue_controller_credential(
+            did.as_str(),
+            did.verification_method_id(),
+            agent,
+            &InMemorySigner::new(key_pair),
+        )
+        .await?)
+    }
+
+    /// Verify a controller credential issued to this identity's agent
+    ///
+    /// The credential's subject DID must list one of the agent's
+    /// authentication keys. On success the identity becomes agent-verified,
+    /// with `verified_by` set to the owner's self-certifying ID.
+    pub async fn verify_controller_credential(
+        &mut self,
+        credential: &VerifiableCredential,
+        resolver: &dyn DidResolver,
+    ) -> Result<ControllerClaim> {
+        let claim =
+            controller::verify_controller_credential(credential, resolver, Utc::now()).await?;
+
+        let subject_keys = resolver
+            .resolve(&claim.agent)
+            .await?
+            .authentication_keys()?;
+        let is_subject = self
+            .agent
+            .keys_for(KeyPurpose::Authentication)
+            .any(|key| subject_keys.contains(key));
+        if !is_subject {
+            return Err(AgentIdError::VerificationFailed(format!(
+                "{} is not the DID of this agent",
+                claim.agent
+            )));
+        }
+
+        self.update_verification(VerificationLevel::AgentVerified, Some(claim.owner_id()))?;
+        Ok(claim)
+    }
+
     /// Update the verification status of this identity
     pub fn update_verification(
         &mut self,
@@ -200,4 +358,151 @@ mod tests {
         assert!(!identity.is_agent_verified());
         assert!(identity.is_authority_verified());
     }
+
+    #[test]
+    fn test_identity_sign_and_verify() {
+        let key_pair = KeyPair::generate().unwrap();
+        let agent = Agent::new("test-agent").unwrap();
+        let identity = Identity::with_key_pair(agent, key_pair.clone()).unwrap();
+        assert!(identity.can_sign());
+        assert_eq!(
+            identity
+                .agent()
+                .keys_for(KeyPurpose::Authentication)
+                .collect::<Vec<_>>(),
+            vec![key_pair.public_key()]
+        );
+
+        let context = SigningContext::DOCUMENT;
+        let signature = identity.sign(&context, b"order #1").unwrap();
+        assert!(identity.verify(&context, b"order #1", &signature).unwrap());
+        assert!(!identity.verify(&context, b"order #2", &signature).unwrap());
+        assert!(!identity
+            .verify(&SigningContext::CHALLENGE, b"order #1", &signature)
+            .unwrap());
+
+        // Another agent's key is not accepted
+        let other = Identity::with_key_pair(
+            Agent::new("other-agent").unwrap(),
+            KeyPair::generate().unwrap(),
+        )
+        .unwrap();
+        let forged = other.sign(&context, b"order #1").unwrap();
+        assert!(!identity.verify(&context, b"order #1", &forged).unwrap());
+    }
+
+    #[test]
+    fn test_identity_signs_only_for_key_purpose() {
+        let authentication = KeyPair::generate().unwrap();
+        let mut agent = Agent::new("test-agent").unwrap();
+        agent
+            .add_key(
+                authentication.public_key().clone(),
+                vec![KeyPurpose::Authentication],
+            )
+            .unwrap();
+        let identity = Identity::with_key_pair(agent, authentication).unwrap();
+
+        let signature = identity.sign(&SigningContext::CHALLENGE, b"nonce").unwrap();
+        assert!(identity
+            .verify(&SigningContext::CHALLENGE, b"nonce", &signature)
+            .unwrap());
+        for context in [
+            SigningContext::ATTESTATION,
+            SigningContext::DOCUMENT,
+            SigningContext::DELEGATION,
+        ] {
+            assert!(identity.sign(&context, b"statement").is_err());
+        }
+
+        let assertion = KeyPair::generate().unwrap();
+        let mut agent = Agent::new("test-agent").unwrap();
+        agent
+            .add_key(assertion.public_key().clone(), vec![KeyPurpose::Assertion])
+            .unwrap();
+        let identity = Identity::with_key_pair(agent, assertion.clone()).unwrap();
+
+        assert!(identity.sign(&SigningContext::CHALLENGE, b"nonce").is_err());
+        identity
+            .sign(&SigningContext::ATTESTATION, b"statement")
+            .unwrap();
+
+        // A challenge answered with the assertion key is not accepted
+        let signature = SigningContext::CHALLENGE
+            .sign(b"nonce", assertion.private_key())
+            .unwrap();
+        assert!(!identity
+            .verify(&SigningContext::CHALLENGE, b"nonce", &signature)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_identity_without_key_pair_cannot_sign() {
+        let identity = Identity::new(Agent::new("test-agent").unwrap()).unwrap();
+        assert!(!identity.can_sign());
+        assert!(identity.sign(&SigningContext::DOCUMENT, b"data").is_err());
+    }
+
+    #[test]
+    fn test_identity_serde_omits_private_key() {
+        let key_pair = KeyPair::generate().unwrap();
+        let identity =
+            Identity::with_key_pair(Agent::new("test-agent").unwrap(), key_pair).unwrap();
+        let signature = identity.sign(&SigningContext::DOCUMENT, b"data").unwrap();
+
+        let json = serde_json::to_string(&identity).unwrap();
+        let restored: Identity = serde_json::from_str(&json).unwrap();
+        assert!(!restored.can_sign());
+        assert!(restored
+            .verify(&SigningContext::DOCUMENT, b"data", &signature)
+            .unwrap());
+
+        // Identities serialized before keys were added still load
+        let mut legacy = serde_json::to_value(&identity).unwrap();
+        legacy["agent"].as_object_mut().unwrap().remove("keys");
+        let legacy: Identity = serde_json::from_value(legacy).unwrap();
+        assert!(legacy.agent().keys().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_controller_credential_verifies_identity() {
+        let owner =
+            Identity::with_key_pair(Agent::new("owner").unwrap(), KeyPair::generate().unwrap())
+                .unwrap();
+        let mut agent = Identity::with_key_pair(
+            Agent::new("shop-agent").unwrap(),
+            KeyPair::generate().unwrap(),
+        )
+        .unwrap();
+        let agent_did = agent.did().unwrap();
+
+        let credential = owner
+            .issue_controller_credential(agent_did.as_str())
+            .await
+            .unwrap();
+        let claim = agent
+            .verify_controller_credential(&credential, &crate::did::DidKeyResolver)
+            .await
+            .unwrap();
+
+        assert_eq!(claim.owner, owner.did().unwrap().as_str());
+        assert!(agent.is_agent_verified());
+        let verified_by = agent.verification().verified_by().unwrap();
+        assert_eq!(verified_by, &claim.owner_id());
+        assert_eq!(verified_by.name(), owner.did().unwrap().as_str());
+
+        // Another agent cannot use the credential
+        let mut other = Identity::with_key_pair(
+            Agent::new("other-agent").unwrap(),
+            KeyPair::generate().unwrap(),
+        )
+        .unwrap();
+        assert!(matches!(
+            other
+                .verify_controller_credential(&credential, &crate::did::DidKeyResolver)
+                .await,
+            Err(AgentIdError::VerificationFailed(_))
+        ));
+        assert!(!other.is_verified());
+    }
 }
@@ -3,15 +3,36 @@
 //! This crate provides the fundamental types and traits for implementing
 //! agent-based identity and trust in commerce applications.
 
-
 pub mod agent;
+pub mod challenge;
+pub mod controller;
+pub mod credentials;
+pub mod did;
+pub mod did_web;
 pub mod identity;
+pub mod presentation;
+#[cfg(test)]
+mod test_utils;
 pub mod trust;
 pub mod verification;
 
 // Re-export our own types
-pub use agent::Agent;
+pub use agent::{Agent, AgentKey, KeyPurpose};
+pub use challenge::{
+    Challenge, ChallengeResponse, ChallengeVerifier, InMemoryNonceStore, NonceStore,
+};
+pub use controller::{ControllerChainVerifier, ControllerClaim};
+pub use credentials::{
+    CredentialError, CredentialSubject, DataIntegrityProof, Issuer, VerifiableCredential,
+};
+pub use did::{
+    DidDocument, DidKey, DidKeyResolver, DidResolver, VerificationMethod, VerificationRelationship,
+};
+#[cfg(feature = "http")]
+pub use did_web::ReqwestFetcher;
+pub use did_web::{DidWeb, DidWebResolver, FixtureFetcher, HttpFetcher, HttpResponse};
 pub use identity::Identity;
+pub use presentation::{CredentialReport, PresentationReport, VerifiablePresentation};
 // Do not re-export Rotation, Trust, Verification unless they exist as types
 
 /// Errors that can occur in the core protocol implementation
@@ -40,6 +61,16 @@ pub enum AgentIdError {
     VerificationFailed(String),
     #[error("Verification error: {0}")]
     VerificationError(String),
+    #[error("Challenge expired at {0}")]
+    ChallengeExpired(chrono::DateTime<chrono::Utc>),
+    #[error("Challenge nonce unknown or already used: {0}")]
+    ChallengeReplayed(String),
+    #[error("Invalid DID: {0}")]
+    InvalidDid(String),
+    #[error("DID resolution failed: {0}")]
+    DidResolution(String),
+    #[error(transparent)]
+    Credential(#[from] credentials::CredentialError),
     #[error("Trust level error: {0}")]
     TrustLevelError(String),
     #[error("Internal error: {0}")]
@@ -0,0 +1,391 @@
+//! W3C Verifiable Presentations.
+//!
+//! A holder wraps its credentials in a [`VerifiablePresentation`] and signs
+//! it with one of its authentication keys, binding the signature to a
+//! `challenge` and `domain` chosen by the verifier. A presentation made for
+//! one verifier or one request therefore cannot be replayed to another.
+//!
+//! Verification fails outright if the holder's proof, challenge or domain
+//! is wrong. Otherwise it returns a [`PresentationReport`] with the outcome
+//! of every embedded credential, so a verifier can accept a presentation in
+//! which only the credentials it needs are valid. A credential is only valid
+//! if its subject is the holder; credentials about someone else, such as
+//! the holder's owner, are reported as verified but not valid.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use agentid_crypto::Signer;
+
+use crate::credentials::{
+    CredentialError, CredentialResult, DataIntegrityProof, VerifiableCredential,
+    AUTHENTICATION_PURPOSE, CREDENTIALS_V2_CONTEXT,
+};
+use crate::did::DidResolver;
+
+/// Base type of every presentation
+pub const VERIFIABLE_PRESENTATION_TYPE: &str = "VerifiablePresentation";
+
+/// A Verifiable Presentation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiablePresentation {
+    /// The JSON-LD contexts, starting with [`CREDENTIALS_V2_CONTEXT`]
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    /// The presentation's identifier
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The presentation types, including [`VERIFIABLE_PRESENTATION_TYPE`]
+    #[serde(rename = "type")]
+    pub types: Vec<String>,
+    /// The DID of the holder presenting the credentials
+    pub holder: String,
+    /// The presented credentials
+    #[serde(default)]
+    pub verifiable_credential: Vec<VerifiableCredential>,
+    /// The holder's proof, set by [`VerifiablePresentation::sign`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof: Option<DataIntegrityProof>,
+}
+
+/// The outcome of verifying one presented credential
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CredentialReport {
+    /// The credential's identifier
+    pub id: Option<String>,
+    /// The credential's issuer
+    pub issuer: String,
+    /// Whether the credential is about the holder
+    pub subject_is_holder: bool,
+    /// Whether the credential verified and is about the holder, and why not
+    ///
+    /// A credential that verified but is about someone else fails with
+    /// [`CredentialError::NotAboutHolder`].
+    pub result: CredentialResult<()>,
+}
+
+impl CredentialReport {
+    /// Check if the credential verified and is about the holder
+    pub fn is_valid(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    /// Check if the credential verified, whoever it is about
+    pub fn is_verified(&self) -> bool {
+        matches!(
+            self.result,
+            Ok(()) | Err(CredentialError::NotAboutHolder(_))
+        )
+    }
+}
+
+/// The outcome of verifying a presentation with a valid holder proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresentationReport {
+    /// The holder's DID, proven by the presentation's signature
+    pub holder: String,
+    /// One report per credential, in presentation order
+    pub credentials: Vec<CredentialReport>,
+}
+
+impl PresentationReport {
+    /// Check if every credential verified and is about the holder
+    pub fn all_valid(&self) -> bool {
+        self.credentials.iter().all(CredentialReport::is_valid)
+    }
+
+    /// Get the credentials that verified and are about the holder
+    pub fn valid_credentials(&self) -> impl Iterator<Item = &CredentialReport> {
+        self.credentials.iter().filter(|report| report.is_valid())
+    }
+}
+
+impl VerifiablePresentation {
+    /// Create an unsigned presentation of `credentials` by `holder`
+    pub fn new(holder: impl Into<String>, credentials: Vec<VerifiableCredential>) -> Self {
+        Self {
+            context: vec![CREDENTIALS_V2_CONTEXT.into()],
+            id: Some(format!("urn:uuid:{}", Uuid::new_v4())),
+            types: vec![VERIFIABLE_PRESENTATION_TYPE.into()],
+            holder: holder.into(),
+            verifiable_credential: credentials,
+            proof: None,
+        }
+    }
+
+    /// Sign the presentation with the holder's key for a verifier's request
+    ///
+    /// `verification_method` is the DID URL of the holder's authentication
+    /// key used by `signer`. Any previous proof is replaced.
+    pub async fn sign(
+        mut self,
+        verification_method: impl Into<String>,
+        challenge: impl Into<String>,
+        domain: impl Into<String>,
+        signer: &dyn Signer,
+    ) -> CredentialResult<Self> {
+        self.check_schema()?;
+        let mut options = DataIntegrityProof::new(verification_method, AUTHENTICATION_PURPOSE);
+        if options.controller() != self.holder {
+            return Err(CredentialError::ControllerMismatch {
+                verification_method: options.verification_method,
+                controller: self.holder.clone(),
+            });
+        }
+        options.challenge = Some(challenge.into());
+        options.domain = Some(domain.into());
+
+        self.proof = None;
+        self.proof = Some(options.sign(&self, signer).await?);
+        Ok(self)
+    }
+
+    /// Verify the presentation now for the given challenge and domain
+    pub async fn verify(
+        &self,
+        challenge: &str,
+        domain: &str,
+        resolver: &dyn DidResolver,
+    ) -> CredentialResult<PresentationReport> {
+        self.verify_at(challenge, domain, resolver, Utc::now())
+            .await
+    }
+
+    /// Verify the presentation as of `now` for the given challenge and domain
+    ///
+    /// The presentation must be signed by one of the holder's authentication
+    /// keys over the expected challenge and domain. Each credential is then
+    /// verified on its own and checked to be about the holder, and reported
+    /// without failing the presentation.
+    pub async fn verify_at(
+        &self,
+        challenge: &str,
+        domain: &str,
+        resolver: &dyn DidResolver,
+        now: DateTime<Utc>,
+    ) -> CredentialResult<PresentationReport> {
+        self.check_schema()?;
+        let proof = self.proof.as_ref().ok_or(CredentialError::MissingProof)?;
+        if proof.challenge.as_deref() != Some(challenge) {
+            return Err(CredentialError::ChallengeMismatch);
+        }
+        if proof.domain.as_deref() != Some(domain) {
+            return Err(CredentialError::DomainMismatch);
+        }
+        proof
+            .verify(self, &self.holder, AUTHENTICATION_PURPOSE, resolver)
+            .await?;
+
+        let mut credentials = Vec::with_capacity(self.verifiable_credential.len());
+        for credential in &self.verifiable_credential {
+            let subject_is_holder =
+                credential.credential_subject.id.as_deref() == Some(self.holder.as_str());
+            let result = match credential.verify_at(resolver, now).await {
+                Ok(_) if !subject_is_holder => {
+                    Err(CredentialError::NotAboutHolder(self.holder.clone()))
+                }
+                result => result.map(|_| ()),
+            };
+            credentials.push(CredentialReport {
+                id: credential.id.clone(),
+                issuer: credential.issuer.id().to_string(),
+                subject_is_holder,
+                result,
+            });
+        }
+
+        Ok(PresentationReport {
+            holder: self.holder.clone(),
+            credentials,
+        })
+    }
+
+    /// Check the presentation against the data model's structural requirements
+    fn check_schema(&self) -> CredentialResult<()> {
+        let invalid = |reason: &str| Err(CredentialError::InvalidSchema(reason.into()));
+
+        if self.context.first().map(String::as_str) != Some(CREDENTIALS_V2_CONTEXT) {
+            return invalid("The first @context must be the credentials v2 context");
+        }
+        if !self.types.iter().any(|t| t == VERIFIABLE_PRESENTATION_TYPE) {
+            return invalid("The type must include VerifiablePresentation");
+        }
+        if !self.holder.contains(':') {
+            return invalid("The holder must be a URL");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::controller_credential;
+    use crate::did::DidKeyResolver;
+    use crate::test_utils::{party, vouch, Party};
+    use chrono::Duration;
+
+    const CHALLENGE: &str = "3f6c1d0e9b7a4e52";
+    const DOMAIN: &str = "seller.example";
+
+    async fn present(
+        holder: &Party,
+        credentials: Vec<VerifiableCredential>,
+    ) -> VerifiablePresentation {
+        VerifiablePresentation::new(holder.did.as_str(), credentials)
+            .sign(
+                holder.did.verification_method_id(),
+                CHALLENGE,
+                DOMAIN,
+                &holder.signer,
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_present_and_verify() {
+        let (buyer, owner, anchor) = (party(), party(), party());
+        let credentials = vec![vouch(&owner, &buyer).await, vouch(&anchor, &owner).await];
+        let presentation = present(&buyer, credentials.clone()).await;
+
+        let json = serde_json::to_value(&presentation).unwrap();
+        assert_eq!(json["type"][0], VERIFIABLE_PRESENTATION_TYPE);
+        assert_eq!(json["proof"]["proofPurpose"], "authentication");
+        assert_eq!(json["proof"]["challenge"], CHALLENGE);
+        assert_eq!(json["proof"]["domain"], DOMAIN);
+        let presentation: VerifiablePresentation = serde_json::from_value(json).unwrap();
+
+        let report = presentation
+            .verify(CHALLENGE, DOMAIN, &DidKeyResolver)
+            .await
+            .unwrap();
+        assert_eq!(report.holder, buyer.did.as_str());
+        assert_eq!(report.credentials.len(), 2);
+        assert!(report.credentials[0].is_valid());
+        assert!(report.credentials[0].subject_is_holder);
+
+        // The owner's credential verifies but is not about the buyer
+        assert!(!report.all_valid());
+        assert!(report.credentials[1].is_verified());
+        assert!(!report.credentials[1].subject_is_holder);
+        assert_eq!(
+            report.credentials[1].result,
+            Err(CredentialError::NotAboutHolder(buyer.did.to_string()))
+        );
+        assert_eq!(report.credentials[1].issuer, anchor.did.as_str());
+        assert_eq!(report.credentials[0].id, credentials[0].id);
+    }
+
+    #[tokio::test]
+    async fn test_presentation_cannot_be_replayed() {
+        let (buyer, owner) = (party(), party());
+        let presentation = present(&buyer, vec![vouch(&owner, &buyer).await]).await;
+
+        assert_eq!(
+            presentation
+                .verify("another-challenge", DOMAIN, &DidKeyResolver)
+                .await,
+            Err(CredentialError::ChallengeMismatch)
+        );
+        assert_eq!(
+            presentation
+                .verify(CHALLENGE, "other-seller.example", &DidKeyResolver)
+                .await,
+            Err(CredentialError::DomainMismatch)
+        );
+
+        // Rewriting the challenge breaks the holder's signature
+        let mut rewritten = presentation;
+        rewritten.proof.as_mut().unwrap().challenge = Some("another-challenge".into());
+        assert_eq!(
+            rewritten
+                .verify("another-challenge", DOMAIN, &DidKeyResolver)
+                .await,
+            Err(CredentialError::InvalidSignature)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_holder_binding() {
+        let (buyer, thief, owner) = (party(), party(), party());
+        let credential = vouch(&owner, &buyer).await;
+
+        // A thief cannot sign as the buyer
+        let result = VerifiablePresentation::new(buyer.did.as_str(), vec![credential.clone()])
+            .sign(
+                thief.did.verification_method_id(),
+                CHALLENGE,
+                DOMAIN,
+                &thief.signer,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(CredentialError::ControllerMismatch { .. })
+        ));
+
+        let forged = VerifiablePresentation::new(buyer.did.as_str(), vec![credential.clone()])
+            .sign(
+                buyer.did.verification_method_id(),
+                CHALLENGE,
+                DOMAIN,
+                &thief.signer,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            forged.verify(CHALLENGE, DOMAIN, &DidKeyResolver).await,
+            Err(CredentialError::InvalidSignature)
+        );
+
+        // Presenting someone else's credential does not make it valid
+        let report = present(&thief, vec![credential])
+            .await
+            .verify(CHALLENGE, DOMAIN, &DidKeyResolver)
+            .await
+            .unwrap();
+        assert!(!report.all_valid());
+        assert_eq!(report.valid_credentials().count(), 0);
+        assert!(matches!(
+            report.credentials[0].result,
+            Err(CredentialError::NotAboutHolder(_))
+        ));
+        assert!(report.credentials[0].is_verified());
+        assert!(!report.credentials[0].subject_is_holder);
+    }
+
+    #[tokio::test]
+    async fn test_per_credential_report() {
+        let (buyer, owner) = (party(), party());
+        let valid = vouch(&owner, &buyer).await;
+        let expired = controller_credential(owner.did.as_str(), buyer.did.as_str())
+            .with_valid_from(Utc::now() - Duration::days(2))
+            .with_valid_until(Utc::now() - Duration::days(1))
+            .issue(owner.did.verification_method_id(), &owner.signer)
+            .await
+            .unwrap();
+        let mut tampered = vouch(&owner, &buyer).await;
+        tampered.credential_subject.id = Some(party().did.to_string());
+
+        let report = present(&buyer, vec![valid, expired, tampered])
+            .await
+            .verify(CHALLENGE, DOMAIN, &DidKeyResolver)
+            .await
+            .unwrap();
+
+        assert!(!report.all_valid());
+        assert_eq!(report.valid_credentials().count(), 1);
+        assert_eq!(report.credentials[0].result, Ok(()));
+        assert!(matches!(
+            report.credentials[1].result,
+            Err(CredentialError::Expired(_))
+        ));
+        assert_eq!(
+            report.credentials[2].result,
+            Err(CredentialError::InvalidSignature)
+        );
+    }
+}
@@ -0,0 +1,34 @@
+//! Fixtures shared by the unit tests of this crate.
+
+use agentid_crypto::{InMemorySigner, KeyPair};
+
+use crate::controller::issue_controller_credential;
+use crate::credentials::VerifiableCredential;
+use crate::did::DidKey;
+
+/// A party identified by the `did:key` of a fresh key pair
+pub(crate) struct Party {
+    pub did: DidKey,
+    pub signer: InMemorySigner,
+}
+
+/// Create a party with a fresh key pair
+pub(crate) fn party() -> Party {
+    let key_pair = KeyPair::generate().unwrap();
+    Party {
+        did: DidKey::from_public_key(key_pair.public_key()).unwrap(),
+        signer: InMemorySigner::new(key_pair),
+    }
+}
+
+/// Issue a controller credential from `owner` to `agent`
+pub(crate) async fn vouch(owner: &Party, agent: &Party) -> VerifiableCredential {
+    issue_controller_credential(
+        owner.did.as_str(),
+        owner.did.verification_method_id(),
+        agent.did.as_str(),
+        &owner.signer,
+    )
+    .await
+    .unwrap()
+}
@@ -0,0 +1,42 @@
+use agentid_crypto::{BatchItem, KeyPair, PublicKey, Signature};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn signed_batch(count: usize) -> Vec<(Vec<u8>, Signature, PublicKey)> {
+    (0..count)
+        .map(|i| {
+            let key_pair = KeyPair::generate().unwrap();
+            let message = format!("attestation {}", i).into_bytes();
+            let signature = key_pair.private_key().sign(&message).unwrap();
+            (message, signature, key_pair.public_key().clone())
+        })
+        .collect()
+}
+
+fn bench_verification(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify");
+
+    for count in [16, 128, 512] {
+        let batch = signed_batch(count);
+        let items: Vec<BatchItem<'_>> = batch
+            .iter()
+            .map(|(message, signature, public_key)| (message.as_slice(), signature, public_key))
+            .collect();
+
+        group.bench_with_input(BenchmarkId::new("per_item", count), &items, |b, items| {
+            b.iter(|| {
+                for (message, signature, public_key) in items {
+                    black_box(signature.verify(message, public_key).unwrap());
+                }
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("batch", count), &items, |b, items| {
+            b.iter(|| Signature::verify_batch(black_box(items)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_verification);
+criterion_main!(benches);
@@ -0,0 +1,297 @@
+//! Signature algorithms supported by the key types.
+//!
+//! Ed25519 is always available. ECDSA over P-256 and secp256k1 are enabled
+//! with the `p256` and `secp256k1` cargo features. Keys and signatures whose
+//! algorithm is not compiled in are rejected with
+//! [`CryptoError::UnsupportedAlgorithm`].
+
+use std::fmt;
+
+use ed25519_dalek::Signer;
+#[cfg(any(feature = "p256", feature = "secp256k1"))]
+use ed25519_dalek::Verifier;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{CryptoError, Result};
+
+/// The signature algorithm of a key or signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum KeyAlgorithm {
+    /// EdDSA over Curve25519
+    #[default]
+    #[serde(rename = "Ed25519")]
+    Ed25519,
+    /// ECDSA over NIST P-256 with SHA-256 (requires the `p256` feature)
+    #[serde(rename = "P-256")]
+    P256,
+    /// ECDSA over secp256k1 with SHA-256 (requires the `secp256k1` feature)
+    #[serde(rename = "secp256k1")]
+    Secp256k1,
+}
+
+impl KeyAlgorithm {
+    /// Check if this is Ed25519, the default algorithm
+    pub fn is_ed25519(&self) -> bool {
+        *self == Self::Ed25519
+    }
+
+    /// Check if support for this algorithm is compiled in
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            Self::Ed25519 => true,
+            Self::P256 => cfg!(feature = "p256"),
+            Self::Secp256k1 => cfg!(feature = "secp256k1"),
+        }
+    }
+
+    /// Get the approximate security level of this algorithm in bits
+    pub fn security_bits(&self) -> u32 {
+        match self {
+            Self::Ed25519 | Self::P256 | Self::Secp256k1 => 128,
+        }
+    }
+
+    /// Get the JWS `alg` name for this algorithm
+    pub fn jws_algorithm(&self) -> &'static str {
+        match self {
+            Self::Ed25519 => "EdDSA",
+            Self::P256 => "ES256",
+            Self::Secp256k1 => "ES256K",
+        }
+    }
+
+    /// Fail with [`CryptoError::UnsupportedAlgorithm`] if this algorithm is not compiled in
+    pub(crate) fn ensure_enabled(&self) -> Result<()> {
+        if self.is_enabled() {
+            Ok(())
+        } else {
+            Err(CryptoError::UnsupportedAlgorithm(format!(
+                "{} support is not enabled",
+                self
+            )))
+        }
+    }
+}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ed25519 => write!(f, "Ed25519"),
+            Self::P256 => write!(f, "P-256"),
+            Self::Secp256k1 => write!(f, "secp256k1"),
+        }
+    }
+}
+
+/// Algorithm-specific verifying key
+#[derive(Debug, Clone)]
+pub(crate) enum VerifyingKeyKind {
+    Ed25519(ed25519_dalek::VerifyingKey),
+    #[cfg(feature = "p256")]
+    P256(p256::ecdsa::VerifyingKey),
+    #[cfg(feature = "secp256k1")]
+    Secp256k1(k256::ecdsa::VerifyingKey),
+}
+
+impl VerifyingKeyKind {
+    /// Parse a verifying key from its canonical encoding
+    ///
+    /// Ed25519 keys are 32 raw bytes; ECDSA keys are SEC1 points, either
+    /// compressed or uncompressed.
+    pub(crate) fn from_bytes(algorithm: KeyAlgorithm, bytes: &[u8]) -> Result<Self> {
+        algorithm.ensure_enabled()?;
+        match algorithm {
+            KeyAlgorithm::Ed25519 => {
+                let bytes: &[u8; 32] = bytes.try_into().map_err(|_| {
+                    CryptoError::InvalidKeyFormat("Invalid Ed25519 public key length".into())
+                })?;
+                ed25519_dalek::VerifyingKey::from_bytes(bytes)
+                    .map(Self::Ed25519)
+                    .map_err(|e| CryptoError::InvalidKeyFormat(e.to_string()))
+            }
+            #[cfg(feature = "p256")]
+            KeyAlgorithm::P256 => p256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                .map(Self::P256)
+                .map_err(|e| CryptoError::InvalidKeyFormat(e.to_string())),
+            #[cfg(feature = "secp256k1")]
+            KeyAlgorithm::Secp256k1 => k256::ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                .map(Self::Secp256k1)
+                .map_err(|e| CryptoError::InvalidKeyFormat(e.to_string())),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("disabled algorithms are rejected above"),
+        }
+    }
+
+    /// Get the algorithm of this key
+    pub(crate) fn algorithm(&self) -> KeyAlgorithm {
+        match self {
+            Self::Ed25519(_) => KeyAlgorithm::Ed25519,
+            #[cfg(feature = "p256")]
+            Self::P256(_) => KeyAlgorithm::P256,
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(_) => KeyAlgorithm::Secp256k1,
+        }
+    }
+
+    /// Get the canonical encoding of this key (compressed SEC1 for ECDSA)
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Ed25519(key) => key.to_bytes().to_vec(),
+            #[cfg(feature = "p256")]
+            Self::P256(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+        }
+    }
+
+    /// Verify a signature over a message
+    ///
+    /// Returns `Ok(false)` if the signature does not match and an error if
+    /// the signature is malformed. Signatures are checked strictly so that a
+    /// valid signature cannot be altered into another valid one: Ed25519
+    /// rejects small-order keys and `R` components, and ECDSA rejects
+    /// signatures whose `s` is in the upper half of the curve order.
+    pub(crate) fn verify(&self, message: &[u8], signature: &[u8]) -> Result<bool> {
+        let invalid_length = || CryptoError::InvalidSignature("Invalid signature length".into());
+        match self {
+            Self::Ed25519(key) => {
+                let bytes: [u8; 64] = signature.try_into().map_err(|_| invalid_length())?;
+                let signature = ed25519_dalek::Signature::from_bytes(&bytes);
+                Ok(key.verify_strict(message, &signature).is_ok())
+            }
+            #[cfg(feature = "p256")]
+            Self::P256(key) => {
+                let signature = p256::ecdsa::Signature::from_slice(signature)
+                    .map_err(|e| CryptoError::InvalidSignature(e.to_string()))?;
+                if signature.normalize_s().is_some() {
+                    return Ok(false);
+                }
+                Ok(key.verify(message, &signature).is_ok())
+            }
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(key) => {
+                let signature = k256::ecdsa::Signature::from_slice(signature)
+                    .map_err(|e| CryptoError::InvalidSignature(e.to_string()))?;
+                if signature.normalize_s().is_some() {
+                    return Ok(false);
+                }
+                Ok(key.verify(message, &signature).is_ok())
+            }
+        }
+    }
+}
+
+impl PartialEq for VerifyingKeyKind {
+    fn eq(&self, other: &Self) -> bool {
+        self.algorithm() == other.algorithm() && self.to_bytes() == other.to_bytes()
+    }
+}
+
+impl Eq for VerifyingKeyKind {}
+
+/// Algorithm-specific signing key
+#[derive(Clone)]
+pub(crate) enum SigningKeyKind {
+    Ed25519(ed25519_dalek::SigningKey),
+    #[cfg(feature = "p256")]
+    P256(p256::ecdsa::SigningKey),
+    #[cfg(feature = "secp256k1")]
+    Secp256k1(k256::ecdsa::SigningKey),
+}
+
+impl SigningKeyKind {
+    /// Generate a random signing key
+    pub(crate) fn generate(algorithm: KeyAlgorithm) -> Result<Self> {
+        algorithm.ensure_enabled()?;
+        match algorithm {
+            KeyAlgorithm::Ed25519 => {
+                let mut secret = Zeroizing::new([0u8; 32]);
+                OsRng.fill_bytes(secret.as_mut());
+                Ok(Self::Ed25519(ed25519_dalek::SigningKey::from_bytes(
+                    &secret,
+                )))
+            }
+            #[cfg(feature = "p256")]
+            KeyAlgorithm::P256 => Ok(Self::P256(p256::ecdsa::SigningKey::random(&mut OsRng))),
+            #[cfg(feature = "secp256k1")]
+            KeyAlgorithm::Secp256k1 => {
+                Ok(Self::Secp256k1(k256::ecdsa::SigningKey::random(&mut OsRng)))
+            }
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("disabled algorithms are rejected above"),
+        }
+    }
+
+    /// Parse a signing key from its 32-byte secret
+    pub(crate) fn from_bytes(algorithm: KeyAlgorithm, bytes: &[u8]) -> Result<Self> {
+        algorithm.ensure_enabled()?;
+        match algorithm {
+            KeyAlgorithm::Ed25519 => {
+                let bytes: &[u8; 32] = bytes.try_into().map_err(|_| {
+                    CryptoError::InvalidKeyFormat("Invalid Ed25519 private key length".into())
+                })?;
+                Ok(Self::Ed25519(ed25519_dalek::SigningKey::from_bytes(bytes)))
+            }
+            #[cfg(feature = "p256")]
+            KeyAlgorithm::P256 => p256::ecdsa::SigningKey::from_slice(bytes)
+                .map(Self::P256)
+                .map_err(|e| CryptoError::InvalidKeyFormat(e.to_string())),
+            #[cfg(feature = "secp256k1")]
+            KeyAlgorithm::Secp256k1 => k256::ecdsa::SigningKey::from_slice(bytes)
+                .map(Self::Secp256k1)
+                .map_err(|e| CryptoError::InvalidKeyFormat(e.to_string())),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("disabled algorithms are rejected above"),
+        }
+    }
+
+    /// Get the 32-byte secret of this key
+    pub(crate) fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(match self {
+            Self::Ed25519(key) => key.to_bytes().to_vec(),
+            #[cfg(feature = "p256")]
+            Self::P256(key) => key.to_bytes().to_vec(),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(key) => key.to_bytes().to_vec(),
+        })
+    }
+
+    /// Get the verifying key matching this signing key
+    pub(crate) fn verifying_key(&self) -> VerifyingKeyKind {
+        match self {
+            Self::Ed25519(key) => VerifyingKeyKind::Ed25519(key.verifying_key()),
+            #[cfg(feature = "p256")]
+            Self::P256(key) => VerifyingKeyKind::P256(*key.verifying_key()),
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(key) => VerifyingKeyKind::Secp256k1(*key.verifying_key()),
+        }
+    }
+
+    /// Sign a message, returning the raw signature bytes
+    ///
+    /// ECDSA signatures are deterministic (RFC 6979) and encoded as `r || s`.
+    pub(crate) fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Ed25519(key) => Ok(key.sign(message).to_bytes().to_vec()),
+            #[cfg(feature = "p256")]
+            Self::P256(key) => {
+                let signature: p256::ecdsa::Signature = key
+                    .try_sign(message)
+                    .map_err(|e| CryptoError::InternalError(e.to_string()))?;
+                // Emit the low-S form that verification requires
+                let signature = signature.normalize_s().unwrap_or(signature);
+                Ok(signature.to_bytes().to_vec())
+            }
+            #[cfg(feature = "secp256k1")]
+            Self::Secp256k1(key) => {
+                let signature: k256::ecdsa::Signature = key
+                    .try_sign(message)
+                    .map_err(|e| CryptoError::InternalError(e.to_string()))?;
+                let signature = signature.normalize_s().unwrap_or(signature);
+                Ok(signature.to_bytes().to_vec())
+            }
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! JSON Canonicalization Scheme (RFC 8785) and detached signatures over it.
+//!
+//! [`canonicalize`] turns any serializable value into the unique JCS byte
+//! representation, so independent implementations sign and verify exactly
+//! the same bytes. [`sign_canonical`] and [`verify_canonical`] build on it to
+//! produce and check a [`DetachedSignature`] bound to a [`SigningContext`].
+
+use std::fmt::Write as _;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{CryptoError, KeyAlgorithm, PublicKey, Result, Signature, Signer, SigningContext};
+
+/// Largest integer that IEEE 754 doubles, and so JCS, represent exactly
+pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Serialize a value to its RFC 8785 canonical JSON form
+///
+/// Object members are sorted by the UTF-16 code units of their names,
+/// numbers use the ECMAScript number serialization and strings use the
+/// shortest escapes. JCS treats all numbers as doubles, so integers beyond
+/// ±[`MAX_SAFE_INTEGER`] are rejected rather than rounded to a neighbour.
+pub fn canonicalize<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let value = serde_json::to_value(value)
+        .map_err(|e| CryptoError::CanonicalizationError(e.to_string()))?;
+
+    let mut out = String::new();
+    write_value(&mut out, &value)?;
+    Ok(out.into_bytes())
+}
+
+fn write_value(out: &mut String, value: &Value) -> Result<()> {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(true) => out.push_str("true"),
+        Value::Bool(false) => out.push_str("false"),
+        Value::Number(number) => {
+            let magnitude = number
+                .as_u64()
+                .or_else(|| number.as_i64().map(i64::unsigned_abs));
+            if magnitude.is_some_and(|magnitude| magnitude > MAX_SAFE_INTEGER) {
+                return Err(CryptoError::CanonicalizationError(format!(
+                    "Integer {} cannot be represented exactly",
+                    number
+                )));
+            }
+            let number = number.as_f64().ok_or_else(|| {
+                CryptoError::CanonicalizationError(format!("Unsupported number: {}", number))
+            })?;
+            write_number(out, number)?;
+        }
+        Value::String(string) => write_string(out, string),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item)?;
+            }
+            out.push(']');
+        }
+        Value::Object(members) => {
+            let mut members: Vec<_> = members.iter().collect();
+            members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+
+            out.push('{');
+            for (i, (name, member)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, name);
+                out.push(':');
+                write_value(out, member)?;
+            }
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+fn write_string(out: &mut String, string: &str) {
+    out.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{08}' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\u{0c}' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Write a number as ECMAScript's `Number.prototype.toString` would
+fn write_number(out: &mut String, number: f64) -> Result<()> {
+    if !number.is_finite() {
+        return Err(CryptoError::CanonicalizationError(
+            "NaN and infinite numbers are not allowed".into(),
+        ));
+    }
+    if number == 0.0 {
+        out.push('0');
+        return Ok(());
+    }
+    if number < 0.0 {
+        out.push('-');
+    }
+
+    // The shortest round-trip representation fixes the number of digits;
+    // formatting again with that precision picks the closest such decimal,
+    // breaking ties towards an even digit as ECMAScript requires
+    let shortest = format!("{:e}", number.abs());
+    let precision = shortest
+        .split_once('e')
+        .map_or(0, |(m, _)| m.len().saturating_sub(2));
+    let scientific = format!("{:.*e}", precision, number.abs());
+
+    // number = 0.digits × 10^n
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("LowerExp output has an exponent");
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    let n = exponent
+        .parse::<i32>()
+        .expect("LowerExp exponent is an integer")
+        + 1;
+
+    if k <= n && n <= 21 {
+        out.push_str(&digits);
+        out.extend(std::iter::repeat_n('0', (n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        out.push_str(&digits[..n as usize]);
+        out.push('.');
+        out.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.extend(std::iter::repeat_n('0', (-n) as usize));
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        let _ = write!(out, "e{}{}", if n > 0 { '+' } else { '-' }, (n - 1).abs());
+    }
+    Ok(())
+}
+
+/// A signature over the canonical form of a value, stored apart from it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetachedSignature {
+    /// The ID of the signing key
+    #[serde(rename = "kid")]
+    pub key_id: String,
+    /// The signature algorithm
+    #[serde(rename = "alg")]
+    pub algorithm: KeyAlgorithm,
+    /// When the signature was created
+    pub created: DateTime<Utc>,
+    /// The raw signature bytes
+    #[serde(rename = "sig", with = "crate::base64url")]
+    pub signature: Vec<u8>,
+}
+
+/// The bytes covered by a detached signature
+///
+/// The key ID, algorithm and creation time are signed together with the
+/// value so that none of them can be changed after signing.
+#[derive(Serialize)]
+struct SigningInput<'a, T: ?Sized> {
+    #[serde(rename = "kid")]
+    key_id: &'a str,
+    #[serde(rename = "alg")]
+    algorithm: KeyAlgorithm,
+    created: DateTime<Utc>,
+    payload: &'a T,
+}
+
+impl DetachedSignature {
+    /// Get the canonical bytes this signature covers for the given value
+    fn signing_input<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>> {
+        canonicalize(&SigningInput {
+            key_id: &self.key_id,
+            algorithm: self.algorithm,
+            created: self.created,
+            payload: value,
+        })
+    }
+}
+
+/// Sign the canonical form of a value in the given context
+pub async fn sign_canonical<T: Serialize + ?Sized>(
+    context: &SigningContext,
+    value: &T,
+    signer: &dyn Signer,
+) -> Result<DetachedSignature> {
+    let public_key = signer.public_key().await?;
+    // Whole seconds keep the timestamp representation identical everywhere
+    let created = DateTime::from_timestamp(Utc::now().timestamp(), 0)
+        .ok_or_else(|| CryptoError::InternalError("Invalid current time".into()))?;
+
+    let mut detached = DetachedSignature {
+        key_id: signer.key_id().await?,
+        algorithm: public_key.algorithm(),
+        created,
+        signature: Vec::new(),
+    };
+    let signature = context
+        .sign_with(&detached.signing_input(value)?, signer)
+        .await?;
+    detached.signature = signature.signature_bytes;
+    Ok(detached)
+}
+
+/// Verify a detached signature over the canonical form of a value, made in
+/// the expected context
+pub fn verify_canonical<T: Serialize + ?Sized>(
+    context: &SigningContext,
+    value: &T,
+    signature: &DetachedSignature,
+    public_key: &PublicKey,
+) -> Result<()> {
+    if signature.key_id != public_key.key_id() {
+        return Err(CryptoError::InvalidSignature(format!(
+            "Signature was made by key {}",
+            signature.key_id
+        )));
+    }
+
+    let raw = Signature::from_algorithm_bytes(signature.algorithm, &signature.signature)?;
+    context.verify(&signature.signing_input(value)?, &raw, public_key)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InMemorySigner, KeyPair};
+
+    fn canonical_string(value: &Value) -> String {
+        String::from_utf8(canonicalize(value).unwrap()).unwrap()
+    }
+
+    // RFC 8785, Section 3.2.2
+    #[test]
+    fn test_rfc8785_sample() {
+        let input: Value = serde_json::from_str(
+            r#"{
+                "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+                "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+                "literals": [null, true, false]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            canonical_string(&input),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+    }
+
+    // RFC 8785, Section 3.2.3
+    #[test]
+    fn test_rfc8785_sorting() {
+        let input: Value = serde_json::from_str(
+            r#"{
+                "€": "Euro Sign",
+                "\r": "Carriage Return",
+                "דּ": "Hebrew Letter Dalet With Dagesh",
+                "1": "One",
+                "😀": "Emoji: Grinning Face",
+                "\u0080": "Control",
+                "ö": "Latin Small Letter O With Diaeresis"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            canonical_string(&input),
+            concat!(
+                r#"{"\r":"Carriage Return","1":"One","#,
+                "\"\u{80}\":\"Control\",",
+                "\"\u{f6}\":\"Latin Small Letter O With Diaeresis\",",
+                "\"\u{20ac}\":\"Euro Sign\",",
+                "\"\u{1f600}\":\"Emoji: Grinning Face\",",
+                "\"\u{fb33}\":\"Hebrew Letter Dalet With Dagesh\"}",
+            )
+        );
+    }
+
+    // RFC 8785, Appendix B
+    #[test]
+    fn test_rfc8785_numbers() {
+        let vectors: &[(u64, &str)] = &[
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ];
+
+        for (bits, expected) in vectors {
+            let mut out = String::new();
+            write_number(&mut out, f64::from_bits(*bits)).unwrap();
+            assert_eq!(&out, expected, "number {:#018x}", bits);
+        }
+
+        let mut out = String::new();
+        assert!(write_number(&mut out, f64::NAN).is_err());
+        assert!(write_number(&mut out, f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn test_integers_and_structs() {
+        #[derive(Serialize)]
+        struct Attestation {
+            subject: &'static str,
+            score: u32,
+            delta: i64,
+        }
+
+        let attestation = Attestation {
+            subject: "agent-1",
+            score: 100,
+            delta: -5,
+        };
+        assert_eq!(
+            String::from_utf8(canonicalize(&attestation).unwrap()).unwrap(),
+            r#"{"delta":-5,"score":100,"subject":"agent-1"}"#
+        );
+
+        // Integers that doubles cannot hold exactly are rejected, so that
+        // neighbouring values never share a canonical form
+        let max = MAX_SAFE_INTEGER as i64;
+        assert_eq!(canonicalize(&max).unwrap(), b"9007199254740991");
+        assert_eq!(canonicalize(&-max).unwrap(), b"-9007199254740991");
+        for unsafe_integer in [9007199254740993u64, 9007199254740992, u64::MAX] {
+            assert!(matches!(
+                canonicalize(&unsafe_integer),
+                Err(CryptoError::CanonicalizationError(_))
+            ));
+        }
+        assert!(canonicalize(&-(max + 1)).is_err());
+        assert!(canonicalize(&i64::MIN).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sign_and_verify_canonical() {
+        let key_pair = KeyPair::generate().unwrap();
+        let signer = InMemorySigner::new(key_pair.clone());
+        let value = serde_json::json!({"b": 1, "a": [true, null]});
+
+        let signature = sign_canonical(&SigningContext::DOCUMENT, &value, &signer)
+            .await
+            .unwrap();
+        assert_eq!(signature.key_id, key_pair.public_key().key_id());
+        assert_eq!(signature.algorithm, KeyAlgorithm::Ed25519);
+        verify_canonical(
+            &SigningContext::DOCUMENT,
+            &value,
+            &signature,
+            key_pair.public_key(),
+        )
+        .unwrap();
+
+        // Member order and whitespace do not matter
+        let reordered: Value = serde_json::from_str(r#"{ "a": [true, null], "b": 1 }"#).unwrap();
+        verify_canonical(
+            &SigningContext::DOCUMENT,
+            &reordered,
+            &signature,
+            key_pair.public_key(),
+        )
+        .unwrap();
+
+        // The detached signature survives serialization
+        let json = serde_json::to_string(&signature).unwrap();
+        let decoded: DetachedSignature = serde_json::from_str(&json).unwrap();
+        verify_canonical(
+            &SigningContext::DOCUMENT,
+            &value,
+            &decoded,
+            key_pair.public_key(),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verify_canonical_rejects_changes() {
+        let key_pair = KeyPair::generate().unwrap();
+        let signer = InMemorySigner::new(key_pair.clone());
+        let value = serde_json::json!({"amount": 10});
+        let signature = sign_canonical(&SigningContext::DOCUMENT, &value, &signer)
+            .await
+            .unwrap();
+
+        let changed = serde_json::json!({"amount": 11});
+        assert!(verify_canonical(
+            &SigningContext::DOCUMENT,
+            &changed,
+            &signature,
+            key_pair.public_key()
+        )
+        .is_err());
+
+        let mut backdated = signature.clone();
+        backdated.created -= chrono::Duration::days(1);
+        assert!(verify_canonical(
+            &SigningContext::DOCUMENT,
+            &value,
+            &backdated,
+            key_pair.public_key()
+        )
+        .is_err());
+
+        assert!(verify_canonical(
+            &SigningContext::ATTESTATION,
+            &value,
+            &signature,
+            key_pair.public_key()
+        )
+        .is_err());
+
+        let other = KeyPair::generate().unwrap();
+        assert!(matches!(
+            verify_canonical(
+                &SigningContext::DOCUMENT,
+                &value,
+                &signature,
+                other.public_key()
+            ),
+            Err(CryptoError::InvalidSignature(_))
+        ));
+    }
+}
@@ -0,0 +1,198 @@
+//! Domain-separated signing contexts.
+//!
+//! Every signature the SDK produces is bound to a [`SigningContext`] naming
+//! the kind of message being signed, such as a challenge response or an
+//! attestation. The context is mixed into the signed bytes, so a signature
+//! made for one kind of message never verifies as another, even when the
+//! message bytes are identical.
+//!
+//! The signed bytes are `"agentid-signature\0" || len(context) || context ||
+//! message`, with the context length as a single byte. This works the same
+//! for every [`KeyAlgorithm`](crate::KeyAlgorithm), unlike Ed25519ctx.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CryptoError, PrivateKey, PublicKey, Result, Signature, Signer};
+
+/// Prefix of every context-bound signing input
+const SIGNING_INPUT_PREFIX: &[u8] = b"agentid-signature\0";
+
+/// Maximum length of a context name in bytes
+pub const MAX_CONTEXT_LENGTH: usize = 255;
+
+/// The kind of message a signature is made for
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SigningContext(Cow<'static, str>);
+
+impl SigningContext {
+    /// Responses to proof-of-possession challenges
+    pub const CHALLENGE: Self = Self::from_static("agentid/challenge/v1");
+
+    /// Attestations made about an agent
+    pub const ATTESTATION: Self = Self::from_static("agentid/attestation/v1");
+
+    /// Delegations of authority to another key or agent
+    pub const DELEGATION: Self = Self::from_static("agentid/delegation/v1");
+
+    /// Detached signatures over canonical JSON documents
+    pub const DOCUMENT: Self = Self::from_static("agentid/document/v1");
+
+    /// Approvals collected in a [`MultiSignature`](crate::MultiSignature)
+    pub const MULTISIG: Self = Self::from_static("agentid/multisig/v1");
+
+    /// Create a context from a static name, panicking if the name is invalid
+    ///
+    /// Intended for constants, where the check runs at compile time.
+    pub const fn from_static(name: &'static str) -> Self {
+        assert!(is_valid_name(name.as_bytes()), "invalid signing context");
+        Self(Cow::Borrowed(name))
+    }
+
+    /// Create a context from a name such as `"myapp/receipt/v1"`
+    ///
+    /// Names must be between 1 and [`MAX_CONTEXT_LENGTH`] bytes of printable
+    /// ASCII.
+    pub fn new(name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        if !is_valid_name(name.as_bytes()) {
+            return Err(CryptoError::InvalidSigningContext(format!("{:?}", name)));
+        }
+        Ok(Self(Cow::Owned(name)))
+    }
+
+    /// Get the context name
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Get the bytes actually signed for a message in this context
+    pub fn signing_input(&self, message: &[u8]) -> Vec<u8> {
+        let name = self.0.as_bytes();
+        let mut input =
+            Vec::with_capacity(SIGNING_INPUT_PREFIX.len() + 1 + name.len() + message.len());
+        input.extend_from_slice(SIGNING_INPUT_PREFIX);
+        // Names are at most 255 bytes, checked on construction
+        input.push(name.len() as u8);
+        input.extend_from_slice(name);
+        input.extend_from_slice(message);
+        input
+    }
+
+    /// Sign a message in this context
+    pub fn sign(&self, message: &[u8], private_key: &PrivateKey) -> Result<Signature> {
+        private_key.sign(&self.signing_input(message))
+    }
+
+    /// Sign a message in this context with a possibly remote signer
+    pub async fn sign_with(&self, message: &[u8], signer: &dyn Signer) -> Result<Signature> {
+        signer.sign(&self.signing_input(message)).await
+    }
+
+    /// Verify a signature made over a message in this context
+    pub fn verify(
+        &self,
+        message: &[u8],
+        signature: &Signature,
+        public_key: &PublicKey,
+    ) -> Result<bool> {
+        signature.verify(&self.signing_input(message), public_key)
+    }
+}
+
+/// Check that a context name is non-empty, short enough and printable ASCII
+const fn is_valid_name(name: &[u8]) -> bool {
+    if name.is_empty() || name.len() > MAX_CONTEXT_LENGTH {
+        return false;
+    }
+    let mut i = 0;
+    while i < name.len() {
+        if !name[i].is_ascii_graphic() {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+impl fmt::Display for SigningContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for SigningContext {
+    type Error = CryptoError;
+
+    fn try_from(name: String) -> Result<Self> {
+        Self::new(name)
+    }
+}
+
+impl From<SigningContext> for String {
+    fn from(context: SigningContext) -> Self {
+        context.0.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InMemorySigner, KeyPair};
+
+    #[test]
+    fn test_signature_is_bound_to_context() {
+        let key_pair = KeyPair::generate().unwrap();
+        let message = b"same bytes";
+
+        let signature = SigningContext::CHALLENGE
+            .sign(message, key_pair.private_key())
+            .unwrap();
+        assert!(SigningContext::CHALLENGE
+            .verify(message, &signature, key_pair.public_key())
+            .unwrap());
+
+        // Neither another context nor the raw message verifies
+        assert!(SigningContext::ATTESTATION
+            .verify(message, &signature, key_pair.public_key())
+            .is_err());
+        assert!(signature.verify(message, key_pair.public_key()).is_err());
+    }
+
+    #[test]
+    fn test_context_boundaries_are_unambiguous() {
+        // Moving bytes between context name and message changes the input
+        let short = SigningContext::new("app/a").unwrap();
+        let long = SigningContext::new("app/ab").unwrap();
+        assert_ne!(short.signing_input(b"bc"), long.signing_input(b"c"));
+    }
+
+    #[test]
+    fn test_context_names_are_validated() {
+        assert!(SigningContext::new("").is_err());
+        assert!(SigningContext::new("has space").is_err());
+        assert!(SigningContext::new("x".repeat(MAX_CONTEXT_LENGTH + 1)).is_err());
+        assert!(SigningContext::new("x".repeat(MAX_CONTEXT_LENGTH)).is_ok());
+
+        let context: SigningContext = serde_json::from_str("\"myapp/receipt/v1\"").unwrap();
+        assert_eq!(context.as_str(), "myapp/receipt/v1");
+        assert!(serde_json::from_str::<SigningContext>("\"\"").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sign_with_signer() {
+        let key_pair = KeyPair::generate().unwrap();
+        let signer = InMemorySigner::new(key_pair.clone());
+
+        let signature = SigningContext::DELEGATION
+            .sign_with(b"delegate", &signer)
+            .await
+            .unwrap();
+        assert!(SigningContext::DELEGATION
+            .verify(b"delegate", &signature, key_pair.public_key())
+            .unwrap());
+    }
+}
@@ -1,6 +1,15 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use ring::aead::{self, Aad};
 use ring::rand::SecureRandom;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use zeroize::Zeroizing;
+
+use crate::SecretBytes;
+
+/// Domain separator for key identifiers of symmetric keys
+const KEY_ID_CONTEXT: &[u8] = b"agentid/encryption-key-id/v1";
 
 /// Encrypted data with associated metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,13 +20,19 @@ pub struct EncryptedData {
     pub nonce: Vec<u8>,
     /// Additional authenticated data (if any)
     pub aad: Option<Vec<u8>>,
+    /// The sender's ephemeral X25519 public key, for public-key encryption
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ephemeral_key: Option<Vec<u8>>,
 }
 
 /// An encryption key for symmetric encryption
-#[derive(Debug, Clone)]
+///
+/// The key bytes are wiped from memory on drop and redacted from `Debug`
+/// output.
+#[derive(Clone)]
 pub struct EncryptionKey {
     /// The raw key bytes
-    key_bytes: Vec<u8>,
+    key_bytes: SecretBytes,
     /// The AEAD key
     #[doc(hidden)]
     aead_key: aead::LessSafeKey,
@@ -32,24 +47,36 @@ impl EncryptionKey {
         );
 
         Ok(Self {
-            key_bytes: bytes.to_vec(),
+            key_bytes: SecretBytes::from_slice(bytes),
             aead_key,
         })
     }
 
     /// Generate a new random encryption key
     pub fn generate() -> crate::Result<Self> {
-        let mut key_bytes = vec![0u8; 32]; // 256 bits for ChaCha20-Poly1305
+        let mut key_bytes = Zeroizing::new([0u8; 32]); // 256 bits for ChaCha20-Poly1305
         ring::rand::SystemRandom::new()
-            .fill(&mut key_bytes)
+            .fill(key_bytes.as_mut())
             .map_err(|e| crate::CryptoError::KeyGenerationError(e.to_string()))?;
 
-        Self::from_bytes(&key_bytes)
+        Self::from_bytes(key_bytes.as_ref())
     }
 
     /// Get the raw key bytes
     pub fn as_bytes(&self) -> &[u8] {
-        &self.key_bytes
+        self.key_bytes.expose_secret()
+    }
+
+    /// Get a stable identifier for this key
+    ///
+    /// The identifier is a truncated hash of the key bytes, so it can be
+    /// stored next to ciphertext to find the right key after rotation.
+    pub fn key_id(&self) -> String {
+        let digest = Sha256::new()
+            .chain_update(KEY_ID_CONTEXT)
+            .chain_update(self.as_bytes())
+            .finalize();
+        URL_SAFE_NO_PAD.encode(&digest[..16])
     }
 
     /// Encrypt data with this key
@@ -65,13 +92,14 @@ impl EncryptionKey {
         let aad_bytes = aad.unwrap_or(&[]);
         let aad = Aad::from(aad_bytes);
         self.aead_key
-            .seal_in_place_separate_tag(nonce, aad, &mut in_out)
+            .seal_in_place_append_tag(nonce, aad, &mut in_out)
             .map_err(|e| crate::CryptoError::EncryptionError(e.to_string()))?;
 
         Ok(EncryptedData {
             ciphertext: in_out,
             nonce: nonce_bytes.to_vec(),
             aad: Some(aad_bytes.to_vec()),
+            ephemeral_key: None,
         })
     }
 
@@ -83,10 +111,66 @@ impl EncryptionKey {
         let mut ciphertext = encrypted.ciphertext.clone();
         let aad = Aad::from(encrypted.aad.as_deref().unwrap_or(&[]));
 
-        self.aead_key
+        let plaintext_len = self
+            .aead_key
             .open_in_place(nonce, aad, &mut ciphertext)
-            .map_err(|e| crate::CryptoError::DecryptionError(e.to_string()))?;
+            .map_err(|e| crate::CryptoError::DecryptionError(e.to_string()))?
+            .len();
+        ciphertext.truncate(plaintext_len);
 
         Ok(ciphertext)
     }
 }
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("key_bytes", &self.key_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let key = EncryptionKey::generate().unwrap();
+        let encrypted = key.encrypt(b"hello agent", Some(b"context")).unwrap();
+        assert_ne!(encrypted.ciphertext, b"hello agent");
+
+        let decrypted = key.decrypt(&encrypted).unwrap();
+        assert_eq!(decrypted, b"hello agent");
+    }
+
+    #[test]
+    fn test_decrypt_rejects_tampered_ciphertext() {
+        let key = EncryptionKey::generate().unwrap();
+        let mut encrypted = key.encrypt(b"hello agent", None).unwrap();
+        encrypted.ciphertext[0] ^= 0x01;
+
+        assert!(matches!(
+            key.decrypt(&encrypted),
+            Err(crate::CryptoError::DecryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_key_id_is_stable() {
+        let key = EncryptionKey::from_bytes(&[0x42; 32]).unwrap();
+        assert_eq!(
+            key.key_id(),
+            EncryptionKey::from_bytes(&[0x42; 32]).unwrap().key_id()
+        );
+        assert_ne!(key.key_id(), EncryptionKey::generate().unwrap().key_id());
+    }
+
+    #[test]
+    fn test_debug_redacts_key() {
+        let key = EncryptionKey::from_bytes(&[0x42; 32]).unwrap();
+        let debug = format!("{:?}", key);
+        assert!(debug.contains("REDACTED"));
+        assert!(!debug.contains("66"));
+    }
+}
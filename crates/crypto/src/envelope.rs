@@ -0,0 +1,412 @@
+//! Versioned, self-describing envelopes for envelope encryption.
+//!
+//! Each payload is sealed under a fresh data-encryption key (DEK), and the
+//! DEK is wrapped by a long-lived key-encryption key (KEK). The envelope
+//! records the format version, the algorithm and the ID of the KEK, so old
+//! ciphertext can still be opened after the KEK rotates, and rotating a KEK
+//! only requires re-wrapping the DEK with [`Envelope::rewrap`].
+//!
+//! Envelopes are encoded either as JSON (binary fields in unpadded
+//! base64url) or in a compact binary form:
+//!
+//! ```text
+//! magic "AIDE" | version u8 | algorithm u8 | key ID length u8 | key ID
+//!     | wrapped key length u16 | wrapped key | nonce (12 bytes)
+//!     | AAD flag u8 | [AAD length u32 | AAD] | ciphertext
+//! ```
+//!
+//! All lengths are big-endian.
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{CryptoError, EncryptedData, EncryptionKey, Result};
+
+/// Current envelope format version
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// Magic bytes starting the binary encoding
+const ENVELOPE_MAGIC: &[u8; 4] = b"AIDE";
+
+/// Length of ChaCha20-Poly1305 nonces
+const NONCE_LEN: usize = 12;
+
+/// Symmetric algorithm used for both the payload and the key wrap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EnvelopeAlgorithm {
+    /// ChaCha20-Poly1305 with 256-bit keys
+    #[serde(rename = "C20P")]
+    ChaCha20Poly1305,
+}
+
+impl EnvelopeAlgorithm {
+    /// Get the identifier used in the binary encoding
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::ChaCha20Poly1305 => 1,
+        }
+    }
+
+    /// Parse the identifier used in the binary encoding
+    fn from_byte(byte: u8) -> Result<Self> {
+        match byte {
+            1 => Ok(Self::ChaCha20Poly1305),
+            _ => Err(CryptoError::UnsupportedAlgorithm(format!(
+                "Unknown envelope algorithm: {}",
+                byte
+            ))),
+        }
+    }
+}
+
+/// A payload encrypted under a per-message key that is wrapped by a KEK
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// The envelope format version
+    pub version: u8,
+    /// The algorithm used for the payload and the key wrap
+    #[serde(rename = "alg")]
+    pub algorithm: EnvelopeAlgorithm,
+    /// The ID of the key-encryption key that wrapped the data key
+    #[serde(rename = "kid")]
+    pub key_id: String,
+    /// The wrapped data key: nonce followed by the sealed key
+    #[serde(with = "crate::base64url")]
+    pub wrapped_key: Vec<u8>,
+    /// The payload nonce
+    #[serde(with = "crate::base64url")]
+    pub nonce: Vec<u8>,
+    /// The sealed payload
+    #[serde(with = "crate::base64url")]
+    pub ciphertext: Vec<u8>,
+    /// Additional authenticated data bound to the payload (if any)
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::base64url::option"
+    )]
+    pub aad: Option<Vec<u8>>,
+}
+
+impl Envelope {
+    /// Encrypt data under a fresh data key wrapped by `kek`
+    pub fn seal(kek: &EncryptionKey, data: &[u8], aad: Option<&[u8]>) -> Result<Self> {
+        let dek = EncryptionKey::generate()?;
+        let payload = dek.encrypt(data, aad)?;
+
+        let mut envelope = Self {
+            version: ENVELOPE_VERSION,
+            algorithm: EnvelopeAlgorithm::ChaCha20Poly1305,
+            key_id: String::new(),
+            wrapped_key: Vec::new(),
+            nonce: payload.nonce,
+            ciphertext: payload.ciphertext,
+            aad: aad.map(<[u8]>::to_vec),
+        };
+        envelope.wrap_key(&dek, kek)?;
+        Ok(envelope)
+    }
+
+    /// Decrypt the payload with the key-encryption key it was wrapped by
+    pub fn open(&self, kek: &EncryptionKey) -> Result<Vec<u8>> {
+        let dek = self.unwrap_key(kek)?;
+        dek.decrypt(&EncryptedData {
+            ciphertext: self.ciphertext.clone(),
+            nonce: self.nonce.clone(),
+            aad: self.aad.clone(),
+            ephemeral_key: None,
+        })
+    }
+
+    /// Decrypt the payload, picking the matching key from `keks` by key ID
+    pub fn open_with(&self, keks: &[EncryptionKey]) -> Result<Vec<u8>> {
+        let kek = keks
+            .iter()
+            .find(|kek| kek.key_id() == self.key_id)
+            .ok_or_else(|| CryptoError::KeyNotFound(self.key_id.clone()))?;
+        self.open(kek)
+    }
+
+    /// Re-wrap the data key under `new_kek` without decrypting the payload
+    pub fn rewrap(&self, old_kek: &EncryptionKey, new_kek: &EncryptionKey) -> Result<Self> {
+        let dek = self.unwrap_key(old_kek)?;
+        let mut envelope = self.clone();
+        envelope.wrap_key(&dek, new_kek)?;
+        Ok(envelope)
+    }
+
+    /// Encode this envelope as JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(|e| CryptoError::InternalError(e.to_string()))
+    }
+
+    /// Decode an envelope from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        let envelope: Self = serde_json::from_str(json)
+            .map_err(|e| CryptoError::DecryptionError(format!("Invalid envelope: {}", e)))?;
+        envelope.check_version()?;
+        Ok(envelope)
+    }
+
+    /// Encode this envelope in the compact binary form
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let key_id_len = u8::try_from(self.key_id.len())
+            .map_err(|_| CryptoError::EncryptionError("Key ID is too long".into()))?;
+        let wrapped_len = u16::try_from(self.wrapped_key.len())
+            .map_err(|_| CryptoError::EncryptionError("Wrapped key is too long".into()))?;
+        if self.nonce.len() != NONCE_LEN {
+            return Err(CryptoError::EncryptionError("Invalid nonce length".into()));
+        }
+
+        let mut bytes = Vec::with_capacity(64 + self.wrapped_key.len() + self.ciphertext.len());
+        bytes.extend_from_slice(ENVELOPE_MAGIC);
+        bytes.push(self.version);
+        bytes.push(self.algorithm.to_byte());
+        bytes.push(key_id_len);
+        bytes.extend_from_slice(self.key_id.as_bytes());
+        bytes.extend_from_slice(&wrapped_len.to_be_bytes());
+        bytes.extend_from_slice(&self.wrapped_key);
+        bytes.extend_from_slice(&self.nonce);
+        match &self.aad {
+            Some(aad) => {
+                let aad_len = u32::try_from(aad.len())
+                    .map_err(|_| CryptoError::EncryptionError("AAD is too long".into()))?;
+                bytes.push(1);
+                bytes.extend_from_slice(&aad_len.to_be_bytes());
+                bytes.extend_from_slice(aad);
+            }
+            None => bytes.push(0),
+        }
+        bytes.extend_from_slice(&self.ciphertext);
+        Ok(bytes)
+    }
+
+    /// Decode an envelope from the compact binary form
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader(bytes);
+        if reader.take(ENVELOPE_MAGIC.len())? != ENVELOPE_MAGIC {
+            return Err(CryptoError::DecryptionError(
+                "Not an envelope: bad magic bytes".into(),
+            ));
+        }
+
+        let version = reader.take(1)?[0];
+        let algorithm = EnvelopeAlgorithm::from_byte(reader.take(1)?[0])?;
+        let key_id_len = reader.take(1)?[0] as usize;
+        let key_id = String::from_utf8(reader.take(key_id_len)?.to_vec())
+            .map_err(|_| CryptoError::DecryptionError("Invalid envelope key ID".into()))?;
+        let wrapped_len = u16::from_be_bytes(reader.array()?) as usize;
+        let wrapped_key = reader.take(wrapped_len)?.to_vec();
+        let nonce = reader.take(NONCE_LEN)?.to_vec();
+        let aad = match reader.take(1)?[0] {
+            0 => None,
+            1 => {
+                let aad_len = u32::from_be_bytes(reader.array()?) as usize;
+                Some(reader.take(aad_len)?.to_vec())
+            }
+            _ => {
+                return Err(CryptoError::DecryptionError(
+                    "Invalid envelope AAD flag".into(),
+                ))
+            }
+        };
+
+        let envelope = Self {
+            version,
+            algorithm,
+            key_id,
+            wrapped_key,
+            nonce,
+            ciphertext: reader.0.to_vec(),
+            aad,
+        };
+        envelope.check_version()?;
+        Ok(envelope)
+    }
+
+    /// Wrap the data key under `kek`, binding it to this envelope's header
+    fn wrap_key(&mut self, dek: &EncryptionKey, kek: &EncryptionKey) -> Result<()> {
+        self.key_id = kek.key_id();
+        let wrapped = kek.encrypt(dek.as_bytes(), Some(&self.wrap_aad()))?;
+
+        let mut wrapped_key = wrapped.nonce;
+        wrapped_key.extend_from_slice(&wrapped.ciphertext);
+        self.wrapped_key = wrapped_key;
+        Ok(())
+    }
+
+    /// Unwrap the data key with `kek`
+    fn unwrap_key(&self, kek: &EncryptionKey) -> Result<EncryptionKey> {
+        self.check_version()?;
+        if kek.key_id() != self.key_id {
+            return Err(CryptoError::KeyNotFound(self.key_id.clone()));
+        }
+        if self.wrapped_key.len() < NONCE_LEN {
+            return Err(CryptoError::DecryptionError("Invalid wrapped key".into()));
+        }
+
+        let (nonce, ciphertext) = self.wrapped_key.split_at(NONCE_LEN);
+        let dek = Zeroizing::new(kek.decrypt(&EncryptedData {
+            ciphertext: ciphertext.to_vec(),
+            nonce: nonce.to_vec(),
+            aad: Some(self.wrap_aad()),
+            ephemeral_key: None,
+        })?);
+        EncryptionKey::from_bytes(&dek)
+    }
+
+    /// Associated data for the key wrap: the envelope header
+    fn wrap_aad(&self) -> Vec<u8> {
+        let mut aad = ENVELOPE_MAGIC.to_vec();
+        aad.push(self.version);
+        aad.push(self.algorithm.to_byte());
+        aad.extend_from_slice(self.key_id.as_bytes());
+        aad
+    }
+
+    /// Reject envelope versions this library cannot read
+    fn check_version(&self) -> Result<()> {
+        if self.version != ENVELOPE_VERSION {
+            return Err(CryptoError::DecryptionError(format!(
+                "Unsupported envelope version: {}",
+                self.version
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Cursor over the binary encoding
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(CryptoError::DecryptionError("Envelope is truncated".into()));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("length checked by take"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let kek = EncryptionKey::generate().unwrap();
+        let envelope = Envelope::seal(&kek, b"evidence bundle", Some(b"agent-7")).unwrap();
+        assert_eq!(envelope.version, ENVELOPE_VERSION);
+        assert_eq!(envelope.key_id, kek.key_id());
+        assert_eq!(envelope.open(&kek).unwrap(), b"evidence bundle");
+
+        let other = EncryptionKey::generate().unwrap();
+        assert!(matches!(
+            envelope.open(&other),
+            Err(CryptoError::KeyNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_json_and_binary_roundtrip() {
+        let kek = EncryptionKey::generate().unwrap();
+        for aad in [None, Some(&b"context"[..])] {
+            let envelope = Envelope::seal(&kek, b"payload", aad).unwrap();
+
+            let json = envelope.to_json().unwrap();
+            let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(value["version"], 1);
+            assert_eq!(value["alg"], "C20P");
+            assert_eq!(value["kid"], kek.key_id());
+            let decoded = Envelope::from_json(&json).unwrap();
+            assert_eq!(decoded, envelope);
+            assert_eq!(decoded.open(&kek).unwrap(), b"payload");
+
+            let bytes = envelope.to_bytes().unwrap();
+            assert!(bytes.starts_with(ENVELOPE_MAGIC));
+            let decoded = Envelope::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded, envelope);
+            assert_eq!(decoded.open(&kek).unwrap(), b"payload");
+        }
+    }
+
+    #[test]
+    fn test_rewrap_keeps_payload() {
+        let old_kek = EncryptionKey::generate().unwrap();
+        let new_kek = EncryptionKey::generate().unwrap();
+        let envelope = Envelope::seal(&old_kek, b"payload", None).unwrap();
+
+        let rewrapped = envelope.rewrap(&old_kek, &new_kek).unwrap();
+        assert_eq!(rewrapped.key_id, new_kek.key_id());
+        assert_eq!(rewrapped.ciphertext, envelope.ciphertext);
+        assert_eq!(rewrapped.nonce, envelope.nonce);
+        assert_eq!(rewrapped.open(&new_kek).unwrap(), b"payload");
+        assert!(rewrapped.open(&old_kek).is_err());
+
+        // Stored envelopes under either key can be opened from a key list
+        let keks = [old_kek, new_kek];
+        assert_eq!(envelope.open_with(&keks).unwrap(), b"payload");
+        assert_eq!(rewrapped.open_with(&keks).unwrap(), b"payload");
+        assert!(matches!(
+            rewrapped.open_with(&keks[..1]),
+            Err(CryptoError::KeyNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_tampering() {
+        let kek = EncryptionKey::generate().unwrap();
+        let envelope = Envelope::seal(&kek, b"payload", Some(b"context")).unwrap();
+
+        let mut tampered = envelope.clone();
+        tampered.ciphertext[0] ^= 0x01;
+        assert!(tampered.open(&kek).is_err());
+
+        let mut tampered = envelope.clone();
+        tampered.wrapped_key[NONCE_LEN] ^= 0x01;
+        assert!(tampered.open(&kek).is_err());
+
+        let mut tampered = envelope.clone();
+        tampered.aad = Some(b"other".to_vec());
+        assert!(tampered.open(&kek).is_err());
+
+        // The data key of one envelope cannot be moved into another
+        let mut swapped = Envelope::seal(&kek, b"other", None).unwrap();
+        swapped.wrapped_key = envelope.wrapped_key.clone();
+        assert!(swapped.open(&kek).is_err());
+    }
+
+    #[test]
+    fn test_rejects_malformed_encodings() {
+        let kek = EncryptionKey::generate().unwrap();
+        let bytes = Envelope::seal(&kek, b"payload", None)
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+
+        assert!(Envelope::from_bytes(&bytes[..10]).is_err());
+        assert!(Envelope::from_bytes(b"NOPE").is_err());
+
+        let mut future = bytes.clone();
+        future[4] = ENVELOPE_VERSION + 1;
+        assert!(matches!(
+            Envelope::from_bytes(&future),
+            Err(CryptoError::DecryptionError(_))
+        ));
+
+        let mut unknown = bytes;
+        unknown[5] = 99;
+        assert!(matches!(
+            Envelope::from_bytes(&unknown),
+            Err(CryptoError::UnsupportedAlgorithm(_))
+        ));
+
+        assert!(Envelope::from_json(r#"{"version":1}"#).is_err());
+    }
+}
@@ -9,6 +9,15 @@ pub enum CryptoError {
     #[error("Invalid signature: {0}")]
     InvalidSignature(String),
 
+    #[error("Invalid signing context: {0}")]
+    InvalidSigningContext(String),
+
+    #[error("Batch verification failed at indexes {0:?}")]
+    BatchVerificationFailed(Vec<usize>),
+
+    #[error("Canonicalization failed: {0}")]
+    CanonicalizationError(String),
+
     #[error("Encryption failed: {0}")]
     EncryptionError(String),
 
@@ -18,6 +27,36 @@ pub enum CryptoError {
     #[error("Key generation failed: {0}")]
     KeyGenerationError(String),
 
+    #[error("Unsupported algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("Key rotation failed: {0}")]
+    KeyRotationError(String),
+
+    #[error("Key not found: {0}")]
+    KeyNotFound(String),
+
+    #[error("Wrong keystore password")]
+    WrongPassword,
+
+    #[error("Keystore error: {0}")]
+    KeystoreError(String),
+
+    #[error("Multi-signature error: {0}")]
+    MultiSignatureError(String),
+
+    #[error("Secret sharing error: {0}")]
+    SecretSharingError(String),
+
+    #[error("Corrupted share: {0}")]
+    CorruptedShare(String),
+
+    #[error("Corrupted shares with indexes {0:?}")]
+    CorruptedShares(Vec<u8>),
+
+    #[error("Signer error: {0}")]
+    SignerError(String),
+
     #[error("Internal error: {0}")]
     InternalError(String),
 }
@@ -0,0 +1,374 @@
+//! Hierarchical deterministic Ed25519 keys (SLIP-0010).
+//!
+//! Every agent key can be reproduced from one backed-up [`MasterSeed`] and a
+//! [`DerivationPath`]. Ed25519 only supports hardened derivation, so every
+//! path segment is hardened. With the `bip39` feature the seed can also be
+//! imported from a BIP-39 mnemonic phrase.
+
+use std::fmt;
+use std::str::FromStr;
+
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha512;
+use zeroize::Zeroizing;
+
+use crate::{CryptoError, KeyPair, PrivateKey, Result, SecretBytes};
+
+/// Offset added to an index to mark it as hardened
+pub const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+/// Purpose segment of agent key paths (BIP-44 layout)
+pub const AGENT_PATH_PURPOSE: u32 = 44;
+
+/// Coin type segment reserved for AgentID agent keys
+pub const AGENT_PATH_COIN_TYPE: u32 = 7683;
+
+/// HMAC key used to derive the master key (SLIP-0010, Ed25519 curve)
+const ED25519_SEED_KEY: &[u8] = b"ed25519 seed";
+
+/// Path of hardened child indexes from the master key
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    /// Create a path from child indexes, each below [`HARDENED_OFFSET`]
+    pub fn new(indexes: Vec<u32>) -> Result<Self> {
+        if let Some(index) = indexes.iter().find(|index| **index >= HARDENED_OFFSET) {
+            return Err(CryptoError::InvalidKeyFormat(format!(
+                "Derivation index out of range: {}",
+                index
+            )));
+        }
+        Ok(Self(indexes))
+    }
+
+    /// Get the path of an agent key: `m/44'/7683'/{account}'/{agent_index}'`
+    pub fn agent(account: u32, agent_index: u32) -> Result<Self> {
+        Self::new(vec![
+            AGENT_PATH_PURPOSE,
+            AGENT_PATH_COIN_TYPE,
+            account,
+            agent_index,
+        ])
+    }
+
+    /// Get the child indexes, without the hardened offset
+    pub fn indexes(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = CryptoError;
+
+    /// Parse a path such as `m/44'/7683'/0'/3'` (`H` may be used instead of `'`)
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || CryptoError::InvalidKeyFormat(format!("Invalid derivation path: {}", s));
+
+        let mut segments = s.split('/');
+        if segments.next() != Some("m") {
+            return Err(invalid());
+        }
+
+        let indexes = segments
+            .map(|segment| {
+                let index = segment
+                    .strip_suffix('\'')
+                    .or_else(|| segment.strip_suffix('H'))
+                    .ok_or_else(|| {
+                        CryptoError::InvalidKeyFormat(format!(
+                            "Ed25519 only supports hardened derivation: {}",
+                            s
+                        ))
+                    })?;
+                index.parse::<u32>().map_err(|_| invalid())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::new(indexes)
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+        for index in &self.0 {
+            write!(f, "/{}'", index)?;
+        }
+        Ok(())
+    }
+}
+
+/// An extended private key: a signing key and its chain code
+struct ExtendedKey {
+    key: Zeroizing<[u8; 32]>,
+    chain_code: Zeroizing<[u8; 32]>,
+}
+
+impl ExtendedKey {
+    /// Split an HMAC-SHA512 output into key and chain code
+    fn from_hmac(key: &[u8], data: &[&[u8]]) -> Self {
+        let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts any key length");
+        for chunk in data {
+            mac.update(chunk);
+        }
+        let output = Zeroizing::new(<[u8; 64]>::from(mac.finalize().into_bytes()));
+
+        let mut extended = Self {
+            key: Zeroizing::new([0u8; 32]),
+            chain_code: Zeroizing::new([0u8; 32]),
+        };
+        extended.key.copy_from_slice(&output[..32]);
+        extended.chain_code.copy_from_slice(&output[32..]);
+        extended
+    }
+
+    /// Derive the hardened child at the given index
+    fn derive_child(&self, index: u32) -> Self {
+        let index = (index | HARDENED_OFFSET).to_be_bytes();
+        Self::from_hmac(self.chain_code.as_ref(), &[&[0], self.key.as_ref(), &index])
+    }
+}
+
+/// The root secret from which agent keys are derived
+///
+/// Seeds are between 16 and 64 bytes, as in BIP-32.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MasterSeed(SecretBytes);
+
+impl MasterSeed {
+    /// Wrap existing seed bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if !(16..=64).contains(&bytes.len()) {
+            return Err(CryptoError::InvalidKeyFormat(
+                "Seed must be between 16 and 64 bytes".into(),
+            ));
+        }
+        Ok(Self(SecretBytes::from_slice(bytes)))
+    }
+
+    /// Generate a random 32-byte seed
+    pub fn generate() -> Self {
+        let mut seed = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(seed.as_mut());
+        Self(SecretBytes::from_slice(seed.as_ref()))
+    }
+
+    /// Import a seed from a BIP-39 English mnemonic phrase and optional passphrase
+    #[cfg(feature = "bip39")]
+    pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<Self> {
+        let mnemonic = bip39::Mnemonic::parse_in_normalized(bip39::Language::English, phrase)
+            .map_err(|e| CryptoError::InvalidKeyFormat(format!("Invalid mnemonic: {}", e)))?;
+        let seed = Zeroizing::new(mnemonic.to_seed_normalized(passphrase));
+        Self::from_bytes(seed.as_ref())
+    }
+
+    /// Get the seed bytes
+    pub fn expose_secret(&self) -> &[u8] {
+        self.0.expose_secret()
+    }
+
+    /// Derive the key pair at the given path
+    pub fn derive(&self, path: &DerivationPath) -> Result<KeyPair> {
+        let extended = self.derive_extended(path);
+        Ok(KeyPair::from_private_key(PrivateKey::from_bytes(
+            &extended.key,
+        )?))
+    }
+
+    /// Derive the key pair of an agent in the given account
+    pub fn derive_agent_key(&self, account: u32, agent_index: u32) -> Result<KeyPair> {
+        self.derive(&DerivationPath::agent(account, agent_index)?)
+    }
+
+    /// Derive the extended key at the given path
+    fn derive_extended(&self, path: &DerivationPath) -> ExtendedKey {
+        let master = ExtendedKey::from_hmac(ED25519_SEED_KEY, &[self.expose_secret()]);
+        path.indexes()
+            .iter()
+            .fold(master, |parent, index| parent.derive_child(*index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Vector {
+        path: &'static str,
+        chain_code: &'static str,
+        private_key: &'static str,
+        public_key: &'static str,
+    }
+
+    fn check_vectors(seed: &str, vectors: &[Vector]) {
+        let seed = MasterSeed::from_bytes(&hex::decode(seed).unwrap()).unwrap();
+        for vector in vectors {
+            let path: DerivationPath = vector.path.parse().unwrap();
+            let extended = seed.derive_extended(&path);
+            assert_eq!(
+                hex::encode(extended.chain_code.as_ref()),
+                vector.chain_code,
+                "chain code for {}",
+                vector.path
+            );
+            assert_eq!(
+                hex::encode(extended.key.as_ref()),
+                vector.private_key,
+                "private key for {}",
+                vector.path
+            );
+
+            // SLIP-0010 prefixes Ed25519 public keys with a zero byte
+            let key_pair = seed.derive(&path).unwrap();
+            assert_eq!(
+                format!("00{}", hex::encode(key_pair.public_key().to_bytes())),
+                vector.public_key,
+                "public key for {}",
+                vector.path
+            );
+        }
+    }
+
+    // SLIP-0010, test vector 1 for ed25519
+    #[test]
+    fn test_slip10_vector_1() {
+        check_vectors(
+            "000102030405060708090a0b0c0d0e0f",
+            &[
+                Vector {
+                    path: "m",
+                    chain_code: "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb",
+                    private_key: "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+                    public_key:
+                        "00a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed",
+                },
+                Vector {
+                    path: "m/0H",
+                    chain_code: "8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69",
+                    private_key: "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+                    public_key:
+                        "008c8a13df77a28f3445213a0f432fde644acaa215fc72dcdf300d5efaa85d350c",
+                },
+                Vector {
+                    path: "m/0H/1H",
+                    chain_code: "a320425f77d1b5c2505a6b1b27382b37368ee640e3557c315416801243552f14",
+                    private_key: "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+                    public_key:
+                        "001932a5270f335bed617d5b935c80aedb1a35bd9fc1e31acafd5372c30f5c1187",
+                },
+                Vector {
+                    path: "m/0H/1H/2H",
+                    chain_code: "2e69929e00b5ab250f49c3fb1c12f252de4fed2c1db88387094a0f8c4c9ccd6c",
+                    private_key: "92a5b23c0b8a99e37d07df3fb9966917f5d06e02ddbd909c7e184371463e9fc9",
+                    public_key:
+                        "00ae98736566d30ed0e9d2f4486a64bc95740d89c7db33f52121f8ea8f76ff0fc1",
+                },
+                Vector {
+                    path: "m/0H/1H/2H/2H",
+                    chain_code: "8f6d87f93d750e0efccda017d662a1b31a266e4a6f5993b15f5c1f07f74dd5cc",
+                    private_key: "30d1dc7e5fc04c31219ab25a27ae00b50f6fd66622f6e9c913253d6511d1e662",
+                    public_key:
+                        "008abae2d66361c879b900d204ad2cc4984fa2aa344dd7ddc46007329ac76c429c",
+                },
+                Vector {
+                    path: "m/0H/1H/2H/2H/1000000000H",
+                    chain_code: "68789923a0cac2cd5a29172a475fe9e0fb14cd6adb5ad98a3fa70333e7afa230",
+                    private_key: "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793",
+                    public_key:
+                        "003c24da049451555d51a7014a37337aa4e12d41e485abccfa46b47dfb2af54b7a",
+                },
+            ],
+        );
+    }
+
+    // SLIP-0010, test vector 2 for ed25519
+    #[test]
+    fn test_slip10_vector_2() {
+        check_vectors(
+            "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542",
+            &[
+                Vector {
+                    path: "m",
+                    chain_code: "ef70a74db9c3a5af931b5fe73ed8e1a53464133654fd55e7a66f8570b8e33c3b",
+                    private_key: "171cb88b1b3c1db25add599712e36245d75bc65a1a5c9e18d76f9f2b1eab4012",
+                    public_key: "008fe9693f8fa62a4305a140b9764c5ee01e455963744fe18204b4fb948249308a",
+                },
+                Vector {
+                    path: "m/0H",
+                    chain_code: "0b78a3226f915c082bf118f83618a618ab6dec793752624cbeb622acb562862d",
+                    private_key: "1559eb2bbec5790b0c65d8693e4d0875b1747f4970ae8b650486ed7470845635",
+                    public_key: "0086fab68dcb57aa196c77c5f264f215a112c22a912c10d123b0d03c3c28ef1037",
+                },
+                Vector {
+                    path: "m/0H/2147483647H",
+                    chain_code: "138f0b2551bcafeca6ff2aa88ba8ed0ed8de070841f0c4ef0165df8181eaad7f",
+                    private_key: "ea4f5bfe8694d8bb74b7b59404632fd5968b774ed545e810de9c32a4fb4192f4",
+                    public_key: "005ba3b9ac6e90e83effcd25ac4e58a1365a9e35a3d3ae5eb07b9e4d90bcf7506d",
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn test_agent_keys_are_reproducible() {
+        let seed = MasterSeed::generate();
+        let restored = MasterSeed::from_bytes(seed.expose_secret()).unwrap();
+
+        let first = seed.derive_agent_key(0, 7).unwrap();
+        assert_eq!(
+            restored.derive_agent_key(0, 7).unwrap().public_key(),
+            first.public_key()
+        );
+        assert_ne!(
+            seed.derive_agent_key(0, 8).unwrap().public_key(),
+            first.public_key()
+        );
+        assert_ne!(
+            seed.derive_agent_key(1, 7).unwrap().public_key(),
+            first.public_key()
+        );
+        assert_eq!(
+            DerivationPath::agent(0, 7).unwrap().to_string(),
+            "m/44'/7683'/0'/7'"
+        );
+    }
+
+    #[test]
+    fn test_derivation_path_parsing() {
+        let path: DerivationPath = "m/44'/7683'/0H/3'".parse().unwrap();
+        assert_eq!(path.indexes(), &[44, 7683, 0, 3]);
+        assert_eq!(path.to_string(), "m/44'/7683'/0'/3'");
+        assert!("m".parse::<DerivationPath>().unwrap().indexes().is_empty());
+
+        for invalid in ["", "44'/0'", "m/0", "m/x'", "m/2147483648'", "m//0'"] {
+            assert!(
+                invalid.parse::<DerivationPath>().is_err(),
+                "accepted {}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_seed_length_is_checked() {
+        assert!(MasterSeed::from_bytes(&[0u8; 15]).is_err());
+        assert!(MasterSeed::from_bytes(&[0u8; 65]).is_err());
+        assert!(MasterSeed::from_bytes(&[0u8; 16]).is_ok());
+    }
+
+    // BIP-39 English test vector with passphrase "TREZOR"
+    #[cfg(feature = "bip39")]
+    #[test]
+    fn test_mnemonic_import() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let seed = MasterSeed::from_mnemonic(phrase, "TREZOR").unwrap();
+        assert_eq!(
+            hex::encode(seed.expose_secret()),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+
+        assert!(MasterSeed::from_mnemonic("abandon abandon", "").is_err());
+    }
+}
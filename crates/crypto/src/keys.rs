@@ -1,52 +1,149 @@
 use async_trait::async_trait;
-use ed25519_dalek::{Signature as Ed25519Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use chrono::{DateTime, Duration, Utc};
 use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::{CryptoError, KeyPair, PrivateKey, PublicKey, Result};
+use crate::{
+    BatchItem, CryptoError, KeyPair, KeyStrengthReport, PublicKey, Result, Signature, Signer,
+    SigningContext,
+};
+
+/// Default period during which a retired key still verifies signatures
+pub const DEFAULT_OVERLAP_PERIOD_HOURS: i64 = 24;
+
+/// A key pair tracked by the [`KeyManager`] together with its version metadata
+#[derive(Debug, Clone)]
+struct ManagedKey {
+    version: u32,
+    key_id: String,
+    key_pair: KeyPair,
+    activated_at: DateTime<Utc>,
+    retired_at: Option<DateTime<Utc>>,
+}
+
+impl ManagedKey {
+    fn new(version: u32, key_pair: KeyPair) -> Self {
+        Self {
+            version,
+            key_id: key_pair.public_key().key_id(),
+            key_pair,
+            activated_at: Utc::now(),
+            retired_at: None,
+        }
+    }
+
+    fn to_versioned(&self) -> VersionedPublicKey {
+        VersionedPublicKey {
+            version: self.version,
+            key_id: self.key_id.clone(),
+            public_key: self.key_pair.public_key().clone(),
+            activated_at: self.activated_at,
+            retired_at: self.retired_at,
+        }
+    }
+}
+
+/// A public key together with its version in the key history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionedPublicKey {
+    /// The key version, starting at 1 and incremented on each rotation
+    pub version: u32,
+    /// The stable identifier of the key
+    pub key_id: String,
+    /// The public key
+    pub public_key: PublicKey,
+    /// When this key became the current key
+    pub activated_at: DateTime<Utc>,
+    /// When this key was rotated out (if it has been)
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+/// The key version a signature was verified against
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyMatch {
+    /// The version of the matching key
+    pub version: u32,
+    /// The identifier of the matching key
+    pub key_id: String,
+    /// Whether the matching key has been retired
+    pub retired: bool,
+}
+
+/// The current key and the history of retired keys
+#[derive(Debug)]
+struct KeyRing {
+    current: ManagedKey,
+    history: Vec<ManagedKey>,
+}
 
 /// Manages cryptographic keys and operations
 #[derive(Clone)]
 pub struct KeyManager {
-    current_key: Arc<RwLock<KeyPair>>,
-    min_key_strength: u32, // Minimum bits of security
+    keys: Arc<RwLock<KeyRing>>,
+    /// Minimum bits of security required of keys
+    min_key_strength: u32,
+    overlap_period: Duration,
 }
 
 impl KeyManager {
     /// Create a new KeyManager with the given initial key pair
     pub fn new(initial_key: KeyPair, min_key_strength: u32) -> Self {
         Self {
-            current_key: Arc::new(RwLock::new(initial_key)),
+            keys: Arc::new(RwLock::new(KeyRing {
+                current: ManagedKey::new(1, initial_key),
+                history: Vec::new(),
+            })),
             min_key_strength,
+            overlap_period: Duration::hours(DEFAULT_OVERLAP_PERIOD_HOURS),
         }
     }
 
+    /// Set how long retired keys keep verifying signatures after rotation
+    pub fn with_overlap_period(mut self, overlap_period: Duration) -> Self {
+        self.overlap_period = overlap_period;
+        self
+    }
+
+    /// Get the overlap period for retired keys
+    pub fn overlap_period(&self) -> Duration {
+        self.overlap_period
+    }
+
     /// Generate a new key pair
     pub async fn generate_key_pair(&self) -> Result<KeyPair> {
-        let mut rng = OsRng;
-        let mut secret_key_bytes = [0u8; 32];
-        rng.fill_bytes(&mut secret_key_bytes);
+        KeyPair::generate()
+    }
 
-        let signing_key = SigningKey::from_bytes(&secret_key_bytes);
-        let verifying_key = VerifyingKey::from(&signing_key);
+    /// Get the minimum bits of security required of keys
+    pub fn min_key_strength(&self) -> u32 {
+        self.min_key_strength
+    }
 
-        Ok(KeyPair::new(
-            PublicKey::from_bytes(&verifying_key.to_bytes())?,
-            PrivateKey::from_bytes(&secret_key_bytes)?,
-        ))
+    /// Validate a key against the minimum strength and validity requirements
+    ///
+    /// The report lists every problem found; the key is only fit for use if
+    /// [`KeyStrengthReport::is_acceptable`] returns `true`.
+    pub async fn validate_key_strength(&self, key: &KeyPair) -> Result<KeyStrengthReport> {
+        Ok(KeyStrengthReport::for_key_pair(key, self.min_key_strength))
     }
 
-    /// Validate if a key meets the minimum strength requirements
-    pub async fn validate_key_strength(&self, key: &KeyPair) -> Result<bool> {
-        // For Ed25519, we consider it secure if it's a valid key
-        // In a real implementation, you might want to add more checks
-        Ok(key.public_key().verifying_key.is_some())
+    /// Get the current public key and its version
+    pub async fn current_public_key(&self) -> Result<VersionedPublicKey> {
+        Ok(self.keys.read().await.current.to_versioned())
     }
 
-    /// Get the current public key
-    pub async fn current_public_key(&self) -> Result<PublicKey> {
-        Ok(self.current_key.read().await.public_key().clone())
+    /// Get the retired keys, oldest first
+    pub async fn key_history(&self) -> Result<Vec<VersionedPublicKey>> {
+        Ok(self
+            .keys
+            .read()
+            .await
+            .history
+            .iter()
+            .map(ManagedKey::to_versioned)
+            .collect())
     }
 
     /// Generate a random challenge for key ownership proof
@@ -56,42 +153,324 @@ impl KeyManager {
         Ok(challenge)
     }
 
-    /// Sign data with the given key
-    pub async fn sign(&self, data: &[u8], key: &KeyPair) -> Result<Vec<u8>> {
-        let signature = key.private_key().signing_key.sign(data);
-        Ok(signature.to_bytes().to_vec())
+    /// Sign data in the given context with the current key
+    pub async fn sign_with_current_key(
+        &self,
+        context: &SigningContext,
+        data: &[u8],
+    ) -> Result<Vec<u8>> {
+        let keys = self.keys.read().await;
+        self.sign(context, data, &keys.current.key_pair).await
+    }
+
+    /// Sign data in the given context with the given key, using the key's algorithm
+    pub async fn sign(
+        &self,
+        context: &SigningContext,
+        data: &[u8],
+        key: &KeyPair,
+    ) -> Result<Vec<u8>> {
+        Ok(context.sign(data, key.private_key())?.signature_bytes)
+    }
+
+    /// Sign data in the given context with an external signer, such as a
+    /// remote signing daemon
+    pub async fn sign_with_signer(
+        &self,
+        context: &SigningContext,
+        data: &[u8],
+        signer: &dyn Signer,
+    ) -> Result<Vec<u8>> {
+        Ok(context.sign_with(data, signer).await?.signature_bytes)
     }
 
-    /// Verify a signature
+    /// Verify a signature made in the given context, using the public key's algorithm
     pub async fn verify(
         &self,
+        context: &SigningContext,
         data: &[u8],
         signature: &[u8],
         public_key: &PublicKey,
     ) -> Result<bool> {
-        let verifying_key = public_key
+        public_key
             .verifying_key
-            .as_ref()
-            .ok_or_else(|| CryptoError::InvalidKeyFormat("Public key not initialized".into()))?;
+            .verify(&context.signing_input(data), signature)
+    }
+
+    /// Verify many signatures at once
+    ///
+    /// Fails with [`CryptoError::BatchVerificationFailed`] listing the
+    /// indexes of every invalid item.
+    pub async fn verify_batch(&self, items: &[BatchItem<'_>]) -> Result<()> {
+        Signature::verify_batch(items)
+    }
+
+    /// Verify a signature against the current key and any retired key that
+    /// is still inside the overlap period
+    ///
+    /// Returns the version of the matching key, or `None` if no key matched.
+    pub async fn verify_with_key_history(
+        &self,
+        context: &SigningContext,
+        data: &[u8],
+        signature: &[u8],
+    ) -> Result<Option<KeyMatch>> {
+        let keys = self.keys.read().await;
+        let now = Utc::now();
+
+        let candidates =
+            std::iter::once(&keys.current).chain(keys.history.iter().rev().filter(|key| {
+                key.retired_at
+                    .is_some_and(|at| now < at + self.overlap_period)
+            }));
+
+        for key in candidates {
+            if self
+                .verify(context, data, signature, key.key_pair.public_key())
+                .await?
+            {
+                return Ok(Some(KeyMatch {
+                    version: key.version,
+                    key_id: key.key_id.clone(),
+                    retired: key.retired_at.is_some(),
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Rotate to a freshly generated key pair
+    pub async fn rotate(&self) -> Result<VersionedPublicKey> {
+        let new_key = self.generate_key_pair().await?;
+        self.rotate_key(new_key).await
+    }
+
+    /// Rotate to the given key pair
+    ///
+    /// The current key is moved into the key history and keeps verifying
+    /// signatures for the configured overlap period. The new key must pass
+    /// [`KeyManager::validate_key_strength`].
+    pub async fn rotate_key(&self, new_key: KeyPair) -> Result<VersionedPublicKey> {
+        let report = self.validate_key_strength(&new_key).await?;
+        if !report.is_acceptable() {
+            return Err(CryptoError::KeyRotationError(report.to_string()));
+        }
+
+        let mut keys = self.keys.write().await;
+        let new_key_id = new_key.public_key().key_id();
+        if keys.current.key_id == new_key_id
+            || keys.history.iter().any(|key| key.key_id == new_key_id)
+        {
+            return Err(CryptoError::KeyRotationError(
+                "New key has already been used".into(),
+            ));
+        }
+
+        let version = keys.current.version + 1;
+        let mut retired = std::mem::replace(&mut keys.current, ManagedKey::new(version, new_key));
+        retired.retired_at = Some(Utc::now());
+        keys.history.push(retired);
+
+        Ok(keys.current.to_versioned())
+    }
+}
+
+/// Signs with the current key, so that rotation is transparent to callers
+///
+/// Like every [`Signer`], this signs raw bytes so that the manager can back
+/// [`SigningContext::sign_with`] and Data Integrity proofs. To sign in a
+/// context directly, use [`KeyManager::sign_with_current_key`].
+#[async_trait]
+impl Signer for KeyManager {
+    async fn sign(&self, message: &[u8]) -> Result<Signature> {
+        self.keys
+            .read()
+            .await
+            .current
+            .key_pair
+            .private_key()
+            .sign(message)
+    }
+
+    async fn public_key(&self) -> Result<PublicKey> {
+        Ok(self.keys.read().await.current.key_pair.public_key().clone())
+    }
+
+    async fn key_id(&self) -> Result<String> {
+        Ok(self.keys.read().await.current.key_id.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rotation_increments_version() {
+        let manager = KeyManager::new(KeyPair::generate().unwrap(), 128);
+        let initial = manager.current_public_key().await.unwrap();
+        assert_eq!(initial.version, 1);
+
+        let rotated = manager.rotate().await.unwrap();
+        assert_eq!(rotated.version, 2);
+        assert_ne!(rotated.key_id, initial.key_id);
+        assert_eq!(manager.current_public_key().await.unwrap(), rotated);
+
+        let history = manager.key_history().await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].key_id, initial.key_id);
+        assert!(history[0].retired_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_signing_after_rotation_uses_new_key() {
+        let manager = KeyManager::new(KeyPair::generate().unwrap(), 128);
+        manager.rotate().await.unwrap();
+
+        let current = manager.current_public_key().await.unwrap();
+        let signature = manager
+            .sign_with_current_key(&SigningContext::CHALLENGE, b"message")
+            .await
+            .unwrap();
+        assert!(manager
+            .verify(
+                &SigningContext::CHALLENGE,
+                b"message",
+                &signature,
+                &current.public_key
+            )
+            .await
+            .unwrap());
+
+        let matched = manager
+            .verify_with_key_history(&SigningContext::CHALLENGE, b"message", &signature)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(matched.version, 2);
+        assert!(!matched.retired);
+    }
+
+    #[tokio::test]
+    async fn test_retired_key_verifies_within_overlap() {
+        let manager = KeyManager::new(KeyPair::generate().unwrap(), 128);
+        let old_signature = manager
+            .sign_with_current_key(&SigningContext::CHALLENGE, b"in flight")
+            .await
+            .unwrap();
+        manager.rotate().await.unwrap();
+
+        let matched = manager
+            .verify_with_key_history(&SigningContext::CHALLENGE, b"in flight", &old_signature)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(matched.version, 1);
+        assert!(matched.retired);
+    }
+
+    #[tokio::test]
+    async fn test_retired_key_rejected_after_overlap() {
+        let manager = KeyManager::new(KeyPair::generate().unwrap(), 128)
+            .with_overlap_period(Duration::zero());
+        let old_signature = manager
+            .sign_with_current_key(&SigningContext::CHALLENGE, b"in flight")
+            .await
+            .unwrap();
+        manager.rotate().await.unwrap();
+
+        assert!(manager
+            .verify_with_key_history(&SigningContext::CHALLENGE, b"in flight", &old_signature)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_rejects_mismatched_key_pair() {
+        let manager = KeyManager::new(KeyPair::generate().unwrap(), 128);
+        let first = KeyPair::generate().unwrap();
+        let second = KeyPair::generate().unwrap();
+        let mismatched = KeyPair::new(first.public_key().clone(), second.private_key().clone());
+
+        assert!(matches!(
+            manager.rotate_key(mismatched).await,
+            Err(CryptoError::KeyRotationError(_))
+        ));
+        assert_eq!(manager.current_public_key().await.unwrap().version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_rotate_rejects_weak_key() {
+        let manager = KeyManager::new(KeyPair::generate().unwrap(), 128);
+        let weak = KeyPair::from_private_key(crate::PrivateKey::from_bytes(&[0u8; 32]).unwrap());
+
+        let report = manager.validate_key_strength(&weak).await.unwrap();
+        assert_eq!(report.issues, vec![crate::KeyIssue::ZeroSecret]);
+        assert!(matches!(
+            manager.rotate_key(weak).await,
+            Err(CryptoError::KeyRotationError(_))
+        ));
+
+        // A stricter manager rejects keys its algorithms cannot back
+        let strict = KeyManager::new(KeyPair::generate().unwrap(), 256);
+        assert!(!strict
+            .validate_key_strength(&KeyPair::generate().unwrap())
+            .await
+            .unwrap()
+            .is_acceptable());
+        assert!(strict.rotate().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_rejects_reused_key() {
+        let initial = KeyPair::generate().unwrap();
+        let manager = KeyManager::new(initial.clone(), 128);
+        manager.rotate().await.unwrap();
+
+        assert!(matches!(
+            manager.rotate_key(initial).await,
+            Err(CryptoError::KeyRotationError(_))
+        ));
+    }
 
-        let sig_bytes: [u8; 64] = signature
-            .try_into()
-            .map_err(|_| CryptoError::InvalidSignature("Invalid signature length".into()))?;
-        let sig = Ed25519Signature::from_bytes(&sig_bytes);
+    #[tokio::test]
+    async fn test_key_manager_as_signer() {
+        let manager = KeyManager::new(KeyPair::generate().unwrap(), 128);
+        manager.rotate().await.unwrap();
 
-        Ok(verifying_key.verify(data, &sig).is_ok())
+        let current = manager.current_public_key().await.unwrap();
+        assert_eq!(Signer::key_id(&manager).await.unwrap(), current.key_id);
+        let signature = SigningContext::CHALLENGE
+            .sign_with(b"message", &manager)
+            .await
+            .unwrap();
+        assert!(SigningContext::CHALLENGE
+            .verify(b"message", &signature, &current.public_key)
+            .unwrap());
+        assert!(!signature
+            .verify(b"message", &current.public_key)
+            .unwrap_or(false));
     }
 
-    /// Rotate to a new key
-    pub async fn rotate_key(&self, new_public_key: PublicKey) -> Result<()> {
-        // In a real implementation, you might want to:
-        // 1. Verify the new key is different from current
-        // 2. Validate the new key
-        // 3. Store the old key for a grace period
-        // 4. Update any key metadata
+    #[tokio::test]
+    async fn test_sign_with_external_signer() {
+        let manager = KeyManager::new(KeyPair::generate().unwrap(), 128);
+        let signer = crate::InMemorySigner::new(KeyPair::generate().unwrap());
 
-        let mut current = self.current_key.write().await;
-        *current = KeyPair::new(new_public_key, current.private_key().clone());
-        Ok(())
+        let signature = manager
+            .sign_with_signer(&SigningContext::CHALLENGE, b"message", &signer)
+            .await
+            .unwrap();
+        assert!(manager
+            .verify(
+                &SigningContext::CHALLENGE,
+                b"message",
+                &signature,
+                &signer.public_key().await.unwrap()
+            )
+            .await
+            .unwrap());
     }
 }
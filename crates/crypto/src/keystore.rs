@@ -0,0 +1,475 @@
+//! Password-protected on-disk keystore for agent key pairs.
+//!
+//! A keystore is a single versioned JSON file. The password is stretched with
+//! Argon2id into a ChaCha20-Poly1305 [`EncryptionKey`], which seals every
+//! stored private key. Each private key is sealed with its key ID as
+//! additional authenticated data, so entries cannot be swapped between IDs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chrono::{DateTime, Utc};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{CryptoError, EncryptedData, EncryptionKey, KeyPair, PrivateKey, PublicKey, Result};
+
+/// Current version of the keystore file format
+pub const KEYSTORE_VERSION: u32 = 1;
+
+/// Plaintext sealed in every keystore to detect a wrong password
+const PASSWORD_CHECK: &[u8] = b"agentid-keystore";
+
+/// Parameters for the Argon2id password KDF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of iterations
+    pub iterations: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+/// KDF configuration stored in the keystore file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KdfConfig {
+    algorithm: String,
+    params: KdfParams,
+    salt: Vec<u8>,
+}
+
+/// Public information about a key stored in a keystore
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeystoreEntry {
+    /// The key identifier
+    pub key_id: String,
+    /// An optional human-readable label
+    pub label: Option<String>,
+    /// The public key
+    pub public_key: PublicKey,
+    /// When the key was added to the keystore
+    pub created_at: DateTime<Utc>,
+}
+
+/// A stored key with its sealed private key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredKey {
+    #[serde(flatten)]
+    entry: KeystoreEntry,
+    encrypted_private_key: EncryptedData,
+}
+
+/// The on-disk keystore file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    kdf: KdfConfig,
+    password_check: EncryptedData,
+    keys: Vec<StoredKey>,
+}
+
+/// A password-protected keystore backed by a file
+#[derive(Debug)]
+pub struct Keystore {
+    path: PathBuf,
+    file: KeystoreFile,
+}
+
+impl KdfConfig {
+    fn generate(params: KdfParams) -> Self {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            algorithm: "argon2id".into(),
+            params,
+            salt,
+        }
+    }
+
+    fn derive_key(&self, password: &str) -> Result<EncryptionKey> {
+        if self.algorithm != "argon2id" {
+            return Err(CryptoError::KeystoreError(format!(
+                "Unsupported KDF: {}",
+                self.algorithm
+            )));
+        }
+
+        let params = Params::new(
+            self.params.memory_kib,
+            self.params.iterations,
+            self.params.parallelism,
+            Some(32),
+        )
+        .map_err(|e| CryptoError::KeystoreError(e.to_string()))?;
+
+        let mut key_bytes = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), &self.salt, key_bytes.as_mut())
+            .map_err(|e| CryptoError::KeystoreError(e.to_string()))?;
+
+        EncryptionKey::from_bytes(key_bytes.as_ref())
+    }
+}
+
+impl Keystore {
+    /// Create a new, empty keystore at the given path with default KDF parameters
+    pub fn create(path: impl AsRef<Path>, password: &str) -> Result<Self> {
+        Self::create_with_params(path, password, KdfParams::default())
+    }
+
+    /// Create a new, empty keystore at the given path
+    pub fn create_with_params(
+        path: impl AsRef<Path>,
+        password: &str,
+        params: KdfParams,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            return Err(CryptoError::KeystoreError(format!(
+                "Keystore already exists: {}",
+                path.display()
+            )));
+        }
+
+        let kdf = KdfConfig::generate(params);
+        let key = kdf.derive_key(password)?;
+        let keystore = Self {
+            path,
+            file: KeystoreFile {
+                version: KEYSTORE_VERSION,
+                kdf,
+                password_check: key.encrypt(PASSWORD_CHECK, Some(PASSWORD_CHECK))?,
+                keys: Vec::new(),
+            },
+        };
+        keystore.save()?;
+        Ok(keystore)
+    }
+
+    /// Open an existing keystore
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let contents = fs::read(&path).map_err(|e| {
+            CryptoError::KeystoreError(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        let file: KeystoreFile = serde_json::from_slice(&contents)
+            .map_err(|e| CryptoError::KeystoreError(format!("Malformed keystore: {}", e)))?;
+
+        if file.version != KEYSTORE_VERSION {
+            return Err(CryptoError::KeystoreError(format!(
+                "Unsupported keystore version: {}",
+                file.version
+            )));
+        }
+
+        Ok(Self { path, file })
+    }
+
+    /// Get the path of the keystore file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// List the keys in the keystore
+    pub fn list_keys(&self) -> Vec<KeystoreEntry> {
+        self.file.keys.iter().map(|key| key.entry.clone()).collect()
+    }
+
+    /// Add a key pair to the keystore and return its key ID
+    pub fn add_key(
+        &mut self,
+        key_pair: &KeyPair,
+        label: Option<String>,
+        password: &str,
+    ) -> Result<String> {
+        let key = self.unlock(password)?;
+        let key_id = key_pair.public_key().key_id();
+        if self.find(&key_id).is_some() {
+            return Err(CryptoError::KeystoreError(format!(
+                "Key already exists: {}",
+                key_id
+            )));
+        }
+
+        let encrypted_private_key =
+            key.encrypt(key_pair.private_key().to_bytes(), Some(key_id.as_bytes()))?;
+        self.file.keys.push(StoredKey {
+            entry: KeystoreEntry {
+                key_id: key_id.clone(),
+                label,
+                public_key: key_pair.public_key().clone(),
+                created_at: Utc::now(),
+            },
+            encrypted_private_key,
+        });
+        self.save()?;
+        Ok(key_id)
+    }
+
+    /// Load a key pair by its key ID
+    pub fn load_key(&self, key_id: &str, password: &str) -> Result<KeyPair> {
+        let key = self.unlock(password)?;
+        let stored = self
+            .find(key_id)
+            .ok_or_else(|| CryptoError::KeyNotFound(key_id.to_string()))?;
+        Self::open_key(&key, stored)
+    }
+
+    /// Remove a key from the keystore
+    pub fn remove_key(&mut self, key_id: &str, password: &str) -> Result<()> {
+        self.unlock(password)?;
+        let index = self
+            .file
+            .keys
+            .iter()
+            .position(|key| key.entry.key_id == key_id)
+            .ok_or_else(|| CryptoError::KeyNotFound(key_id.to_string()))?;
+        self.file.keys.remove(index);
+        self.save()
+    }
+
+    /// Re-encrypt every key in the keystore under a new password
+    pub fn change_password(&mut self, old_password: &str, new_password: &str) -> Result<()> {
+        let old_key = self.unlock(old_password)?;
+
+        let kdf = KdfConfig::generate(self.file.kdf.params);
+        let new_key = kdf.derive_key(new_password)?;
+
+        let mut keys = Vec::with_capacity(self.file.keys.len());
+        for stored in &self.file.keys {
+            let key_pair = Self::open_key(&old_key, stored)?;
+            keys.push(StoredKey {
+                entry: stored.entry.clone(),
+                encrypted_private_key: new_key.encrypt(
+                    key_pair.private_key().to_bytes(),
+                    Some(stored.entry.key_id.as_bytes()),
+                )?,
+            });
+        }
+
+        self.file = KeystoreFile {
+            version: KEYSTORE_VERSION,
+            kdf,
+            password_check: new_key.encrypt(PASSWORD_CHECK, Some(PASSWORD_CHECK))?,
+            keys,
+        };
+        self.save()
+    }
+
+    /// Derive the keystore key and check it against the password check value
+    fn unlock(&self, password: &str) -> Result<EncryptionKey> {
+        let key = self.file.kdf.derive_key(password)?;
+        if self.file.password_check.aad.as_deref() != Some(PASSWORD_CHECK) {
+            return Err(CryptoError::KeystoreError(
+                "Malformed password check".into(),
+            ));
+        }
+        match key.decrypt(&self.file.password_check) {
+            Ok(check) if check == PASSWORD_CHECK => Ok(key),
+            _ => Err(CryptoError::WrongPassword),
+        }
+    }
+
+    fn find(&self, key_id: &str) -> Option<&StoredKey> {
+        self.file.keys.iter().find(|key| key.entry.key_id == key_id)
+    }
+
+    fn open_key(key: &EncryptionKey, stored: &StoredKey) -> Result<KeyPair> {
+        if stored.encrypted_private_key.aad.as_deref() != Some(stored.entry.key_id.as_bytes()) {
+            return Err(CryptoError::KeystoreError(format!(
+                "Key entry does not match its ID: {}",
+                stored.entry.key_id
+            )));
+        }
+
+        let decrypted = Zeroizing::new(key.decrypt(&stored.encrypted_private_key)?);
+        let private_key =
+            PrivateKey::from_algorithm_bytes(stored.entry.public_key.algorithm(), &decrypted)?;
+
+        let key_pair = KeyPair::new(stored.entry.public_key.clone(), private_key);
+        if !key_pair.is_valid() {
+            return Err(CryptoError::KeystoreError(format!(
+                "Stored key pair is inconsistent: {}",
+                stored.entry.key_id
+            )));
+        }
+        Ok(key_pair)
+    }
+
+    /// Write the keystore atomically by replacing the file
+    fn save(&self) -> Result<()> {
+        let contents = serde_json::to_vec_pretty(&self.file)
+            .map_err(|e| CryptoError::KeystoreError(e.to_string()))?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, contents)
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|e| {
+                CryptoError::KeystoreError(format!(
+                    "Failed to write {}: {}",
+                    self.path.display(),
+                    e
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap KDF parameters so the tests run quickly
+    const TEST_PARAMS: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn create_keystore(dir: &tempfile::TempDir, password: &str) -> Keystore {
+        Keystore::create_with_params(dir.path().join("keys.json"), password, TEST_PARAMS).unwrap()
+    }
+
+    #[test]
+    fn test_add_and_load_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut keystore = create_keystore(&dir, "password");
+        let key_pair = KeyPair::generate().unwrap();
+
+        let key_id = keystore
+            .add_key(&key_pair, Some("agent-1".into()), "password")
+            .unwrap();
+        assert_eq!(key_id, key_pair.public_key().key_id());
+
+        let reopened = Keystore::open(keystore.path()).unwrap();
+        let entries = reopened.list_keys();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key_id, key_id);
+        assert_eq!(entries[0].label.as_deref(), Some("agent-1"));
+
+        let loaded = reopened.load_key(&key_id, "password").unwrap();
+        assert_eq!(loaded.public_key(), key_pair.public_key());
+        assert_eq!(
+            loaded.private_key().to_bytes(),
+            key_pair.private_key().to_bytes()
+        );
+    }
+
+    #[test]
+    fn test_private_key_not_stored_in_plaintext() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut keystore = create_keystore(&dir, "password");
+        let key_pair = KeyPair::generate().unwrap();
+        keystore.add_key(&key_pair, None, "password").unwrap();
+
+        let contents = fs::read_to_string(keystore.path()).unwrap();
+        let private_bytes = serde_json::to_string(key_pair.private_key().to_bytes()).unwrap();
+        assert!(!contents.contains(&private_bytes));
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut keystore = create_keystore(&dir, "password");
+        let key_id = keystore
+            .add_key(&KeyPair::generate().unwrap(), None, "password")
+            .unwrap();
+
+        assert!(matches!(
+            keystore.load_key(&key_id, "wrong"),
+            Err(CryptoError::WrongPassword)
+        ));
+        assert!(matches!(
+            keystore.add_key(&KeyPair::generate().unwrap(), None, "wrong"),
+            Err(CryptoError::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn test_unknown_key_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = create_keystore(&dir, "password");
+
+        assert!(matches!(
+            keystore.load_key("missing", "password"),
+            Err(CryptoError::KeyNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_change_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut keystore = create_keystore(&dir, "old");
+        let key_pair = KeyPair::generate().unwrap();
+        let key_id = keystore.add_key(&key_pair, None, "old").unwrap();
+
+        assert!(matches!(
+            keystore.change_password("wrong", "new"),
+            Err(CryptoError::WrongPassword)
+        ));
+        keystore.change_password("old", "new").unwrap();
+
+        let reopened = Keystore::open(keystore.path()).unwrap();
+        assert!(matches!(
+            reopened.load_key(&key_id, "old"),
+            Err(CryptoError::WrongPassword)
+        ));
+        let loaded = reopened.load_key(&key_id, "new").unwrap();
+        assert_eq!(loaded.public_key(), key_pair.public_key());
+    }
+
+    #[test]
+    fn test_remove_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut keystore = create_keystore(&dir, "password");
+        let key_id = keystore
+            .add_key(&KeyPair::generate().unwrap(), None, "password")
+            .unwrap();
+
+        keystore.remove_key(&key_id, "password").unwrap();
+        assert!(keystore.list_keys().is_empty());
+        assert!(Keystore::open(keystore.path())
+            .unwrap()
+            .list_keys()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_rejects_duplicate_key_and_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut keystore = create_keystore(&dir, "password");
+        let key_pair = KeyPair::generate().unwrap();
+        keystore.add_key(&key_pair, None, "password").unwrap();
+
+        assert!(keystore.add_key(&key_pair, None, "password").is_err());
+        assert!(Keystore::create_with_params(keystore.path(), "password", TEST_PARAMS).is_err());
+    }
+
+    #[test]
+    fn test_rejects_unsupported_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = create_keystore(&dir, "password");
+
+        let mut file: serde_json::Value =
+            serde_json::from_slice(&fs::read(keystore.path()).unwrap()).unwrap();
+        file["version"] = serde_json::json!(KEYSTORE_VERSION + 1);
+        fs::write(keystore.path(), serde_json::to_vec(&file).unwrap()).unwrap();
+
+        assert!(matches!(
+            Keystore::open(keystore.path()),
+            Err(CryptoError::KeystoreError(_))
+        ));
+    }
+}
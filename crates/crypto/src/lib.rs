@@ -8,115 +8,171 @@
 //! - Secure random number generation
 
 use async_trait::async_trait;
-use ed25519_dalek::{Signature as Ed25519Signature, Signer, SigningKey, Verifier, VerifyingKey};
-use rand::{rngs::OsRng, RngCore};
-use serde::{de, Deserialize, Deserializer, Serialize};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
+use algorithm::{SigningKeyKind, VerifyingKeyKind};
+
+mod algorithm;
+mod base64url;
+mod canonical;
+mod context;
 mod encryption;
+mod envelope;
 mod error;
+mod formats;
+mod hd;
 mod keys;
+mod keystore;
+mod multisig;
+mod provider;
+mod sealed;
+mod secret;
+mod shamir;
 mod signatures;
-
+mod signer;
+mod stream;
+mod strength;
+
+pub use algorithm::KeyAlgorithm;
+pub use canonical::{
+    canonicalize, sign_canonical, verify_canonical, DetachedSignature, MAX_SAFE_INTEGER,
+};
+pub use context::{SigningContext, MAX_CONTEXT_LENGTH};
 pub use encryption::{EncryptedData, EncryptionKey};
+pub use envelope::{Envelope, EnvelopeAlgorithm, ENVELOPE_VERSION};
 pub use error::CryptoError;
-pub use keys::KeyManager;
-pub use signatures::Signature;
+pub use formats::{Jwk, JWK_CURVE_ED25519, JWK_KEY_TYPE_EC, JWK_KEY_TYPE_OKP};
+pub use hd::{
+    DerivationPath, MasterSeed, AGENT_PATH_COIN_TYPE, AGENT_PATH_PURPOSE, HARDENED_OFFSET,
+};
+pub use keys::{KeyManager, KeyMatch, VersionedPublicKey, DEFAULT_OVERLAP_PERIOD_HOURS};
+pub use keystore::{KdfParams, Keystore, KeystoreEntry, KEYSTORE_VERSION};
+pub use multisig::{MultiSignature, SignerSignature};
+pub use provider::Ed25519Provider;
+pub use sealed::{open_sealed, seal};
+pub use secret::SecretBytes;
+pub use shamir::{combine_shares, split_key, KeyShare, SHARE_VERSION};
+pub use signatures::{BatchItem, Signature};
+pub use signer::{
+    serve_signer, InMemorySigner, RemoteSigner, Signer, SignerRequest, SignerResponse,
+};
+pub use stream::{StreamCipher, DEFAULT_CHUNK_SIZE, MAX_CHUNK_SIZE, STREAM_VERSION};
+pub use strength::{KeyIssue, KeyStrengthReport};
 
 /// Result type for cryptographic operations
 pub type Result<T> = std::result::Result<T, CryptoError>;
 
 /// A key pair consisting of a public and private key
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Serializing a key pair only writes out the public key. The result
+/// deserializes as a [`PublicOnlyKeyPair`]; private keys have to be persisted
+/// explicitly, for example with a [`Keystore`].
+#[derive(Debug, Clone, Serialize)]
 pub struct KeyPair {
     /// The public key component
     pub public_key: PublicKey,
     /// The private key component
-    #[serde(skip_serializing, skip_deserializing, default)]
+    #[serde(skip_serializing)]
     pub private_key: PrivateKey,
 }
 
+/// The public half of a serialized [`KeyPair`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicOnlyKeyPair {
+    /// The public key component
+    pub public_key: PublicKey,
+}
+
 /// A public key used for verification and encryption
+///
+/// Ed25519 keys serialize exactly as before algorithm tags were introduced
+/// (a bare `key_bytes` array); other algorithms add an `algorithm` field.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "KeyRepr<Vec<u8>>", into = "KeyRepr<Vec<u8>>")]
 pub struct PublicKey {
+    /// The signature algorithm of this key
+    pub(crate) algorithm: KeyAlgorithm,
     /// The raw public key bytes
-    pub key_bytes: Vec<u8>,
+    pub(crate) key_bytes: Vec<u8>,
     /// The verifying key for signatures
-    #[serde(skip)]
-    pub verifying_key: Option<VerifyingKey>,
+    pub(crate) verifying_key: VerifyingKeyKind,
 }
 
 /// A private key used for signing and decryption
-#[derive(Debug, Clone, Serialize)]
+///
+/// The key bytes are wiped from memory on drop, redacted from `Debug` output
+/// and compared in constant time.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "KeyRepr<SecretBytes>", into = "KeyRepr<SecretBytes>")]
 pub struct PrivateKey {
+    /// The signature algorithm of this key
+    pub(crate) algorithm: KeyAlgorithm,
     /// The raw private key bytes
-    pub key_bytes: Vec<u8>,
+    pub(crate) key_bytes: SecretBytes,
     /// The signing key for signatures
-    #[serde(skip_serializing, skip_deserializing)]
-    pub signing_key: SigningKey,
-}
-
-impl<'de> Deserialize<'de> for PrivateKey {
-    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        #[derive(Deserialize)]
-        #[serde(field_identifier)]
-        enum Field {
-            #[serde(rename = "key_bytes")]
-            KeyBytes,
+    pub(crate) signing_key: SigningKeyKind,
+}
+
+/// Serialized form of [`PublicKey`] and [`PrivateKey`]
+#[derive(Serialize, Deserialize)]
+struct KeyRepr<B> {
+    #[serde(default, skip_serializing_if = "KeyAlgorithm::is_ed25519")]
+    algorithm: KeyAlgorithm,
+    key_bytes: B,
+}
+
+impl TryFrom<KeyRepr<Vec<u8>>> for PublicKey {
+    type Error = CryptoError;
+
+    fn try_from(repr: KeyRepr<Vec<u8>>) -> Result<Self> {
+        Self::from_algorithm_bytes(repr.algorithm, &repr.key_bytes)
+    }
+}
+
+impl From<PublicKey> for KeyRepr<Vec<u8>> {
+    fn from(key: PublicKey) -> Self {
+        Self {
+            algorithm: key.algorithm,
+            key_bytes: key.key_bytes,
         }
+    }
+}
+
+impl TryFrom<KeyRepr<SecretBytes>> for PrivateKey {
+    type Error = CryptoError;
 
-        struct PrivateKeyVisitor;
-
-        impl<'de> de::Visitor<'de> for PrivateKeyVisitor {
-            type Value = PrivateKey;
-
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("struct PrivateKey")
-            }
-
-            fn visit_map<V>(self, mut map: V) -> std::result::Result<PrivateKey, V::Error>
-            where
-                V: de::MapAccess<'de>,
-            {
-                let mut key_bytes: Option<Vec<u8>> = None;
-                while let Some(key) = map.next_key()? {
-                    match key {
-                        Field::KeyBytes => {
-                            if key_bytes.is_some() {
-                                return Err(de::Error::duplicate_field("key_bytes"));
-                            }
-                            key_bytes = Some(map.next_value()?);
-                        }
-                    }
-                }
-                let key_bytes = key_bytes.ok_or_else(|| de::Error::missing_field("key_bytes"))?;
-                let key_bytes_clone = key_bytes.clone();
-                let signing_key = SigningKey::from_bytes(
-                    &key_bytes_clone
-                        .try_into()
-                        .map_err(|_| de::Error::custom("Invalid key length"))?,
-                );
-                Ok(PrivateKey {
-                    key_bytes,
-                    signing_key,
-                })
-            }
+    fn try_from(repr: KeyRepr<SecretBytes>) -> Result<Self> {
+        Self::from_algorithm_bytes(repr.algorithm, repr.key_bytes.expose_secret())
+    }
+}
+
+impl From<PrivateKey> for KeyRepr<SecretBytes> {
+    fn from(key: PrivateKey) -> Self {
+        Self {
+            algorithm: key.algorithm,
+            key_bytes: key.key_bytes,
         }
+    }
+}
 
-        deserializer.deserialize_struct("PrivateKey", &["key_bytes"], PrivateKeyVisitor)
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("algorithm", &self.algorithm)
+            .field("key_bytes", &self.key_bytes)
+            .finish_non_exhaustive()
     }
 }
 
-impl Default for PrivateKey {
-    fn default() -> Self {
-        panic!("Default not implemented for PrivateKey");
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.algorithm == other.algorithm && self.key_bytes == other.key_bytes
     }
 }
 
+impl Eq for PrivateKey {}
+
 impl KeyPair {
     /// Create a new key pair from existing keys
     pub fn new(public_key: PublicKey, private_key: PrivateKey) -> Self {
@@ -126,19 +182,21 @@ impl KeyPair {
         }
     }
 
-    /// Generate a new key pair
+    /// Generate a new Ed25519 key pair
     pub fn generate() -> Result<Self> {
-        let mut rng = OsRng;
-        let mut secret_key_bytes = [0u8; 32];
-        rng.fill_bytes(&mut secret_key_bytes);
+        Self::generate_with_algorithm(KeyAlgorithm::Ed25519)
+    }
 
-        let signing_key = SigningKey::from_bytes(&secret_key_bytes);
-        let verifying_key = VerifyingKey::from(&signing_key);
+    /// Generate a new key pair for the given algorithm
+    pub fn generate_with_algorithm(algorithm: KeyAlgorithm) -> Result<Self> {
+        Ok(Self::from_private_key(PrivateKey::from_signing_key(
+            SigningKeyKind::generate(algorithm)?,
+        )))
+    }
 
-        Ok(Self::new(
-            PublicKey::from_bytes(&verifying_key.to_bytes())?,
-            PrivateKey::from_bytes(&secret_key_bytes)?,
-        ))
+    /// Create a key pair by deriving the public key from a private key
+    pub fn from_private_key(private_key: PrivateKey) -> Self {
+        Self::new(private_key.public_key(), private_key)
     }
 
     /// Get the public key
@@ -151,52 +209,144 @@ impl KeyPair {
         &self.private_key
     }
 
+    /// Get the signature algorithm of this key pair
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        self.public_key.algorithm
+    }
+
+    /// Get the public half of this key pair
+    pub fn to_public_only(&self) -> PublicOnlyKeyPair {
+        PublicOnlyKeyPair {
+            public_key: self.public_key.clone(),
+        }
+    }
+
     /// Check if the key pair is valid
+    ///
+    /// A valid key pair has a public key that matches the public half of the
+    /// private key.
     pub fn is_valid(&self) -> bool {
-        self.public_key.verifying_key.is_some() && !self.private_key.key_bytes.is_empty()
+        !self.private_key.key_bytes.is_empty()
+            && self.public_key.verifying_key == self.private_key.signing_key.verifying_key()
     }
 }
 
 impl PublicKey {
-    /// Create a new public key from bytes
+    /// Create a new Ed25519 public key from bytes
     pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self> {
-        let verifying_key = VerifyingKey::from_bytes(bytes)
-            .map_err(|e| CryptoError::InvalidKeyFormat(e.to_string()))?;
+        Self::from_algorithm_bytes(KeyAlgorithm::Ed25519, bytes)
+    }
+
+    /// Create a new public key for the given algorithm from bytes
+    ///
+    /// ECDSA keys are SEC1 points and are stored compressed.
+    pub fn from_algorithm_bytes(algorithm: KeyAlgorithm, bytes: &[u8]) -> Result<Self> {
+        let verifying_key = VerifyingKeyKind::from_bytes(algorithm, bytes)?;
 
         Ok(Self {
-            key_bytes: bytes.to_vec(),
-            verifying_key: Some(verifying_key),
+            algorithm,
+            key_bytes: verifying_key.to_bytes(),
+            verifying_key,
         })
     }
 
+    /// Get the signature algorithm of this key
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        self.algorithm
+    }
+
     /// Get the raw key bytes
     pub fn to_bytes(&self) -> &[u8] {
         &self.key_bytes
     }
+
+    /// Get a stable identifier for this key
+    ///
+    /// The identifier is the RFC 7638 JWK thumbprint of the key.
+    pub fn key_id(&self) -> String {
+        self.jwk_thumbprint()
+    }
+
+    /// Get the Ed25519 verifying key, failing for other algorithms
+    pub(crate) fn ed25519_verifying_key(&self) -> Result<&ed25519_dalek::VerifyingKey> {
+        match &self.verifying_key {
+            VerifyingKeyKind::Ed25519(key) => Ok(key),
+            #[allow(unreachable_patterns)]
+            _ => Err(CryptoError::UnsupportedAlgorithm(format!(
+                "Expected an Ed25519 key, got {}",
+                self.algorithm
+            ))),
+        }
+    }
 }
 
 impl PartialEq for PublicKey {
     fn eq(&self, other: &Self) -> bool {
-        self.key_bytes == other.key_bytes
+        self.algorithm == other.algorithm && self.key_bytes == other.key_bytes
     }
 }
 
 impl Eq for PublicKey {}
 
 impl PrivateKey {
-    /// Create a new private key from bytes
+    /// Create a new Ed25519 private key from bytes
     pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self> {
-        let signing_key = SigningKey::from_bytes(bytes);
+        Self::from_algorithm_bytes(KeyAlgorithm::Ed25519, bytes)
+    }
 
-        Ok(Self {
-            key_bytes: bytes.to_vec(),
+    /// Create a new private key for the given algorithm from bytes
+    pub fn from_algorithm_bytes(algorithm: KeyAlgorithm, bytes: &[u8]) -> Result<Self> {
+        Ok(Self::from_signing_key(SigningKeyKind::from_bytes(
+            algorithm, bytes,
+        )?))
+    }
+
+    fn from_signing_key(signing_key: SigningKeyKind) -> Self {
+        Self {
+            algorithm: signing_key.verifying_key().algorithm(),
+            key_bytes: SecretBytes::from_slice(&signing_key.to_bytes()),
             signing_key,
-        })
+        }
+    }
+
+    /// Get the signature algorithm of this key
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        self.algorithm
     }
 
     /// Get the raw key bytes
     pub fn to_bytes(&self) -> &[u8] {
-        &self.key_bytes
+        self.key_bytes.expose_secret()
+    }
+
+    /// Get the public key matching this private key
+    pub fn public_key(&self) -> PublicKey {
+        let verifying_key = self.signing_key.verifying_key();
+        PublicKey {
+            algorithm: self.algorithm,
+            key_bytes: verifying_key.to_bytes(),
+            verifying_key,
+        }
+    }
+
+    /// Sign a message with this key
+    pub fn sign(&self, message: &[u8]) -> Result<Signature> {
+        Ok(Signature {
+            algorithm: self.algorithm,
+            signature_bytes: self.signing_key.sign(message)?,
+        })
+    }
+
+    /// Get the Ed25519 signing key, failing for other algorithms
+    pub(crate) fn ed25519_signing_key(&self) -> Result<&ed25519_dalek::SigningKey> {
+        match &self.signing_key {
+            SigningKeyKind::Ed25519(key) => Ok(key),
+            #[allow(unreachable_patterns)]
+            _ => Err(CryptoError::UnsupportedAlgorithm(format!(
+                "Expected an Ed25519 key, got {}",
+                self.algorithm
+            ))),
+        }
     }
 }
 
@@ -206,12 +356,18 @@ pub trait CryptoOperations {
     /// Generate a new key pair
     async fn generate_key_pair() -> Result<KeyPair>;
 
-    /// Sign a message with a private key
-    async fn sign(&self, message: &[u8], private_key: &PrivateKey) -> Result<Signature>;
+    /// Sign a message in the given context with a private key
+    async fn sign(
+        &self,
+        context: &SigningContext,
+        message: &[u8],
+        private_key: &PrivateKey,
+    ) -> Result<Signature>;
 
-    /// Verify a signature with a public key
+    /// Verify a signature made in the given context with a public key
     async fn verify(
         &self,
+        context: &SigningContext,
         message: &[u8],
         signature: &Signature,
         public_key: &PublicKey,
@@ -227,3 +383,138 @@ pub trait CryptoOperations {
         private_key: &PrivateKey,
     ) -> Result<Vec<u8>>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_key_debug_is_redacted() {
+        let key_pair = KeyPair::generate().unwrap();
+        let debug = format!("{:?}", key_pair);
+        let secret = format!("{:?}", key_pair.private_key().to_bytes());
+        assert!(debug.contains("REDACTED"));
+        assert!(!debug.contains(&secret));
+    }
+
+    #[test]
+    fn test_private_key_equality() {
+        let key_pair = KeyPair::generate().unwrap();
+        let bytes: [u8; 32] = key_pair.private_key().to_bytes().try_into().unwrap();
+        assert_eq!(
+            &PrivateKey::from_bytes(&bytes).unwrap(),
+            key_pair.private_key()
+        );
+        assert_ne!(
+            KeyPair::generate().unwrap().private_key(),
+            key_pair.private_key()
+        );
+    }
+
+    #[test]
+    fn test_private_key_serde_roundtrip() {
+        let key_pair = KeyPair::generate().unwrap();
+        let json = serde_json::to_string(key_pair.private_key()).unwrap();
+        let decoded: PrivateKey = serde_json::from_str(&json).unwrap();
+        assert_eq!(&decoded, key_pair.private_key());
+
+        assert!(serde_json::from_str::<PrivateKey>(r#"{"key_bytes":[1,2,3]}"#).is_err());
+        assert!(serde_json::from_str::<PrivateKey>("{}").is_err());
+    }
+
+    #[test]
+    fn test_key_pair_serializes_public_only() {
+        let key_pair = KeyPair::generate().unwrap();
+        let json = serde_json::to_value(&key_pair).unwrap();
+        assert!(json.get("private_key").is_none());
+
+        let decoded: PublicOnlyKeyPair = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, key_pair.to_public_only());
+    }
+
+    #[test]
+    fn test_ed25519_serialization_is_untagged() {
+        let key_pair = KeyPair::generate().unwrap();
+        let public_json = serde_json::to_value(key_pair.public_key()).unwrap();
+        assert!(public_json.get("algorithm").is_none());
+
+        let signature = key_pair.private_key().sign(b"message").unwrap();
+        let signature_json = serde_json::to_value(&signature).unwrap();
+        assert!(signature_json.get("algorithm").is_none());
+
+        // Data written before algorithm tags were introduced still decodes
+        let legacy = serde_json::json!({ "key_bytes": key_pair.public_key().to_bytes() });
+        let decoded: PublicKey = serde_json::from_value(legacy).unwrap();
+        assert_eq!(&decoded, key_pair.public_key());
+        assert_eq!(decoded.algorithm(), KeyAlgorithm::Ed25519);
+
+        let legacy = serde_json::json!({ "signature_bytes": signature.as_bytes() });
+        let decoded: Signature = serde_json::from_value(legacy).unwrap();
+        assert!(decoded.verify(b"message", key_pair.public_key()).unwrap());
+    }
+
+    #[cfg(not(feature = "p256"))]
+    #[test]
+    fn test_disabled_algorithm_is_rejected() {
+        assert!(matches!(
+            KeyPair::generate_with_algorithm(KeyAlgorithm::P256),
+            Err(CryptoError::UnsupportedAlgorithm(_))
+        ));
+        assert!(serde_json::from_value::<PublicKey>(serde_json::json!({
+            "algorithm": "P-256",
+            "key_bytes": vec![2u8; 33],
+        }))
+        .is_err());
+    }
+
+    #[cfg(any(feature = "p256", feature = "secp256k1"))]
+    #[tokio::test]
+    async fn test_ecdsa_sign_and_verify() {
+        let mut algorithms = Vec::new();
+        if cfg!(feature = "p256") {
+            algorithms.push(KeyAlgorithm::P256);
+        }
+        if cfg!(feature = "secp256k1") {
+            algorithms.push(KeyAlgorithm::Secp256k1);
+        }
+
+        for algorithm in algorithms {
+            let key_pair = KeyPair::generate_with_algorithm(algorithm).unwrap();
+            let signature = key_pair.private_key().sign(b"message").unwrap();
+            assert_eq!(signature.algorithm(), algorithm);
+            assert!(signature.verify(b"message", key_pair.public_key()).unwrap());
+            assert!(signature.verify(b"other", key_pair.public_key()).is_err());
+
+            // Tagged serialization roundtrips
+            let json = serde_json::to_value(key_pair.public_key()).unwrap();
+            assert_eq!(json["algorithm"], algorithm.to_string());
+            let decoded: PublicKey = serde_json::from_value(json).unwrap();
+            assert_eq!(&decoded, key_pair.public_key());
+
+            // Signatures are not accepted under a different algorithm
+            let ed25519 = KeyPair::generate().unwrap();
+            assert!(signature.verify(b"message", ed25519.public_key()).is_err());
+
+            // The key manager dispatches on the key's algorithm
+            let manager = KeyManager::new(key_pair.clone(), 128);
+            assert!(manager
+                .validate_key_strength(&key_pair)
+                .await
+                .unwrap()
+                .is_acceptable());
+            let signature = manager
+                .sign_with_current_key(&SigningContext::ATTESTATION, b"message")
+                .await
+                .unwrap();
+            assert!(manager
+                .verify(
+                    &SigningContext::ATTESTATION,
+                    b"message",
+                    &signature,
+                    key_pair.public_key()
+                )
+                .await
+                .unwrap());
+        }
+    }
+}
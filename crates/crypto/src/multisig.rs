@@ -0,0 +1,485 @@
+//! M-of-N multi-signatures over a single message.
+//!
+//! A [`MultiSignature`] fixes the set of authorized signers, the number of
+//! them that must sign, the [`SigningContext`] and the canonical message
+//! bytes. Signatures are collected one at a time and checked as they are
+//! added.
+//!
+//! The message, signer set and threshold travel with the container, so
+//! whoever produced it chose them. [`MultiSignature::verify`] therefore
+//! checks the signatures against the verifier's own message, signer set and
+//! threshold, never the ones the container declares.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CryptoError, PublicKey, Result, Signature, Signer, SigningContext};
+
+/// A signature by one member of the signer set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignerSignature {
+    /// The key ID of the signer
+    pub key_id: String,
+    /// The signature over the container's message, in the container's context
+    pub signature: Signature,
+}
+
+/// Signatures by M of N authorized keys over one message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiSignature {
+    signers: Vec<PublicKey>,
+    threshold: usize,
+    context: SigningContext,
+    message: Vec<u8>,
+    signatures: Vec<SignerSignature>,
+}
+
+impl MultiSignature {
+    /// Create a new container requiring `threshold` of `signers` to sign
+    /// `message` in `context`
+    ///
+    /// Fails if a key appears more than once in the signer set or if the
+    /// threshold is zero or larger than the number of signers.
+    pub fn new(
+        signers: Vec<PublicKey>,
+        threshold: usize,
+        context: SigningContext,
+        message: Vec<u8>,
+    ) -> Result<Self> {
+        Self::check_policy(&signers, threshold)?;
+
+        Ok(Self {
+            signers,
+            threshold,
+            context,
+            message,
+            signatures: Vec::new(),
+        })
+    }
+
+    /// Get the authorized signers
+    pub fn signers(&self) -> &[PublicKey] {
+        &self.signers
+    }
+
+    /// Get the number of signatures required
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Get the context the message is signed in
+    pub fn context(&self) -> &SigningContext {
+        &self.context
+    }
+
+    /// Get the message being signed
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// Get the signatures collected so far
+    pub fn signatures(&self) -> &[SignerSignature] {
+        &self.signatures
+    }
+
+    /// Check if enough signatures have been collected
+    pub fn is_complete(&self) -> bool {
+        self.signatures.len() >= self.threshold
+    }
+
+    /// Get the key IDs of authorized signers that have not signed yet
+    pub fn pending_signers(&self) -> Vec<String> {
+        self.signers
+            .iter()
+            .map(PublicKey::key_id)
+            .filter(|key_id| !self.has_signed(key_id))
+            .collect()
+    }
+
+    /// Add a signature by the signer with the given key ID
+    ///
+    /// The signature must be made in the container's context and is verified
+    /// immediately. Fails if the key is not in the signer set or has already
+    /// signed.
+    pub fn add_signature(&mut self, key_id: &str, signature: Signature) -> Result<()> {
+        let public_key = self.signer(key_id)?;
+        if self.has_signed(key_id) {
+            return Err(CryptoError::MultiSignatureError(format!(
+                "Signer has already signed: {}",
+                key_id
+            )));
+        }
+        self.context.verify(&self.message, &signature, public_key)?;
+
+        self.signatures.push(SignerSignature {
+            key_id: key_id.to_string(),
+            signature,
+        });
+        Ok(())
+    }
+
+    /// Sign the message with the given signer and add the signature
+    pub async fn sign_with(&mut self, signer: &dyn Signer) -> Result<()> {
+        let key_id = signer.key_id().await?;
+        // Check membership before asking a possibly remote signer to sign
+        self.signer(&key_id)?;
+        let signature = self.context.sign_with(&self.message, signer).await?;
+        self.add_signature(&key_id, signature)
+    }
+
+    /// Verify that at least `min_threshold` distinct keys of
+    /// `expected_signers` signed `expected_message` in the expected context
+    ///
+    /// Every collected signature must be valid and come from a distinct
+    /// member of both the container's signer set and `expected_signers`. The
+    /// container's own threshold must be met as well.
+    pub fn verify(
+        &self,
+        context: &SigningContext,
+        expected_message: &[u8],
+        expected_signers: &[PublicKey],
+        min_threshold: usize,
+    ) -> Result<()> {
+        if &self.context != context {
+            return Err(CryptoError::InvalidSigningContext(format!(
+                "Expected {}, got {}",
+                context, self.context
+            )));
+        }
+        if self.message != expected_message {
+            return Err(CryptoError::MultiSignatureError(
+                "Signed message is not the expected message".into(),
+            ));
+        }
+        Self::check_policy(expected_signers, min_threshold)?;
+        Self::check_policy(&self.signers, self.threshold)?;
+
+        let mut seen = HashSet::new();
+        for entry in &self.signatures {
+            if !seen.insert(entry.key_id.as_str()) {
+                return Err(CryptoError::MultiSignatureError(format!(
+                    "Duplicate signature by: {}",
+                    entry.key_id
+                )));
+            }
+            self.signer(&entry.key_id)?;
+            let public_key = expected_signers
+                .iter()
+                .find(|signer| signer.key_id() == entry.key_id)
+                .ok_or_else(|| {
+                    CryptoError::MultiSignatureError(format!(
+                        "Not an expected signer: {}",
+                        entry.key_id
+                    ))
+                })?;
+            self.context
+                .verify(&self.message, &entry.signature, public_key)?;
+        }
+
+        let required = self.threshold.max(min_threshold);
+        if seen.len() < required {
+            return Err(CryptoError::MultiSignatureError(format!(
+                "Only {} of the required {} signatures are present",
+                seen.len(),
+                required
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the signer set and threshold
+    fn check_policy(signers: &[PublicKey], threshold: usize) -> Result<()> {
+        if threshold == 0 || threshold > signers.len() {
+            return Err(CryptoError::MultiSignatureError(format!(
+                "Threshold must be between 1 and {}, got {}",
+                signers.len(),
+                threshold
+            )));
+        }
+
+        let mut seen = HashSet::new();
+        for signer in signers {
+            let key_id = signer.key_id();
+            if !seen.insert(key_id.clone()) {
+                return Err(CryptoError::MultiSignatureError(format!(
+                    "Duplicate signer: {}",
+                    key_id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the authorized signer with the given key ID
+    fn signer(&self, key_id: &str) -> Result<&PublicKey> {
+        self.signers
+            .iter()
+            .find(|signer| signer.key_id() == key_id)
+            .ok_or_else(|| {
+                CryptoError::MultiSignatureError(format!("Not an authorized signer: {}", key_id))
+            })
+    }
+
+    /// Check if the signer with the given key ID has signed
+    fn has_signed(&self, key_id: &str) -> bool {
+        self.signatures.iter().any(|entry| entry.key_id == key_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InMemorySigner, KeyPair};
+
+    const MESSAGE: &[u8] = b"approve payment";
+
+    fn key_pairs(count: usize) -> Vec<KeyPair> {
+        (0..count).map(|_| KeyPair::generate().unwrap()).collect()
+    }
+
+    fn multisig(key_pairs: &[KeyPair], threshold: usize) -> MultiSignature {
+        let signers = key_pairs.iter().map(|k| k.public_key().clone()).collect();
+        MultiSignature::new(
+            signers,
+            threshold,
+            SigningContext::MULTISIG,
+            MESSAGE.to_vec(),
+        )
+        .unwrap()
+    }
+
+    fn public_keys(key_pairs: &[KeyPair]) -> Vec<PublicKey> {
+        key_pairs.iter().map(|k| k.public_key().clone()).collect()
+    }
+
+    fn sign(multisig: &mut MultiSignature, key_pair: &KeyPair) -> Result<()> {
+        let signature = multisig
+            .context()
+            .sign(multisig.message(), key_pair.private_key())?;
+        multisig.add_signature(&key_pair.public_key().key_id(), signature)
+    }
+
+    #[test]
+    fn test_threshold_reached() {
+        let key_pairs = key_pairs(3);
+        let mut multisig = multisig(&key_pairs, 2);
+
+        sign(&mut multisig, &key_pairs[0]).unwrap();
+        assert!(!multisig.is_complete());
+        assert!(matches!(
+            multisig.verify(
+                &SigningContext::MULTISIG,
+                MESSAGE,
+                &public_keys(&key_pairs),
+                2
+            ),
+            Err(CryptoError::MultiSignatureError(_))
+        ));
+        assert_eq!(multisig.pending_signers().len(), 2);
+
+        sign(&mut multisig, &key_pairs[2]).unwrap();
+        assert!(multisig.is_complete());
+        multisig
+            .verify(
+                &SigningContext::MULTISIG,
+                MESSAGE,
+                &public_keys(&key_pairs),
+                2,
+            )
+            .unwrap();
+        assert_eq!(
+            multisig.pending_signers(),
+            vec![key_pairs[1].public_key().key_id()]
+        );
+    }
+
+    #[test]
+    fn test_rejects_duplicate_signers() {
+        let key_pair = KeyPair::generate().unwrap();
+        let signers = vec![key_pair.public_key().clone(), key_pair.public_key().clone()];
+        assert!(matches!(
+            MultiSignature::new(signers, 1, SigningContext::MULTISIG, b"message".to_vec()),
+            Err(CryptoError::MultiSignatureError(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_threshold() {
+        let key_pairs = key_pairs(2);
+        let signers: Vec<_> = key_pairs.iter().map(|k| k.public_key().clone()).collect();
+        assert!(MultiSignature::new(
+            signers.clone(),
+            0,
+            SigningContext::MULTISIG,
+            b"message".to_vec()
+        )
+        .is_err());
+        assert!(
+            MultiSignature::new(signers, 3, SigningContext::MULTISIG, b"message".to_vec()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_rejects_repeated_and_unauthorized_signatures() {
+        let key_pairs = key_pairs(3);
+        let mut multisig = multisig(&key_pairs[..2], 2);
+
+        sign(&mut multisig, &key_pairs[0]).unwrap();
+        assert!(matches!(
+            sign(&mut multisig, &key_pairs[0]),
+            Err(CryptoError::MultiSignatureError(_))
+        ));
+        assert!(matches!(
+            sign(&mut multisig, &key_pairs[2]),
+            Err(CryptoError::MultiSignatureError(_))
+        ));
+
+        // A signature over a different message is rejected
+        let key_id = key_pairs[1].public_key().key_id();
+        let signature = SigningContext::MULTISIG
+            .sign(b"other", key_pairs[1].private_key())
+            .unwrap();
+        assert!(multisig.add_signature(&key_id, signature).is_err());
+
+        // So is a signature over the message in another context
+        let signature = SigningContext::ATTESTATION
+            .sign(multisig.message(), key_pairs[1].private_key())
+            .unwrap();
+        assert!(multisig.add_signature(&key_id, signature).is_err());
+        assert_eq!(multisig.signatures().len(), 1);
+    }
+
+    #[test]
+    fn test_verify_rejects_tampered_container() {
+        let key_pairs = key_pairs(2);
+        let mut multisig = multisig(&key_pairs, 2);
+        sign(&mut multisig, &key_pairs[0]).unwrap();
+        sign(&mut multisig, &key_pairs[1]).unwrap();
+
+        // Counting the same signer twice does not reach the threshold
+        let mut json = serde_json::to_value(&multisig).unwrap();
+        json["signatures"][1] = json["signatures"][0].clone();
+        let tampered: MultiSignature = serde_json::from_value(json).unwrap();
+        assert!(tampered
+            .verify(
+                &SigningContext::MULTISIG,
+                MESSAGE,
+                &public_keys(&key_pairs),
+                2
+            )
+            .is_err());
+
+        // Changing the message invalidates the signatures
+        let mut json = serde_json::to_value(&multisig).unwrap();
+        json["message"] = serde_json::json!(b"approve refund".to_vec());
+        let tampered: MultiSignature = serde_json::from_value(json).unwrap();
+        assert!(tampered
+            .verify(
+                &SigningContext::MULTISIG,
+                MESSAGE,
+                &public_keys(&key_pairs),
+                2
+            )
+            .is_err());
+
+        let decoded: MultiSignature =
+            serde_json::from_str(&serde_json::to_string(&multisig).unwrap()).unwrap();
+        decoded
+            .verify(
+                &SigningContext::MULTISIG,
+                MESSAGE,
+                &public_keys(&key_pairs),
+                2,
+            )
+            .unwrap();
+
+        // The container only verifies in the context it was created for
+        assert!(matches!(
+            decoded.verify(
+                &SigningContext::ATTESTATION,
+                MESSAGE,
+                &public_keys(&key_pairs),
+                2
+            ),
+            Err(CryptoError::InvalidSigningContext(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_rejects_self_declared_signers() {
+        let key_pairs = key_pairs(3);
+        let expected = public_keys(&key_pairs[..2]);
+
+        // An attacker declares their own key as the only signer
+        let mut forged = multisig(&key_pairs[2..], 1);
+        sign(&mut forged, &key_pairs[2]).unwrap();
+        forged
+            .verify(
+                &SigningContext::MULTISIG,
+                MESSAGE,
+                &public_keys(&key_pairs[2..]),
+                1,
+            )
+            .unwrap();
+        assert!(matches!(
+            forged.verify(&SigningContext::MULTISIG, MESSAGE, &expected, 1),
+            Err(CryptoError::MultiSignatureError(_))
+        ));
+
+        // Or lowers the threshold of the expected signer set
+        let mut lowered = multisig(&key_pairs[..2], 1);
+        sign(&mut lowered, &key_pairs[0]).unwrap();
+        lowered
+            .verify(&SigningContext::MULTISIG, MESSAGE, &expected, 1)
+            .unwrap();
+        assert!(matches!(
+            lowered.verify(&SigningContext::MULTISIG, MESSAGE, &expected, 2),
+            Err(CryptoError::MultiSignatureError(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_rejects_other_message() {
+        let key_pairs = key_pairs(2);
+        let expected = public_keys(&key_pairs);
+
+        // A complete approval of another message in the same context
+        let mut other = MultiSignature::new(
+            expected.clone(),
+            2,
+            SigningContext::MULTISIG,
+            b"approve refund".to_vec(),
+        )
+        .unwrap();
+        sign(&mut other, &key_pairs[0]).unwrap();
+        sign(&mut other, &key_pairs[1]).unwrap();
+        other
+            .verify(&SigningContext::MULTISIG, b"approve refund", &expected, 2)
+            .unwrap();
+        assert!(matches!(
+            other.verify(&SigningContext::MULTISIG, MESSAGE, &expected, 2),
+            Err(CryptoError::MultiSignatureError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sign_with_signer() {
+        let key_pairs = key_pairs(2);
+        let mut multisig = multisig(&key_pairs, 1);
+
+        let outsider = InMemorySigner::new(KeyPair::generate().unwrap());
+        assert!(multisig.sign_with(&outsider).await.is_err());
+
+        let signer = InMemorySigner::new(key_pairs[1].clone());
+        multisig.sign_with(&signer).await.unwrap();
+        multisig
+            .verify(
+                &SigningContext::MULTISIG,
+                MESSAGE,
+                &public_keys(&key_pairs),
+                1,
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,310 @@
+//! Default implementation of [`CryptoOperations`] built on Ed25519 and X25519.
+//!
+//! Signatures are Ed25519 over the input of a [`SigningContext`], so they
+//! only verify in the context they were made for. Public-key encryption
+//! converts the Ed25519 keys to their X25519 (Montgomery) form, performs an
+//! ephemeral-static ECDH exchange, derives a ChaCha20-Poly1305 key with
+//! HKDF-SHA256 and seals the payload with [`EncryptionKey`].
+
+use async_trait::async_trait;
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::{
+    CryptoError, CryptoOperations, EncryptedData, EncryptionKey, KeyPair, PrivateKey, PublicKey,
+    Result, Signature, SigningContext,
+};
+
+/// HKDF info prefix used when deriving the symmetric key from the ECDH secret
+const KDF_INFO: &[u8] = b"agentid/x25519-chacha20poly1305/v1";
+
+/// Crypto provider for Ed25519 signatures and X25519 public-key encryption
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ed25519Provider;
+
+impl Ed25519Provider {
+    /// Create a new provider
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Convert an Ed25519 public key to its X25519 equivalent
+    pub(crate) fn x25519_public_key(public_key: &PublicKey) -> Result<X25519PublicKey> {
+        let verifying_key = public_key.ed25519_verifying_key()?;
+
+        Ok(X25519PublicKey::from(
+            verifying_key.to_montgomery().to_bytes(),
+        ))
+    }
+
+    /// Convert an Ed25519 private key to its X25519 equivalent
+    pub(crate) fn x25519_static_secret(private_key: &PrivateKey) -> Result<StaticSecret> {
+        Ok(StaticSecret::from(
+            private_key.ed25519_signing_key()?.to_scalar_bytes(),
+        ))
+    }
+
+    /// Derive the symmetric key shared between the ephemeral and recipient keys
+    fn derive_key(
+        shared_secret: &x25519_dalek::SharedSecret,
+        ephemeral: &X25519PublicKey,
+        recipient: &X25519PublicKey,
+    ) -> Result<EncryptionKey> {
+        if !shared_secret.was_contributory() {
+            return Err(CryptoError::InvalidKeyFormat(
+                "Key exchange produced a non-contributory shared secret".into(),
+            ));
+        }
+
+        let mut info = Vec::with_capacity(KDF_INFO.len() + 64);
+        info.extend_from_slice(KDF_INFO);
+        info.extend_from_slice(ephemeral.as_bytes());
+        info.extend_from_slice(recipient.as_bytes());
+
+        let mut key_bytes = Zeroizing::new([0u8; 32]);
+        Hkdf::<Sha256>::new(None, shared_secret.as_bytes())
+            .expand(&info, key_bytes.as_mut())
+            .map_err(|e| CryptoError::InternalError(e.to_string()))?;
+
+        EncryptionKey::from_bytes(key_bytes.as_ref())
+    }
+}
+
+#[async_trait]
+impl CryptoOperations for Ed25519Provider {
+    async fn generate_key_pair() -> Result<KeyPair> {
+        KeyPair::generate()
+    }
+
+    async fn sign(
+        &self,
+        context: &SigningContext,
+        message: &[u8],
+        private_key: &PrivateKey,
+    ) -> Result<Signature> {
+        context.sign(message, private_key)
+    }
+
+    async fn verify(
+        &self,
+        context: &SigningContext,
+        message: &[u8],
+        signature: &Signature,
+        public_key: &PublicKey,
+    ) -> Result<bool> {
+        match context.verify(message, signature, public_key) {
+            Ok(valid) => Ok(valid),
+            Err(CryptoError::InvalidSignature(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn encrypt(&self, data: &[u8], public_key: &PublicKey) -> Result<EncryptedData> {
+        let recipient = Self::x25519_public_key(public_key)?;
+        let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
+        let ephemeral = X25519PublicKey::from(&ephemeral_secret);
+
+        let shared_secret = ephemeral_secret.diffie_hellman(&recipient);
+        let key = Self::derive_key(&shared_secret, &ephemeral, &recipient)?;
+
+        let mut encrypted = key.encrypt(data, None)?;
+        encrypted.ephemeral_key = Some(ephemeral.as_bytes().to_vec());
+        Ok(encrypted)
+    }
+
+    async fn decrypt(
+        &self,
+        encrypted_data: &EncryptedData,
+        private_key: &PrivateKey,
+    ) -> Result<Vec<u8>> {
+        let ephemeral_bytes: [u8; 32] = encrypted_data
+            .ephemeral_key
+            .as_deref()
+            .ok_or_else(|| CryptoError::DecryptionError("Missing ephemeral key".into()))?
+            .try_into()
+            .map_err(|_| CryptoError::DecryptionError("Invalid ephemeral key length".into()))?;
+        let ephemeral = X25519PublicKey::from(ephemeral_bytes);
+
+        let secret = Self::x25519_static_secret(private_key)?;
+        let recipient = X25519PublicKey::from(&secret);
+
+        let shared_secret = secret.diffie_hellman(&ephemeral);
+        let key = Self::derive_key(&shared_secret, &ephemeral, &recipient)?;
+
+        key.decrypt(encrypted_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sign_verify_roundtrip() {
+        let provider = Ed25519Provider::new();
+        let key_pair = Ed25519Provider::generate_key_pair().await.unwrap();
+
+        let signature = provider
+            .sign(
+                &SigningContext::DOCUMENT,
+                b"test message",
+                key_pair.private_key(),
+            )
+            .await
+            .unwrap();
+        assert!(provider
+            .verify(
+                &SigningContext::DOCUMENT,
+                b"test message",
+                &signature,
+                key_pair.public_key()
+            )
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_verify_rejects_tampered_message() {
+        let provider = Ed25519Provider::new();
+        let key_pair = Ed25519Provider::generate_key_pair().await.unwrap();
+
+        let signature = provider
+            .sign(
+                &SigningContext::DOCUMENT,
+                b"test message",
+                key_pair.private_key(),
+            )
+            .await
+            .unwrap();
+        assert!(!provider
+            .verify(
+                &SigningContext::DOCUMENT,
+                b"tampered message",
+                &signature,
+                key_pair.public_key()
+            )
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_verify_rejects_wrong_key() {
+        let provider = Ed25519Provider::new();
+        let key_pair = Ed25519Provider::generate_key_pair().await.unwrap();
+        let other = Ed25519Provider::generate_key_pair().await.unwrap();
+
+        let signature = provider
+            .sign(
+                &SigningContext::DOCUMENT,
+                b"test message",
+                key_pair.private_key(),
+            )
+            .await
+            .unwrap();
+        assert!(!provider
+            .verify(
+                &SigningContext::DOCUMENT,
+                b"test message",
+                &signature,
+                other.public_key()
+            )
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_verify_rejects_other_context() {
+        let provider = Ed25519Provider::new();
+        let key_pair = Ed25519Provider::generate_key_pair().await.unwrap();
+
+        let signature = provider
+            .sign(
+                &SigningContext::CHALLENGE,
+                b"test message",
+                key_pair.private_key(),
+            )
+            .await
+            .unwrap();
+        assert!(!provider
+            .verify(
+                &SigningContext::ATTESTATION,
+                b"test message",
+                &signature,
+                key_pair.public_key()
+            )
+            .await
+            .unwrap());
+        assert!(!signature
+            .verify(b"test message", key_pair.public_key())
+            .unwrap_or(false));
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_decrypt_roundtrip() {
+        let provider = Ed25519Provider::new();
+        let key_pair = Ed25519Provider::generate_key_pair().await.unwrap();
+
+        let encrypted = provider
+            .encrypt(b"secret payload", key_pair.public_key())
+            .await
+            .unwrap();
+        assert!(encrypted.ephemeral_key.is_some());
+
+        let decrypted = provider
+            .decrypt(&encrypted, key_pair.private_key())
+            .await
+            .unwrap();
+        assert_eq!(decrypted, b"secret payload");
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_with_wrong_key_fails() {
+        let provider = Ed25519Provider::new();
+        let key_pair = Ed25519Provider::generate_key_pair().await.unwrap();
+        let other = Ed25519Provider::generate_key_pair().await.unwrap();
+
+        let encrypted = provider
+            .encrypt(b"secret payload", key_pair.public_key())
+            .await
+            .unwrap();
+        assert!(provider
+            .decrypt(&encrypted, other.private_key())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_detects_tampering() {
+        let provider = Ed25519Provider::new();
+        let key_pair = Ed25519Provider::generate_key_pair().await.unwrap();
+
+        let encrypted = provider
+            .encrypt(b"secret payload", key_pair.public_key())
+            .await
+            .unwrap();
+
+        let mut tampered = encrypted.clone();
+        tampered.ciphertext[0] ^= 0x01;
+        assert!(provider
+            .decrypt(&tampered, key_pair.private_key())
+            .await
+            .is_err());
+
+        let mut tampered = encrypted.clone();
+        tampered.ephemeral_key.as_mut().unwrap()[0] ^= 0x01;
+        assert!(provider
+            .decrypt(&tampered, key_pair.private_key())
+            .await
+            .is_err());
+
+        let mut tampered = encrypted;
+        tampered.ephemeral_key = None;
+        assert!(matches!(
+            provider.decrypt(&tampered, key_pair.private_key()).await,
+            Err(CryptoError::DecryptionError(_))
+        ));
+    }
+}
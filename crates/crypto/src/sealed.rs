@@ -0,0 +1,192 @@
+//! Anonymous public-key encryption compatible with libsodium sealed boxes.
+//!
+//! [`seal`] encrypts to an agent's Ed25519 [`PublicKey`] without revealing
+//! or authenticating the sender: every message uses a fresh ephemeral X25519
+//! key, and the recipient's key is converted to its X25519 form exactly as
+//! `crypto_sign_ed25519_pk_to_curve25519` does. The result is the same as
+//! libsodium's `crypto_box_seal`, so either side can be implemented with
+//! libsodium. [`open_sealed`] decrypts with the recipient's [`PrivateKey`].
+//!
+//! In the returned [`EncryptedData`], `ephemeral_key` holds the sender's
+//! ephemeral public key, `nonce` the nonce derived from both public keys and
+//! `ciphertext` the XSalsa20-Poly1305 box (tag first). Use
+//! [`EncryptedData::to_sealed_box`] and [`EncryptedData::from_sealed_box`] to
+//! convert from and to libsodium's wire format.
+
+use blake2::{
+    digest::{consts::U24, Digest},
+    Blake2b,
+};
+use rand::rngs::OsRng;
+
+use crate::{CryptoError, Ed25519Provider, EncryptedData, PrivateKey, PublicKey, Result};
+
+/// Length of an X25519 public key
+const KEY_LENGTH: usize = 32;
+
+/// Length of the Poly1305 tag
+const TAG_LENGTH: usize = 16;
+
+/// Encrypt data so that only the holder of the recipient's private key can read it
+pub fn seal(data: &[u8], recipient: &PublicKey) -> Result<EncryptedData> {
+    let x25519 =
+        crypto_box::PublicKey::from(Ed25519Provider::x25519_public_key(recipient)?.to_bytes());
+    let sealed = x25519
+        .seal(&mut OsRng, data)
+        .map_err(|e| CryptoError::EncryptionError(e.to_string()))?;
+
+    EncryptedData::from_sealed_box(&sealed, recipient)
+}
+
+/// Decrypt data sealed to the public key matching `recipient`
+pub fn open_sealed(encrypted: &EncryptedData, recipient: &PrivateKey) -> Result<Vec<u8>> {
+    let secret = Ed25519Provider::x25519_static_secret(recipient)?;
+    let secret_key = crypto_box::SecretKey::from(secret.to_bytes());
+    let public_key = secret_key.public_key();
+
+    let ephemeral = encrypted
+        .ephemeral_key
+        .as_deref()
+        .ok_or_else(|| CryptoError::DecryptionError("Missing ephemeral key".into()))?;
+    if ephemeral.len() != KEY_LENGTH {
+        return Err(CryptoError::DecryptionError(
+            "Invalid ephemeral key length".into(),
+        ));
+    }
+    if encrypted.nonce != sealed_box_nonce(ephemeral, public_key.as_bytes()) {
+        return Err(CryptoError::DecryptionError(
+            "Nonce does not match a sealed box for this recipient".into(),
+        ));
+    }
+
+    secret_key
+        .unseal(&encrypted.to_sealed_box()?)
+        .map_err(|_| CryptoError::DecryptionError("Failed to open sealed box".into()))
+}
+
+impl EncryptedData {
+    /// Parse libsodium's sealed box format: the ephemeral public key
+    /// followed by the box
+    ///
+    /// The recipient's public key is needed to derive the nonce.
+    pub fn from_sealed_box(sealed: &[u8], recipient: &PublicKey) -> Result<Self> {
+        let recipient = Ed25519Provider::x25519_public_key(recipient)?;
+        if sealed.len() < KEY_LENGTH + TAG_LENGTH {
+            return Err(CryptoError::DecryptionError(
+                "Sealed box is too short".into(),
+            ));
+        }
+        let (ephemeral, ciphertext) = sealed.split_at(KEY_LENGTH);
+
+        Ok(Self {
+            ciphertext: ciphertext.to_vec(),
+            nonce: sealed_box_nonce(ephemeral, recipient.as_bytes()),
+            aad: None,
+            ephemeral_key: Some(ephemeral.to_vec()),
+        })
+    }
+
+    /// Encode as libsodium's sealed box format
+    pub fn to_sealed_box(&self) -> Result<Vec<u8>> {
+        let ephemeral = self
+            .ephemeral_key
+            .as_deref()
+            .ok_or_else(|| CryptoError::InvalidKeyFormat("Missing ephemeral key".into()))?;
+        Ok([ephemeral, &self.ciphertext].concat())
+    }
+}
+
+/// Derive the nonce of a sealed box: BLAKE2b-192 of both public keys
+fn sealed_box_nonce(ephemeral: &[u8], recipient: &[u8]) -> Vec<u8> {
+    Blake2b::<U24>::new()
+        .chain_update(ephemeral)
+        .chain_update(recipient)
+        .finalize()
+        .to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyPair;
+
+    /// RFC 8032 test 1 Ed25519 secret key
+    const SEED: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+
+    fn recipient() -> KeyPair {
+        let seed: [u8; 32] = hex::decode(SEED).unwrap().try_into().unwrap();
+        KeyPair::from_private_key(PrivateKey::from_bytes(&seed).unwrap())
+    }
+
+    // Produced by libsodium's crypto_box_seal to
+    // crypto_sign_ed25519_pk_to_curve25519 of the RFC 8032 test 1 key
+    #[test]
+    fn test_opens_libsodium_sealed_box() {
+        let recipient = recipient();
+        let x25519 = Ed25519Provider::x25519_public_key(recipient.public_key()).unwrap();
+        assert_eq!(
+            hex::encode(x25519.as_bytes()),
+            "d85e07ec22b0ad881537c2f44d662d1a143cf830c57aca4305d85c7a90f6b62e"
+        );
+
+        let sealed = hex::decode(
+            "063eba4e6ded05e2ea2f276647188164c2c3a6220db5953e08003a67724dad7f\
+             dab93cf4b3ca70e533067750989b1af9f3469494dded691591b7003536c2a27b\
+             bc324a2f0faceeeb958abdbc7d809e5629d1fe",
+        )
+        .unwrap();
+        let encrypted = EncryptedData::from_sealed_box(&sealed, recipient.public_key()).unwrap();
+
+        assert_eq!(
+            open_sealed(&encrypted, recipient.private_key()).unwrap(),
+            b"Ship to: 1 Market St, San Francisco"
+        );
+        assert_eq!(encrypted.to_sealed_box().unwrap(), sealed);
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let recipient = recipient();
+        let data = b"Ship to: 1 Market St, San Francisco";
+
+        let encrypted = seal(data, recipient.public_key()).unwrap();
+        let sealed = encrypted.to_sealed_box().unwrap();
+        // Same length as crypto_box_seal: ephemeral key, tag, message
+        assert_eq!(sealed.len(), KEY_LENGTH + TAG_LENGTH + data.len());
+        assert_eq!(
+            open_sealed(&encrypted, recipient.private_key()).unwrap(),
+            data
+        );
+
+        // The ephemeral key is fresh for every message
+        let again = seal(data, recipient.public_key()).unwrap();
+        assert_ne!(again.ephemeral_key, encrypted.ephemeral_key);
+        assert_ne!(again.ciphertext, encrypted.ciphertext);
+    }
+
+    #[test]
+    fn test_only_recipient_can_open() {
+        let recipient = recipient();
+        let encrypted = seal(b"secret", recipient.public_key()).unwrap();
+
+        let other = KeyPair::generate().unwrap();
+        assert!(matches!(
+            open_sealed(&encrypted, other.private_key()),
+            Err(CryptoError::DecryptionError(_))
+        ));
+
+        let mut tampered = encrypted.clone();
+        tampered.ciphertext[TAG_LENGTH] ^= 0x01;
+        assert!(open_sealed(&tampered, recipient.private_key()).is_err());
+
+        let mut swapped = encrypted;
+        swapped.ephemeral_key = Some(
+            KeyPair::generate()
+                .unwrap()
+                .public_key()
+                .to_bytes()
+                .to_vec(),
+        );
+        assert!(open_sealed(&swapped, recipient.private_key()).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! Wrapper type for secret key material.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// Secret bytes that are wiped from memory when dropped
+///
+/// The bytes are redacted from `Debug` output and compared in constant time.
+#[derive(Clone, Default)]
+pub struct SecretBytes(Zeroizing<Vec<u8>>);
+
+impl SecretBytes {
+    /// Wrap the given bytes
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(Zeroizing::new(bytes))
+    }
+
+    /// Copy the given bytes into a new secret
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        Self::new(bytes.to_vec())
+    }
+
+    /// Get the secret bytes
+    pub fn expose_secret(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Get the number of secret bytes
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if the secret is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretBytes([REDACTED])")
+    }
+}
+
+impl ConstantTimeEq for SecretBytes {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.expose_secret().ct_eq(other.expose_secret())
+    }
+}
+
+impl PartialEq for SecretBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SecretBytes {}
+
+impl Serialize for SecretBytes {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.expose_secret().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretBytes {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<u8>::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let secret = SecretBytes::from_slice(&[0x42; 32]);
+        let debug = format!("{:?}", secret);
+        assert_eq!(debug, "SecretBytes([REDACTED])");
+        assert!(!debug.contains("66"));
+    }
+
+    #[test]
+    fn test_equality() {
+        let secret = SecretBytes::from_slice(&[1, 2, 3]);
+        assert_eq!(secret, SecretBytes::from_slice(&[1, 2, 3]));
+        assert_ne!(secret, SecretBytes::from_slice(&[1, 2, 4]));
+        assert_ne!(secret, SecretBytes::from_slice(&[1, 2]));
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let secret = SecretBytes::from_slice(&[1, 2, 3]);
+        let json = serde_json::to_string(&secret).unwrap();
+        assert_eq!(json, "[1,2,3]");
+        assert_eq!(serde_json::from_str::<SecretBytes>(&json).unwrap(), secret);
+    }
+}
@@ -0,0 +1,695 @@
+//! Shamir secret sharing for backing up private keys.
+//!
+//! [`split_key`] splits a key pair's private key into `count` [`KeyShare`]s,
+//! any `threshold` of which recombine into the original key pair with
+//! [`combine_shares`]; fewer reveal nothing about the key. Sharing is done
+//! byte-wise over GF(2^8).
+//!
+//! Each share carries its index, the threshold, the key algorithm and a
+//! fingerprint of the public key, followed by a checksum. Shares are
+//! exchanged as unpadded base64url text.
+
+use std::fmt;
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{CryptoError, KeyAlgorithm, KeyPair, PrivateKey, PublicKey, Result};
+
+/// Current version of the share encoding
+pub const SHARE_VERSION: u8 = 1;
+
+/// Length of the public key fingerprint in a share
+const FINGERPRINT_LENGTH: usize = 8;
+
+/// Number of share subsets tried when there are too many corrupted shares
+/// to locate them directly
+const MAX_RECOVERY_ATTEMPTS: usize = 1024;
+
+/// Length of the checksum at the end of an encoded share
+const CHECKSUM_LENGTH: usize = 4;
+
+/// Length of the fields before the share value:
+/// version, algorithm, threshold, index and fingerprint
+const HEADER_LENGTH: usize = 4 + FINGERPRINT_LENGTH;
+
+/// One share of a private key
+#[derive(Clone, PartialEq, Eq)]
+pub struct KeyShare {
+    algorithm: KeyAlgorithm,
+    threshold: u8,
+    index: u8,
+    fingerprint: [u8; FINGERPRINT_LENGTH],
+    value: Zeroizing<Vec<u8>>,
+}
+
+impl KeyShare {
+    /// Get the algorithm of the shared key
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        self.algorithm
+    }
+
+    /// Get the number of shares needed to recover the key
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// Get the identifier of this share, between 1 and 255
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Get the fingerprint of the public key this share belongs to
+    pub fn fingerprint(&self) -> &[u8] {
+        &self.fingerprint
+    }
+
+    /// Encode this share as bytes, ending in a checksum
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut bytes = Zeroizing::new(Vec::with_capacity(
+            HEADER_LENGTH + self.value.len() + CHECKSUM_LENGTH,
+        ));
+        bytes.extend_from_slice(&[
+            SHARE_VERSION,
+            algorithm_code(self.algorithm),
+            self.threshold,
+            self.index,
+        ]);
+        bytes.extend_from_slice(&self.fingerprint);
+        bytes.extend_from_slice(&self.value);
+        let checksum = checksum(&bytes);
+        bytes.extend_from_slice(&checksum);
+        bytes
+    }
+
+    /// Decode a share, failing with [`CryptoError::CorruptedShare`] if the
+    /// checksum does not match
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() <= HEADER_LENGTH + CHECKSUM_LENGTH {
+            return Err(CryptoError::CorruptedShare("Share is too short".into()));
+        }
+        let (body, expected) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+        if checksum(body) != expected {
+            return Err(CryptoError::CorruptedShare("Checksum mismatch".into()));
+        }
+        if body[0] != SHARE_VERSION {
+            return Err(CryptoError::SecretSharingError(format!(
+                "Unsupported share version: {}",
+                body[0]
+            )));
+        }
+
+        let share = Self {
+            algorithm: algorithm_from_code(body[1])?,
+            threshold: body[2],
+            index: body[3],
+            fingerprint: body[4..HEADER_LENGTH]
+                .try_into()
+                .expect("header length is checked above"),
+            value: Zeroizing::new(body[HEADER_LENGTH..].to_vec()),
+        };
+        if share.threshold == 0 || share.index == 0 {
+            return Err(CryptoError::CorruptedShare(
+                "Threshold and index must not be zero".into(),
+            ));
+        }
+        Ok(share)
+    }
+}
+
+impl fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("algorithm", &self.algorithm)
+            .field("threshold", &self.threshold)
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Writes the share as unpadded base64url text
+impl fmt::Display for KeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&URL_SAFE_NO_PAD.encode(self.to_bytes().as_slice()))
+    }
+}
+
+impl FromStr for KeyShare {
+    type Err = CryptoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let bytes = Zeroizing::new(
+            URL_SAFE_NO_PAD
+                .decode(s.trim())
+                .map_err(|e| CryptoError::CorruptedShare(e.to_string()))?,
+        );
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Split a key pair's private key into `count` shares, any `threshold` of
+/// which recover it
+pub fn split_key(key_pair: &KeyPair, threshold: u8, count: u8) -> Result<Vec<KeyShare>> {
+    if threshold == 0 || threshold > count {
+        return Err(CryptoError::SecretSharingError(format!(
+            "Threshold must be between 1 and {}, got {}",
+            count, threshold
+        )));
+    }
+    if !key_pair.is_valid() {
+        return Err(CryptoError::SecretSharingError(
+            "Key pair is not valid".into(),
+        ));
+    }
+
+    let secret = key_pair.private_key().to_bytes();
+    // One random polynomial per secret byte, with the byte as constant term
+    let mut coefficients = Zeroizing::new(vec![0u8; secret.len() * (threshold as usize - 1)]);
+    OsRng.fill_bytes(&mut coefficients);
+
+    let fingerprint = fingerprint(key_pair.public_key());
+    Ok((1..=count)
+        .map(|x| {
+            let value = secret
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| {
+                    let chunk = threshold as usize - 1;
+                    let terms = &coefficients[i * chunk..(i + 1) * chunk];
+                    // Horner's rule, highest coefficient first
+                    let acc = terms
+                        .iter()
+                        .rev()
+                        .fold(0u8, |acc, coefficient| gf_mul(acc, x) ^ coefficient);
+                    gf_mul(acc, x) ^ byte
+                })
+                .collect();
+
+            KeyShare {
+                algorithm: key_pair.algorithm(),
+                threshold,
+                index: x,
+                fingerprint,
+                value: Zeroizing::new(value),
+            }
+        })
+        .collect())
+}
+
+/// Recover a key pair from at least `threshold` of its shares
+///
+/// Shares that do not lie on the same polynomial as the others are reported
+/// with [`CryptoError::CorruptedShares`], listing their indexes. Up to half
+/// of the shares beyond the threshold can always be identified; past that a
+/// bounded number of subsets is tried. The list is empty if the corrupted
+/// shares cannot be told apart.
+pub fn combine_shares(shares: &[KeyShare]) -> Result<KeyPair> {
+    let first = shares
+        .first()
+        .ok_or_else(|| CryptoError::SecretSharingError("No shares given".into()))?;
+    for share in shares {
+        if share.algorithm != first.algorithm
+            || share.threshold != first.threshold
+            || share.fingerprint != first.fingerprint
+            || share.value.len() != first.value.len()
+        {
+            return Err(CryptoError::SecretSharingError(format!(
+                "Share {} belongs to a different key",
+                share.index
+            )));
+        }
+        if shares.iter().filter(|s| s.index == share.index).count() > 1 {
+            return Err(CryptoError::SecretSharingError(format!(
+                "Share {} is given more than once",
+                share.index
+            )));
+        }
+    }
+
+    let threshold = first.threshold as usize;
+    if shares.len() < threshold {
+        return Err(CryptoError::SecretSharingError(format!(
+            "{} shares are required, got {}",
+            threshold,
+            shares.len()
+        )));
+    }
+
+    // Usually every share is intact
+    let leading: Vec<&KeyShare> = shares[..threshold].iter().collect();
+    if let Some(key_pair) = recover(&leading) {
+        if shares.iter().all(|share| lies_on(share, &leading)) {
+            return Ok(key_pair);
+        }
+    }
+
+    // Locate the corrupted shares as errors in a Reed-Solomon code
+    if let Some(corrupted) = find_corrupted(shares, threshold) {
+        let intact: Vec<&KeyShare> = shares
+            .iter()
+            .filter(|share| !corrupted.contains(&share.index))
+            .take(threshold)
+            .collect();
+        if !corrupted.is_empty() && recover(&intact).is_some() {
+            return Err(CryptoError::CorruptedShares(corrupted));
+        }
+    }
+
+    // Past the correction capacity, look for a subset reproducing the key
+    let mut subset: Vec<usize> = (0..threshold).collect();
+    for _ in 0..MAX_RECOVERY_ATTEMPTS {
+        let chosen: Vec<&KeyShare> = subset.iter().map(|i| &shares[*i]).collect();
+        if recover(&chosen).is_some() {
+            let corrupted: Vec<u8> = shares
+                .iter()
+                .filter(|share| !lies_on(share, &chosen))
+                .map(|share| share.index)
+                .collect();
+            return Err(CryptoError::CorruptedShares(corrupted));
+        }
+        if !next_combination(&mut subset, shares.len()) {
+            break;
+        }
+    }
+
+    Err(CryptoError::CorruptedShares(Vec::new()))
+}
+
+/// Find the indexes of the shares that do not lie on a common polynomial
+///
+/// Each byte position is decoded with the Berlekamp-Welch algorithm, which
+/// corrects up to half the number of shares beyond the threshold. Returns
+/// `None` if the shares have more errors than that.
+fn find_corrupted(shares: &[KeyShare], threshold: usize) -> Option<Vec<u8>> {
+    let max_errors = (shares.len() - threshold) / 2;
+    let mut corrupted = vec![false; shares.len()];
+    for i in 0..shares[0].value.len() {
+        let points: Vec<(u8, u8)> = shares
+            .iter()
+            .map(|share| (share.index, share.value[i]))
+            .collect();
+        let polynomial = berlekamp_welch(&points, threshold, max_errors)?;
+        for (flag, (x, y)) in corrupted.iter_mut().zip(&points) {
+            *flag |= evaluate(&polynomial, *x) != *y;
+        }
+    }
+
+    let indexes: Vec<u8> = shares
+        .iter()
+        .zip(&corrupted)
+        .filter(|(_, corrupted)| **corrupted)
+        .map(|(share, _)| share.index)
+        .collect();
+    (indexes.len() <= max_errors).then_some(indexes)
+}
+
+/// Find the polynomial of degree below `k` through all but at most `errors`
+/// of the points, as coefficients from the constant term up
+fn berlekamp_welch(points: &[(u8, u8)], k: usize, errors: usize) -> Option<Vec<u8>> {
+    // Solve Q(x) = y E(x) for Q of degree below errors + k and monic E of
+    // degree errors; the unknowns are Q's coefficients, then E's
+    let q_len = errors + k;
+    let unknowns = q_len + errors;
+    let rows = points
+        .iter()
+        .map(|(x, y)| {
+            let powers: Vec<u8> = std::iter::successors(Some(1u8), |p| Some(gf_mul(*p, *x)))
+                .take(q_len + 1)
+                .collect();
+            let mut row = powers[..q_len].to_vec();
+            row.extend(powers[..errors].iter().map(|p| gf_mul(*p, *y)));
+            row.push(gf_mul(powers[errors], *y));
+            row
+        })
+        .collect();
+    let solution = solve(rows, unknowns)?;
+
+    let mut locator = solution[q_len..].to_vec();
+    locator.push(1);
+    divide_monic(&solution[..q_len], &locator)
+}
+
+/// Solve a linear system over GF(2^8) given as rows of coefficients followed
+/// by the right-hand side, setting free unknowns to zero
+fn solve(mut rows: Vec<Vec<u8>>, unknowns: usize) -> Option<Vec<u8>> {
+    let mut pivots = Vec::new();
+    for column in 0..unknowns {
+        let rank = pivots.len();
+        let Some(found) = (rank..rows.len()).find(|r| rows[*r][column] != 0) else {
+            continue;
+        };
+        rows.swap(rank, found);
+        let inverse = gf_inv(rows[rank][column]);
+        let pivot: Vec<u8> = rows[rank].iter().map(|v| gf_mul(*v, inverse)).collect();
+        for row in rows.iter_mut() {
+            let factor = row[column];
+            if factor != 0 {
+                for (value, p) in row.iter_mut().zip(&pivot) {
+                    *value ^= gf_mul(factor, *p);
+                }
+            }
+        }
+        rows[rank] = pivot;
+        pivots.push(column);
+    }
+
+    if rows[pivots.len()..].iter().any(|row| row[unknowns] != 0) {
+        return None;
+    }
+    let mut solution = vec![0u8; unknowns];
+    for (row, column) in pivots.iter().enumerate() {
+        solution[*column] = rows[row][unknowns];
+    }
+    Some(solution)
+}
+
+/// Divide a polynomial by a monic one, returning `None` if it does not divide
+fn divide_monic(numerator: &[u8], divisor: &[u8]) -> Option<Vec<u8>> {
+    let degree = divisor.len() - 1;
+    let mut remainder = numerator.to_vec();
+    let mut quotient = vec![0u8; numerator.len().saturating_sub(degree)];
+    for i in (0..quotient.len()).rev() {
+        let coefficient = remainder[i + degree];
+        quotient[i] = coefficient;
+        for (value, d) in remainder[i..].iter_mut().zip(divisor) {
+            *value ^= gf_mul(coefficient, *d);
+        }
+    }
+    remainder.iter().all(|c| *c == 0).then_some(quotient)
+}
+
+/// Evaluate a polynomial at `x`
+fn evaluate(polynomial: &[u8], x: u8) -> u8 {
+    polynomial
+        .iter()
+        .rev()
+        .fold(0u8, |acc, coefficient| gf_mul(acc, x) ^ coefficient)
+}
+
+/// Interpolate the secret from exactly `threshold` shares
+///
+/// Returns `None` if the result does not match the shares' fingerprint.
+fn recover(shares: &[&KeyShare]) -> Option<KeyPair> {
+    let first = shares[0];
+    let secret = interpolate(shares, 0);
+
+    let Ok(private_key) = PrivateKey::from_algorithm_bytes(first.algorithm, &secret) else {
+        return None;
+    };
+    let key_pair = KeyPair::from_private_key(private_key);
+    (fingerprint(key_pair.public_key()) == first.fingerprint).then_some(key_pair)
+}
+
+/// Check if a share lies on the polynomial through the given shares
+fn lies_on(share: &KeyShare, shares: &[&KeyShare]) -> bool {
+    *interpolate(shares, share.index) == *share.value
+}
+
+/// Evaluate at `x` the polynomials through the shares' bytes at every position
+fn interpolate(shares: &[&KeyShare], x: u8) -> Zeroizing<Vec<u8>> {
+    // The Lagrange basis only depends on the indexes, not on the position
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1u8, |basis, other| {
+                    gf_mul(
+                        basis,
+                        gf_mul(x ^ other.index, gf_inv(share.index ^ other.index)),
+                    )
+                })
+        })
+        .collect();
+
+    Zeroizing::new(
+        (0..shares[0].value.len())
+            .map(|i| {
+                shares.iter().zip(&basis).fold(0u8, |acc, (share, basis)| {
+                    acc ^ gf_mul(share.value[i], *basis)
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Advance to the next `k`-combination of `0..n` in lexicographic order
+fn next_combination(subset: &mut [usize], n: usize) -> bool {
+    let k = subset.len();
+    for i in (0..k).rev() {
+        if subset[i] < n - k + i {
+            subset[i] += 1;
+            for j in i + 1..k {
+                subset[j] = subset[j - 1] + 1;
+            }
+            return true;
+        }
+    }
+    false
+}
+
+/// Multiply in GF(2^8) with the AES polynomial x^8 + x^4 + x^3 + x + 1
+///
+/// Runs in constant time with respect to the operands.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Invert a non-zero element of GF(2^8), as a^254
+fn gf_inv(a: u8) -> u8 {
+    let a2 = gf_mul(a, a);
+    let a4 = gf_mul(a2, a2);
+    let a8 = gf_mul(a4, a4);
+    let a16 = gf_mul(a8, a8);
+    let a32 = gf_mul(a16, a16);
+    let a64 = gf_mul(a32, a32);
+    let a128 = gf_mul(a64, a64);
+    // 254 = 128 + 64 + 32 + 16 + 8 + 4 + 2
+    [a64, a32, a16, a8, a4, a2]
+        .iter()
+        .fold(a128, |acc, power| gf_mul(acc, *power))
+}
+
+/// Get the fingerprint of a public key stored in its shares
+fn fingerprint(public_key: &PublicKey) -> [u8; FINGERPRINT_LENGTH] {
+    let digest = Sha256::digest(public_key.to_bytes());
+    digest[..FINGERPRINT_LENGTH]
+        .try_into()
+        .expect("digest is longer than the fingerprint")
+}
+
+/// Get the checksum of an encoded share body
+fn checksum(body: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let digest = Sha256::digest(body);
+    digest[..CHECKSUM_LENGTH]
+        .try_into()
+        .expect("digest is longer than the checksum")
+}
+
+/// Get the byte identifying an algorithm in encoded shares
+fn algorithm_code(algorithm: KeyAlgorithm) -> u8 {
+    match algorithm {
+        KeyAlgorithm::Ed25519 => 0,
+        KeyAlgorithm::P256 => 1,
+        KeyAlgorithm::Secp256k1 => 2,
+    }
+}
+
+/// Get the algorithm identified by a byte in an encoded share
+fn algorithm_from_code(code: u8) -> Result<KeyAlgorithm> {
+    match code {
+        0 => Ok(KeyAlgorithm::Ed25519),
+        1 => Ok(KeyAlgorithm::P256),
+        2 => Ok(KeyAlgorithm::Secp256k1),
+        _ => Err(CryptoError::UnsupportedAlgorithm(format!(
+            "Unknown share algorithm: {}",
+            code
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Call `f` with every subset of `0..n`, as a list of indexes
+    fn for_each_subset(n: usize, mut f: impl FnMut(&[usize])) {
+        for mask in 0u32..(1 << n) {
+            let subset: Vec<usize> = (0..n).filter(|i| mask & (1 << i) != 0).collect();
+            f(&subset);
+        }
+    }
+
+    #[test]
+    fn test_gf_arithmetic() {
+        // FIPS-197 section 4.2 example
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1, "inverse of {}", a);
+        }
+    }
+
+    #[test]
+    fn test_every_threshold_combination() {
+        let key_pair = KeyPair::generate().unwrap();
+
+        for count in 1..=5u8 {
+            for threshold in 1..=count {
+                let shares = split_key(&key_pair, threshold, count).unwrap();
+                assert_eq!(shares.len(), count as usize);
+
+                for_each_subset(count as usize, |subset| {
+                    let chosen: Vec<KeyShare> = subset.iter().map(|i| shares[*i].clone()).collect();
+                    let result = combine_shares(&chosen);
+
+                    if chosen.len() >= threshold as usize {
+                        let recovered = result.unwrap_or_else(|e| {
+                            panic!("{} of {} with {:?}: {}", threshold, count, subset, e)
+                        });
+                        assert_eq!(recovered.private_key(), key_pair.private_key());
+                        assert_eq!(recovered.public_key(), key_pair.public_key());
+                    } else {
+                        assert!(
+                            result.is_err(),
+                            "{} of {} with {:?}",
+                            threshold,
+                            count,
+                            subset
+                        );
+                    }
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn test_shares_roundtrip_as_text() {
+        let key_pair = KeyPair::generate().unwrap();
+        let shares = split_key(&key_pair, 2, 3).unwrap();
+
+        let decoded: Vec<KeyShare> = shares
+            .iter()
+            .map(|share| share.to_string().parse().unwrap())
+            .collect();
+        assert_eq!(decoded, shares);
+        assert_eq!(decoded[1].index(), 2);
+        assert_eq!(decoded[1].threshold(), 2);
+        assert_eq!(
+            combine_shares(&decoded[1..]).unwrap().public_key(),
+            key_pair.public_key()
+        );
+    }
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        let key_pair = KeyPair::generate().unwrap();
+        let share = &split_key(&key_pair, 2, 3).unwrap()[0];
+
+        let bytes = share.to_bytes();
+        for position in 0..bytes.len() {
+            let mut corrupted = bytes.to_vec();
+            corrupted[position] ^= 0x01;
+            assert!(
+                matches!(
+                    KeyShare::from_bytes(&corrupted),
+                    Err(CryptoError::CorruptedShare(_))
+                ),
+                "flip at {}",
+                position
+            );
+        }
+        assert!("not a share".parse::<KeyShare>().is_err());
+    }
+
+    #[test]
+    fn test_corrupted_share_is_identified() {
+        let key_pair = KeyPair::generate().unwrap();
+        let mut shares = split_key(&key_pair, 2, 4).unwrap();
+        // A share altered before its checksum was computed
+        shares[1].value[0] ^= 0x80;
+
+        match combine_shares(&shares) {
+            Err(CryptoError::CorruptedShares(indexes)) => assert_eq!(indexes, vec![2]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Without the corrupted share the key is recovered
+        shares.remove(1);
+        assert_eq!(
+            combine_shares(&shares).unwrap().public_key(),
+            key_pair.public_key()
+        );
+
+        // With only the threshold and one bad share, nothing can be recovered
+        let mut pair = split_key(&key_pair, 2, 2).unwrap();
+        pair[0].value[5] ^= 0x01;
+        assert!(matches!(
+            combine_shares(&pair),
+            Err(CryptoError::CorruptedShares(_))
+        ));
+    }
+
+    #[test]
+    fn test_many_shares_with_early_corruption() {
+        let key_pair = KeyPair::generate().unwrap();
+        let mut shares = split_key(&key_pair, 15, 30).unwrap();
+        for share in &mut shares[..7] {
+            share.value[3] ^= 0x5a;
+        }
+
+        match combine_shares(&shares) {
+            Err(CryptoError::CorruptedShares(indexes)) => {
+                assert_eq!(indexes, (1..=7).collect::<Vec<u8>>())
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Too many corrupted shares to locate ends the search quickly
+        for share in &mut shares[7..12] {
+            share.value[0] ^= 0x01;
+        }
+        assert!(matches!(
+            combine_shares(&shares),
+            Err(CryptoError::CorruptedShares(_))
+        ));
+
+        assert_eq!(
+            combine_shares(&shares[12..]).unwrap().private_key(),
+            key_pair.private_key()
+        );
+    }
+
+    #[test]
+    fn test_rejects_mixed_and_duplicate_shares() {
+        let first = split_key(&KeyPair::generate().unwrap(), 2, 3).unwrap();
+        let second = split_key(&KeyPair::generate().unwrap(), 2, 3).unwrap();
+
+        assert!(matches!(
+            combine_shares(&[first[0].clone(), second[1].clone()]),
+            Err(CryptoError::SecretSharingError(_))
+        ));
+        assert!(matches!(
+            combine_shares(&[first[0].clone(), first[0].clone()]),
+            Err(CryptoError::SecretSharingError(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_parameters() {
+        let key_pair = KeyPair::generate().unwrap();
+        assert!(split_key(&key_pair, 0, 3).is_err());
+        assert!(split_key(&key_pair, 4, 3).is_err());
+        assert_eq!(split_key(&key_pair, 255, 255).unwrap().len(), 255);
+    }
+}
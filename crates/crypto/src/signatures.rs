@@ -1,57 +1,249 @@
-use ed25519_dalek::{Signature as Ed25519Signature, Signer, Verifier};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_dalek::Signature as Ed25519Signature;
 use serde::{Deserialize, Serialize};
 
+use crate::{CryptoError, KeyAlgorithm, PublicKey};
+
+/// A signature to check in a batch, with the message and key it belongs to
+pub type BatchItem<'a> = (&'a [u8], &'a Signature, &'a PublicKey);
+
 /// A digital signature
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Ed25519 signatures serialize as a bare `signature_bytes` array; other
+/// algorithms add an `algorithm` field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Signature {
+    /// The algorithm that produced this signature
+    #[serde(default, skip_serializing_if = "KeyAlgorithm::is_ed25519")]
+    pub algorithm: KeyAlgorithm,
     /// The raw signature bytes
     pub signature_bytes: Vec<u8>,
-    /// The Ed25519 signature
-    #[serde(skip)]
-    pub ed25519_signature: Option<Ed25519Signature>,
 }
 
 impl Signature {
-    /// Create a new signature from bytes
+    /// Create a new Ed25519 signature from bytes
     pub fn from_bytes(bytes: &[u8]) -> crate::Result<Self> {
-        let bytes: [u8; 64] = bytes
-            .try_into()
-            .map_err(|_| crate::CryptoError::InvalidSignature("Invalid signature length".into()))?;
-        let ed25519_signature = Ed25519Signature::from_bytes(&bytes);
+        Self::from_algorithm_bytes(KeyAlgorithm::Ed25519, bytes)
+    }
+
+    /// Create a new signature for the given algorithm from bytes
+    ///
+    /// All supported algorithms use 64-byte signatures (`r || s` for ECDSA).
+    pub fn from_algorithm_bytes(algorithm: KeyAlgorithm, bytes: &[u8]) -> crate::Result<Self> {
+        if bytes.len() != 64 {
+            return Err(CryptoError::InvalidSignature(
+                "Invalid signature length".into(),
+            ));
+        }
 
         Ok(Self {
+            algorithm,
             signature_bytes: bytes.to_vec(),
-            ed25519_signature: Some(ed25519_signature),
         })
     }
 
+    /// Get the algorithm that produced this signature
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        self.algorithm
+    }
+
     /// Get the raw signature bytes
     pub fn as_bytes(&self) -> &[u8] {
         &self.signature_bytes
     }
 
     /// Verify this signature against a message and public key
-    pub fn verify(&self, message: &[u8], public_key: &crate::PublicKey) -> crate::Result<bool> {
-        let signature = self.ed25519_signature.as_ref().ok_or_else(|| {
-            crate::CryptoError::InvalidSignature("Signature not initialized".into())
-        })?;
+    pub fn verify(&self, message: &[u8], public_key: &PublicKey) -> crate::Result<bool> {
+        if self.algorithm != public_key.algorithm() {
+            return Err(CryptoError::InvalidSignature(format!(
+                "{} signature cannot be verified with a {} key",
+                self.algorithm,
+                public_key.algorithm()
+            )));
+        }
+
+        if public_key
+            .verifying_key
+            .verify(message, &self.signature_bytes)?
+        {
+            Ok(true)
+        } else {
+            Err(CryptoError::InvalidSignature(
+                "Signature verification failed".into(),
+            ))
+        }
+    }
 
-        let verifying_key = public_key.verifying_key.as_ref().ok_or_else(|| {
-            crate::CryptoError::InvalidKeyFormat("Public key not initialized".into())
-        })?;
+    /// Verify many signatures at once
+    ///
+    /// Ed25519 signatures are checked together with batch verification, which
+    /// is considerably faster than checking them one by one. If the batch
+    /// fails, each item is checked individually so that the error reports
+    /// exactly which indexes are invalid. Other algorithms, and Ed25519 items
+    /// with a small-order key or `R` component that strict verification
+    /// would reject, are always checked individually.
+    pub fn verify_batch(items: &[BatchItem<'_>]) -> crate::Result<()> {
+        let mut failed = Vec::new();
+        let mut batch = Vec::with_capacity(items.len());
 
-        verifying_key
-            .verify(message, signature)
-            .map(|_| true)
-            .map_err(|e| crate::CryptoError::InvalidSignature(e.to_string()))
+        for (index, (message, signature, public_key)) in items.iter().enumerate() {
+            match Self::ed25519_batch_entry(signature, public_key) {
+                Some(entry) => batch.push((index, *message, entry.0, entry.1)),
+                None => {
+                    if !matches!(signature.verify(message, public_key), Ok(true)) {
+                        failed.push(index);
+                    }
+                }
+            }
+        }
+
+        let messages: Vec<&[u8]> = batch.iter().map(|(_, message, _, _)| *message).collect();
+        let signatures: Vec<Ed25519Signature> = batch.iter().map(|(_, _, sig, _)| *sig).collect();
+        let keys: Vec<ed25519_dalek::VerifyingKey> =
+            batch.iter().map(|(_, _, _, key)| *key).collect();
+
+        if ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_err() {
+            for (index, message, signature, key) in &batch {
+                if key.verify_strict(message, signature).is_err() {
+                    failed.push(*index);
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            failed.sort_unstable();
+            Err(CryptoError::BatchVerificationFailed(failed))
+        }
+    }
+
+    /// Get the Ed25519 signature and key of a batch item, if it can take
+    /// part in Ed25519 batch verification
+    ///
+    /// Batch verification uses the cofactored equation, so items that only
+    /// pass because of a small-order component are left out of the batch.
+    fn ed25519_batch_entry(
+        signature: &Signature,
+        public_key: &PublicKey,
+    ) -> Option<(Ed25519Signature, ed25519_dalek::VerifyingKey)> {
+        if !signature.algorithm.is_ed25519() || !public_key.algorithm().is_ed25519() {
+            return None;
+        }
+        let bytes: [u8; 64] = signature.signature_bytes.as_slice().try_into().ok()?;
+        let key = public_key.ed25519_verifying_key().ok()?;
+        let r = CompressedEdwardsY::from_slice(&bytes[..32])
+            .ok()?
+            .decompress()?;
+        if key.is_weak() || r.is_small_order() {
+            return None;
+        }
+        Some((Ed25519Signature::from_bytes(&bytes), *key))
     }
 }
 
 impl From<Ed25519Signature> for Signature {
     fn from(sig: Ed25519Signature) -> Self {
         Self {
+            algorithm: KeyAlgorithm::Ed25519,
             signature_bytes: sig.to_bytes().to_vec(),
-            ed25519_signature: Some(sig),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyPair;
+
+    fn signed_batch(count: usize) -> Vec<(Vec<u8>, Signature, PublicKey)> {
+        (0..count)
+            .map(|i| {
+                let key_pair = KeyPair::generate().unwrap();
+                let message = format!("attestation {}", i).into_bytes();
+                let signature = key_pair.private_key().sign(&message).unwrap();
+                (message, signature, key_pair.public_key().clone())
+            })
+            .collect()
+    }
+
+    fn as_items(batch: &[(Vec<u8>, Signature, PublicKey)]) -> Vec<BatchItem<'_>> {
+        batch
+            .iter()
+            .map(|(message, signature, public_key)| (message.as_slice(), signature, public_key))
+            .collect()
+    }
+
+    #[test]
+    fn test_verify_batch_accepts_valid_signatures() {
+        let batch = signed_batch(16);
+        assert!(Signature::verify_batch(&as_items(&batch)).is_ok());
+        assert!(Signature::verify_batch(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_verify_batch_reports_failing_indexes() {
+        let mut batch = signed_batch(8);
+        batch[2].0 = b"tampered".to_vec();
+        batch[5].1.signature_bytes[0] ^= 0x01;
+        batch[7].2 = KeyPair::generate().unwrap().public_key().clone();
+
+        match Signature::verify_batch(&as_items(&batch)) {
+            Err(CryptoError::BatchVerificationFailed(failed)) => {
+                assert_eq!(failed, vec![2, 5, 7])
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_verify_batch_reports_malformed_signatures() {
+        let mut batch = signed_batch(3);
+        batch[1].1.signature_bytes.truncate(10);
+
+        match Signature::verify_batch(&as_items(&batch)) {
+            Err(CryptoError::BatchVerificationFailed(failed)) => assert_eq!(failed, vec![1]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_small_order_signatures_are_rejected() {
+        // With the identity as public key and R, s = 0 satisfies the
+        // verification equation for every message
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        let public_key = PublicKey::from_bytes(&identity).unwrap();
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&identity);
+        let signature = Signature::from_bytes(&bytes).unwrap();
+
+        assert!(signature.verify(b"anything", &public_key).is_err());
+
+        let mut batch = signed_batch(3);
+        batch.push((b"anything".to_vec(), signature, public_key));
+        match Signature::verify_batch(&as_items(&batch)) {
+            Err(CryptoError::BatchVerificationFailed(failed)) => assert_eq!(failed, vec![3]),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "p256")]
+    #[test]
+    fn test_high_s_ecdsa_signatures_are_rejected() {
+        let key_pair = KeyPair::generate_with_algorithm(KeyAlgorithm::P256).unwrap();
+        let signature = key_pair.private_key().sign(b"message").unwrap();
+        assert!(signature.verify(b"message", key_pair.public_key()).unwrap());
+
+        // Replacing s with n - s yields a signature that plain ECDSA accepts
+        let ecdsa = p256::ecdsa::Signature::from_slice(signature.as_bytes()).unwrap();
+        let (r, s) = ecdsa.split_scalars();
+        let flipped = p256::ecdsa::Signature::from_scalars(r, -s).unwrap();
+        let flipped =
+            Signature::from_algorithm_bytes(KeyAlgorithm::P256, &flipped.to_bytes()).unwrap();
+
+        assert!(!flipped
+            .verify(b"message", key_pair.public_key())
+            .unwrap_or(false));
+    }
+}
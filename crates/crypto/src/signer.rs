@@ -0,0 +1,471 @@
+//! Signing abstraction that does not require the private key in memory.
+//!
+//! [`Signer`] is implemented by [`InMemorySigner`], which wraps a
+//! [`KeyPair`], and by [`RemoteSigner`], which forwards requests to a
+//! separate signing daemon over a Unix socket or a child process's stdio.
+//!
+//! The daemon protocol is newline-delimited JSON: each line written by the
+//! client is a [`SignerRequest`] and the daemon answers each one with a single
+//! [`SignerResponse`] line. [`serve_signer`] implements the daemon side on top
+//! of any [`Signer`].
+
+use std::process::Stdio;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+
+use crate::{CryptoError, KeyPair, PublicKey, Result, Signature};
+
+/// Produces signatures with a key that may live outside the process
+///
+/// Signers sign raw bytes: they are the primitive underneath
+/// [`SigningContext::sign_with`](crate::SigningContext::sign_with), which
+/// binds the bytes to a context first, and underneath `eddsa-jcs-2022` Data
+/// Integrity proofs, whose signed hash is fixed by the specification and
+/// cannot carry a context prefix. Application code should sign through a
+/// [`SigningContext`](crate::SigningContext) rather than call
+/// [`Signer::sign`] directly.
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// Sign a message as given, without a signing context
+    async fn sign(&self, message: &[u8]) -> Result<Signature>;
+
+    /// Get the public key matching the signing key
+    async fn public_key(&self) -> Result<PublicKey>;
+
+    /// Get the identifier of the signing key
+    async fn key_id(&self) -> Result<String> {
+        Ok(self.public_key().await?.key_id())
+    }
+}
+
+/// Signer backed by a key pair held in process memory
+#[derive(Debug, Clone)]
+pub struct InMemorySigner {
+    key_pair: KeyPair,
+    key_id: String,
+}
+
+impl InMemorySigner {
+    /// Create a new signer for the given key pair
+    pub fn new(key_pair: KeyPair) -> Self {
+        Self {
+            key_id: key_pair.public_key().key_id(),
+            key_pair,
+        }
+    }
+
+    /// Get the wrapped key pair
+    pub fn key_pair(&self) -> &KeyPair {
+        &self.key_pair
+    }
+}
+
+impl From<KeyPair> for InMemorySigner {
+    fn from(key_pair: KeyPair) -> Self {
+        Self::new(key_pair)
+    }
+}
+
+#[async_trait]
+impl Signer for InMemorySigner {
+    async fn sign(&self, message: &[u8]) -> Result<Signature> {
+        self.key_pair.private_key().sign(message)
+    }
+
+    async fn public_key(&self) -> Result<PublicKey> {
+        Ok(self.key_pair.public_key().clone())
+    }
+
+    async fn key_id(&self) -> Result<String> {
+        Ok(self.key_id.clone())
+    }
+}
+
+/// A request sent to a signing daemon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SignerRequest {
+    /// Ask for the daemon's public key
+    PublicKey,
+    /// Ask the daemon to sign a base64-encoded message
+    Sign { message: String },
+}
+
+/// A response returned by a signing daemon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignerResponse {
+    /// The daemon's public key
+    PublicKey(PublicKey),
+    /// A signature over the requested message
+    Signature(Signature),
+    /// The request failed
+    Error(String),
+}
+
+type BoxedReader = BufReader<Box<dyn AsyncRead + Send + Unpin>>;
+type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// An open connection to a signing daemon
+struct Connection {
+    reader: BoxedReader,
+    writer: BoxedWriter,
+}
+
+impl Connection {
+    /// Send a request and wait for its response
+    async fn call(&mut self, request: &SignerRequest) -> Result<SignerResponse> {
+        let mut line = serde_json::to_vec(request)
+            .map_err(|e| CryptoError::SignerError(format!("Failed to encode request: {}", e)))?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await.map_err(io_error)?;
+        self.writer.flush().await.map_err(io_error)?;
+
+        let mut response = String::new();
+        if self
+            .reader
+            .read_line(&mut response)
+            .await
+            .map_err(io_error)?
+            == 0
+        {
+            return Err(CryptoError::SignerError(
+                "Signing daemon closed the connection".into(),
+            ));
+        }
+
+        match serde_json::from_str(&response) {
+            Ok(SignerResponse::Error(message)) => Err(CryptoError::SignerError(message)),
+            Ok(response) => Ok(response),
+            Err(e) => Err(CryptoError::SignerError(format!(
+                "Invalid response from signing daemon: {}",
+                e
+            ))),
+        }
+    }
+}
+
+/// Map an I/O failure on the daemon connection to [`CryptoError::SignerError`]
+fn io_error(e: std::io::Error) -> CryptoError {
+    CryptoError::SignerError(format!("Signing daemon I/O failed: {}", e))
+}
+
+/// Signer that forwards requests to a separate signing daemon
+///
+/// The public key is fetched once when connecting. Every signature returned
+/// by the daemon is checked against it before being handed out, so a
+/// misbehaving daemon cannot produce signatures for a different key.
+pub struct RemoteSigner {
+    connection: Mutex<Connection>,
+    public_key: PublicKey,
+    key_id: String,
+    _child: Option<Child>,
+}
+
+impl RemoteSigner {
+    /// Connect to a signing daemon listening on a Unix socket
+    #[cfg(unix)]
+    pub async fn connect_unix(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let stream = tokio::net::UnixStream::connect(path)
+            .await
+            .map_err(io_error)?;
+        let (reader, writer) = stream.into_split();
+        Self::from_stream(reader, writer).await
+    }
+
+    /// Spawn a signing daemon and talk to it over its stdin and stdout
+    ///
+    /// The child process is killed when the signer is dropped.
+    pub async fn spawn(mut command: Command) -> Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(io_error)?;
+
+        let (Some(writer), Some(reader)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(CryptoError::SignerError(
+                "Signing daemon stdio is not available".into(),
+            ));
+        };
+
+        let mut signer = Self::from_stream(reader, writer).await?;
+        signer._child = Some(child);
+        Ok(signer)
+    }
+
+    /// Talk to a signing daemon over an already established byte stream
+    pub async fn from_stream<R, W>(reader: R, writer: W) -> Result<Self>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let mut connection = Connection {
+            reader: BufReader::new(Box::new(reader)),
+            writer: Box::new(writer),
+        };
+
+        let public_key = match connection.call(&SignerRequest::PublicKey).await? {
+            SignerResponse::PublicKey(public_key) => public_key,
+            _ => {
+                return Err(CryptoError::SignerError(
+                    "Unexpected response to public key request".into(),
+                ))
+            }
+        };
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            key_id: public_key.key_id(),
+            public_key,
+            _child: None,
+        })
+    }
+}
+
+impl std::fmt::Debug for RemoteSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteSigner")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    async fn sign(&self, message: &[u8]) -> Result<Signature> {
+        let request = SignerRequest::Sign {
+            message: STANDARD.encode(message),
+        };
+        let signature = match self.connection.lock().await.call(&request).await? {
+            SignerResponse::Signature(signature) => signature,
+            _ => {
+                return Err(CryptoError::SignerError(
+                    "Unexpected response to sign request".into(),
+                ))
+            }
+        };
+
+        signature.verify(message, &self.public_key)?;
+        Ok(signature)
+    }
+
+    async fn public_key(&self) -> Result<PublicKey> {
+        Ok(self.public_key.clone())
+    }
+
+    async fn key_id(&self) -> Result<String> {
+        Ok(self.key_id.clone())
+    }
+}
+
+/// Serve signing requests from a single client until it disconnects
+///
+/// This is the daemon side of [`RemoteSigner`]. Failed requests are answered
+/// with [`SignerResponse::Error`] and do not end the session.
+pub async fn serve_signer<S, R, W>(signer: &S, reader: R, mut writer: W) -> Result<()>
+where
+    S: Signer + ?Sized,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await.map_err(io_error)? {
+        let response = match serde_json::from_str::<SignerRequest>(&line) {
+            Ok(SignerRequest::PublicKey) => {
+                signer.public_key().await.map(SignerResponse::PublicKey)
+            }
+            Ok(SignerRequest::Sign { message }) => match STANDARD.decode(message) {
+                Ok(message) => signer.sign(&message).await.map(SignerResponse::Signature),
+                Err(e) => Err(CryptoError::SignerError(format!(
+                    "Invalid message encoding: {}",
+                    e
+                ))),
+            },
+            Err(e) => Err(CryptoError::SignerError(format!("Invalid request: {}", e))),
+        }
+        .unwrap_or_else(|e| SignerResponse::Error(e.to_string()));
+
+        let mut line = serde_json::to_vec(&response)
+            .map_err(|e| CryptoError::SignerError(format!("Failed to encode response: {}", e)))?;
+        line.push(b'\n');
+        writer.write_all(&line).await.map_err(io_error)?;
+        writer.flush().await.map_err(io_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start a stand-in daemon on one end of an in-process pipe
+    async fn connect_to_daemon(key_pair: KeyPair) -> RemoteSigner {
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let daemon = InMemorySigner::new(key_pair);
+            let (reader, writer) = tokio::io::split(server);
+            serve_signer(&daemon, reader, writer).await.unwrap();
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        RemoteSigner::from_stream(reader, writer).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_signer() {
+        let key_pair = KeyPair::generate().unwrap();
+        let signer = InMemorySigner::new(key_pair.clone());
+
+        assert_eq!(
+            signer.key_id().await.unwrap(),
+            key_pair.public_key().key_id()
+        );
+        let signature = signer.sign(b"message").await.unwrap();
+        assert!(signature
+            .verify(b"message", &signer.public_key().await.unwrap())
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_over_stream() {
+        let key_pair = KeyPair::generate().unwrap();
+        let signer = connect_to_daemon(key_pair.clone()).await;
+
+        assert_eq!(&signer.public_key().await.unwrap(), key_pair.public_key());
+        assert_eq!(
+            signer.key_id().await.unwrap(),
+            key_pair.public_key().key_id()
+        );
+        for message in [&b"first"[..], b"second", b""] {
+            let signature = signer.sign(message).await.unwrap();
+            assert!(signature.verify(message, key_pair.public_key()).unwrap());
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_remote_signer_over_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("signer.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+
+        let key_pair = KeyPair::generate().unwrap();
+        let daemon = InMemorySigner::new(key_pair.clone());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, writer) = stream.into_split();
+            serve_signer(&daemon, reader, writer).await.unwrap();
+        });
+
+        let signer = RemoteSigner::connect_unix(&path).await.unwrap();
+        let signature = signer.sign(b"message").await.unwrap();
+        assert!(signature.verify(b"message", key_pair.public_key()).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_remote_signer_over_child_process() {
+        // A stand-in daemon that answers with precomputed responses
+        let key_pair = KeyPair::generate().unwrap();
+        let responses = [
+            SignerResponse::PublicKey(key_pair.public_key().clone()),
+            SignerResponse::Signature(key_pair.private_key().sign(b"message").unwrap()),
+        ]
+        .map(|response| serde_json::to_string(&response).unwrap());
+        let script = format!(
+            "read request; echo '{}'; read request; echo '{}'",
+            responses[0], responses[1]
+        );
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        let signer = RemoteSigner::spawn(command).await.unwrap();
+        assert_eq!(&signer.public_key().await.unwrap(), key_pair.public_key());
+        let signature = signer.sign(b"message").await.unwrap();
+        assert!(signature.verify(b"message", key_pair.public_key()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_rejects_foreign_signature() {
+        // A daemon that advertises one key but signs with another
+        let advertised = KeyPair::generate().unwrap();
+        let actual = KeyPair::generate().unwrap();
+
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let (reader, mut writer) = tokio::io::split(server);
+            let mut lines = BufReader::new(reader).lines();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let response = match serde_json::from_str(&line).unwrap() {
+                    SignerRequest::PublicKey => {
+                        SignerResponse::PublicKey(advertised.public_key().clone())
+                    }
+                    SignerRequest::Sign { message } => SignerResponse::Signature(
+                        actual
+                            .private_key()
+                            .sign(&STANDARD.decode(message).unwrap())
+                            .unwrap(),
+                    ),
+                };
+                let mut line = serde_json::to_vec(&response).unwrap();
+                line.push(b'\n');
+                writer.write_all(&line).await.unwrap();
+            }
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let signer = RemoteSigner::from_stream(reader, writer).await.unwrap();
+        assert!(matches!(
+            signer.sign(b"message").await,
+            Err(CryptoError::InvalidSignature(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_daemon_errors_are_reported() {
+        let (client, server) = tokio::io::duplex(4096);
+        let key_pair = KeyPair::generate().unwrap();
+        tokio::spawn(async move {
+            let daemon = InMemorySigner::new(key_pair);
+            let (reader, writer) = tokio::io::split(server);
+            serve_signer(&daemon, reader, writer).await.unwrap();
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        let mut connection = Connection {
+            reader: BufReader::new(Box::new(reader)),
+            writer: Box::new(writer),
+        };
+        let request = SignerRequest::Sign {
+            message: "not base64!".into(),
+        };
+        assert!(matches!(
+            connection.call(&request).await,
+            Err(CryptoError::SignerError(_))
+        ));
+
+        // The session survives a failed request
+        assert!(matches!(
+            connection.call(&SignerRequest::PublicKey).await,
+            Ok(SignerResponse::PublicKey(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_daemon_disconnect_is_an_error() {
+        let (client, server) = tokio::io::duplex(4096);
+        drop(server);
+        let (reader, writer) = tokio::io::split(client);
+        assert!(matches!(
+            RemoteSigner::from_stream(reader, writer).await,
+            Err(CryptoError::SignerError(_))
+        ));
+    }
+}
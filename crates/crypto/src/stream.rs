@@ -0,0 +1,533 @@
+//! Chunked streaming encryption (STREAM construction).
+//!
+//! Large payloads are split into fixed-size chunks that are sealed one at a
+//! time with ChaCha20-Poly1305, so memory use is bounded by the chunk size
+//! regardless of the payload size. The stream starts with a header carrying
+//! the format version, the chunk size, a salt for the per-stream key and a
+//! nonce prefix. Each chunk nonce is the prefix, a big-endian chunk counter
+//! and a flag marking the final chunk:
+//!
+//! ```text
+//! nonce = prefix (7 bytes) || counter (4 bytes) || last (1 byte)
+//! ```
+//!
+//! Reordered chunks fail authentication because of the counter, and a
+//! stream that ends without a chunk flagged as final is reported as
+//! truncated. Plaintext is written out as each chunk is authenticated, so a
+//! failed decryption means everything written so far must be discarded.
+
+use std::io::{ErrorKind, Read, Write};
+
+use hkdf::Hkdf;
+use rand::{rngs::OsRng, RngCore};
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use sha2::Sha256;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use zeroize::Zeroizing;
+
+use crate::{CryptoError, EncryptionKey, Result};
+
+/// Version byte of the stream format
+pub const STREAM_VERSION: u8 = 1;
+
+/// Default plaintext chunk size
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Largest chunk size accepted when encrypting or decrypting
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// HKDF info used to derive the per-stream key
+const STREAM_KDF_INFO: &[u8] = b"agentid/stream-chacha20poly1305/v1";
+
+const SALT_LEN: usize = 16;
+const NONCE_PREFIX_LEN: usize = 7;
+const HEADER_LEN: usize = 1 + 4 + SALT_LEN + NONCE_PREFIX_LEN;
+const TAG_LEN: usize = 16;
+
+/// Encrypts and decrypts payloads as a sequence of authenticated chunks
+#[derive(Debug, Clone)]
+pub struct StreamCipher {
+    key: EncryptionKey,
+    chunk_size: usize,
+}
+
+impl StreamCipher {
+    /// Create a new stream cipher using the given key
+    pub fn new(key: &EncryptionKey) -> Self {
+        Self {
+            key: key.clone(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// Set the plaintext chunk size used when encrypting
+    ///
+    /// Decryption always uses the chunk size recorded in the stream header.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Get the plaintext chunk size used when encrypting
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Encrypt everything read from `reader` into `writer`
+    ///
+    /// Returns the number of plaintext bytes encrypted.
+    pub fn encrypt<R: Read, W: Write>(&self, mut reader: R, mut writer: W) -> Result<u64> {
+        let (header, mut state) = self.start_encryption()?;
+        writer.write_all(&header).map_err(encryption_io)?;
+
+        let block = self.chunk_size;
+        let mut buf = vec![0u8; block + 1];
+        let mut filled = read_full(&mut reader, &mut buf).map_err(encryption_io)?;
+        let mut total = 0u64;
+        loop {
+            let last = filled <= block;
+            let len = filled.min(block);
+            let tag = state.seal(&mut buf[..len], last)?;
+            writer.write_all(&buf[..len]).map_err(encryption_io)?;
+            writer.write_all(tag.as_ref()).map_err(encryption_io)?;
+            total += len as u64;
+            if last {
+                break;
+            }
+
+            // Carry over the look-ahead byte that told us this was not the end
+            buf[0] = buf[block];
+            filled = 1 + read_full(&mut reader, &mut buf[1..]).map_err(encryption_io)?;
+        }
+
+        writer.flush().map_err(encryption_io)?;
+        Ok(total)
+    }
+
+    /// Decrypt a stream read from `reader` into `writer`
+    ///
+    /// Returns the number of plaintext bytes written.
+    pub fn decrypt<R: Read, W: Write>(&self, mut reader: R, mut writer: W) -> Result<u64> {
+        let mut header = [0u8; HEADER_LEN];
+        if read_full(&mut reader, &mut header).map_err(decryption_io)? < HEADER_LEN {
+            return Err(truncated());
+        }
+        let (chunk_size, mut state) = self.start_decryption(&header)?;
+
+        let block = chunk_size + TAG_LEN;
+        let mut buf = vec![0u8; block + 1];
+        let mut filled = read_full(&mut reader, &mut buf).map_err(decryption_io)?;
+        let mut total = 0u64;
+        loop {
+            let last = filled <= block;
+            let plaintext = state.open(&mut buf[..filled.min(block)], last)?;
+            writer.write_all(plaintext).map_err(decryption_io)?;
+            total += plaintext.len() as u64;
+            if last {
+                break;
+            }
+
+            buf[0] = buf[block];
+            filled = 1 + read_full(&mut reader, &mut buf[1..]).map_err(decryption_io)?;
+        }
+
+        writer.flush().map_err(decryption_io)?;
+        Ok(total)
+    }
+
+    /// Encrypt everything read from an async `reader` into `writer`
+    ///
+    /// Returns the number of plaintext bytes encrypted.
+    pub async fn encrypt_async<R, W>(&self, mut reader: R, mut writer: W) -> Result<u64>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let (header, mut state) = self.start_encryption()?;
+        writer.write_all(&header).await.map_err(encryption_io)?;
+
+        let block = self.chunk_size;
+        let mut buf = vec![0u8; block + 1];
+        let mut filled = read_full_async(&mut reader, &mut buf)
+            .await
+            .map_err(encryption_io)?;
+        let mut total = 0u64;
+        loop {
+            let last = filled <= block;
+            let len = filled.min(block);
+            let tag = state.seal(&mut buf[..len], last)?;
+            writer.write_all(&buf[..len]).await.map_err(encryption_io)?;
+            writer
+                .write_all(tag.as_ref())
+                .await
+                .map_err(encryption_io)?;
+            total += len as u64;
+            if last {
+                break;
+            }
+
+            buf[0] = buf[block];
+            filled = 1 + read_full_async(&mut reader, &mut buf[1..])
+                .await
+                .map_err(encryption_io)?;
+        }
+
+        writer.flush().await.map_err(encryption_io)?;
+        Ok(total)
+    }
+
+    /// Decrypt a stream read from an async `reader` into `writer`
+    ///
+    /// Returns the number of plaintext bytes written.
+    pub async fn decrypt_async<R, W>(&self, mut reader: R, mut writer: W) -> Result<u64>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut header = [0u8; HEADER_LEN];
+        if read_full_async(&mut reader, &mut header)
+            .await
+            .map_err(decryption_io)?
+            < HEADER_LEN
+        {
+            return Err(truncated());
+        }
+        let (chunk_size, mut state) = self.start_decryption(&header)?;
+
+        let block = chunk_size + TAG_LEN;
+        let mut buf = vec![0u8; block + 1];
+        let mut filled = read_full_async(&mut reader, &mut buf)
+            .await
+            .map_err(decryption_io)?;
+        let mut total = 0u64;
+        loop {
+            let last = filled <= block;
+            let plaintext = state.open(&mut buf[..filled.min(block)], last)?;
+            writer.write_all(plaintext).await.map_err(decryption_io)?;
+            total += plaintext.len() as u64;
+            if last {
+                break;
+            }
+
+            buf[0] = buf[block];
+            filled = 1 + read_full_async(&mut reader, &mut buf[1..])
+                .await
+                .map_err(decryption_io)?;
+        }
+
+        writer.flush().await.map_err(decryption_io)?;
+        Ok(total)
+    }
+
+    /// Build a fresh header and the matching chunk state
+    fn start_encryption(&self) -> Result<([u8; HEADER_LEN], ChunkState)> {
+        if self.chunk_size == 0 || self.chunk_size > MAX_CHUNK_SIZE {
+            return Err(CryptoError::EncryptionError(format!(
+                "Chunk size must be between 1 and {} bytes",
+                MAX_CHUNK_SIZE
+            )));
+        }
+
+        let mut header = [0u8; HEADER_LEN];
+        header[0] = STREAM_VERSION;
+        header[1..5].copy_from_slice(&(self.chunk_size as u32).to_be_bytes());
+        OsRng.fill_bytes(&mut header[5..]);
+
+        let state = ChunkState::new(&self.key, header)?;
+        Ok((header, state))
+    }
+
+    /// Parse a header, returning its chunk size and the matching chunk state
+    fn start_decryption(&self, header: &[u8; HEADER_LEN]) -> Result<(usize, ChunkState)> {
+        if header[0] != STREAM_VERSION {
+            return Err(CryptoError::DecryptionError(format!(
+                "Unsupported stream version: {}",
+                header[0]
+            )));
+        }
+
+        let chunk_size = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(CryptoError::DecryptionError(format!(
+                "Invalid stream chunk size: {}",
+                chunk_size
+            )));
+        }
+
+        Ok((chunk_size, ChunkState::new(&self.key, *header)?))
+    }
+}
+
+/// Per-stream key, header and chunk counter
+struct ChunkState {
+    key: LessSafeKey,
+    header: [u8; HEADER_LEN],
+    counter: u32,
+    finished: bool,
+}
+
+impl ChunkState {
+    /// Derive the per-stream key from the long-term key and the header salt
+    fn new(key: &EncryptionKey, header: [u8; HEADER_LEN]) -> Result<Self> {
+        let salt = &header[5..5 + SALT_LEN];
+        let mut stream_key = Zeroizing::new([0u8; 32]);
+        Hkdf::<Sha256>::new(Some(salt), key.as_bytes())
+            .expand(STREAM_KDF_INFO, stream_key.as_mut())
+            .map_err(|e| CryptoError::InternalError(e.to_string()))?;
+
+        let key = UnboundKey::new(&aead::CHACHA20_POLY1305, stream_key.as_ref())
+            .map_err(|e| CryptoError::InternalError(e.to_string()))?;
+
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            header,
+            counter: 0,
+            finished: false,
+        })
+    }
+
+    /// Get the nonce of the next chunk and advance the counter
+    fn next_nonce(&mut self, last: bool) -> Result<Nonce> {
+        if self.finished {
+            return Err(CryptoError::InternalError(
+                "Stream is already finished".into(),
+            ));
+        }
+
+        let mut nonce = [0u8; 12];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(&self.header[HEADER_LEN - NONCE_PREFIX_LEN..]);
+        nonce[7..11].copy_from_slice(&self.counter.to_be_bytes());
+        nonce[11] = last as u8;
+
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| CryptoError::InternalError("Stream has too many chunks".into()))?;
+        self.finished = last;
+        Ok(Nonce::assume_unique_for_key(nonce))
+    }
+
+    /// Seal a plaintext chunk in place, returning its tag
+    fn seal(&mut self, chunk: &mut [u8], last: bool) -> Result<aead::Tag> {
+        let nonce = self.next_nonce(last)?;
+        self.key
+            .seal_in_place_separate_tag(nonce, Aad::from(&self.header), chunk)
+            .map_err(|e| CryptoError::EncryptionError(e.to_string()))
+    }
+
+    /// Open a ciphertext chunk in place, returning the plaintext
+    fn open<'a>(&mut self, chunk: &'a mut [u8], last: bool) -> Result<&'a [u8]> {
+        if chunk.len() < TAG_LEN {
+            return Err(truncated());
+        }
+
+        let nonce = self.next_nonce(last)?;
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(&self.header), chunk)
+            .map_err(|_| {
+                CryptoError::DecryptionError(if last {
+                    "Stream chunk failed authentication or stream is truncated".into()
+                } else {
+                    "Stream chunk failed authentication".into()
+                })
+            })?;
+        Ok(plaintext)
+    }
+}
+
+/// Read until `buf` is full or the reader is exhausted
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Read until `buf` is full or the async reader is exhausted
+async fn read_full_async<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut [u8],
+) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+fn truncated() -> CryptoError {
+    CryptoError::DecryptionError("Stream is truncated".into())
+}
+
+fn encryption_io(e: std::io::Error) -> CryptoError {
+    CryptoError::EncryptionError(format!("Stream I/O failed: {}", e))
+}
+
+fn decryption_io(e: std::io::Error) -> CryptoError {
+    CryptoError::DecryptionError(format!("Stream I/O failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHUNK: usize = 64;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn encrypt(cipher: &StreamCipher, plaintext: &[u8]) -> Vec<u8> {
+        let mut ciphertext = Vec::new();
+        cipher.encrypt(plaintext, &mut ciphertext).unwrap();
+        ciphertext
+    }
+
+    fn decrypt(cipher: &StreamCipher, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let mut plaintext = Vec::new();
+        cipher.decrypt(ciphertext, &mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    #[test]
+    fn test_roundtrip_at_chunk_boundaries() {
+        let key = EncryptionKey::generate().unwrap();
+        let cipher = StreamCipher::new(&key).with_chunk_size(CHUNK);
+
+        for len in [0, 1, CHUNK - 1, CHUNK, CHUNK + 1, 3 * CHUNK, 3 * CHUNK + 7] {
+            let plaintext = payload(len);
+            let ciphertext = encrypt(&cipher, &plaintext);
+            let chunks = len.div_ceil(CHUNK).max(1);
+            assert_eq!(ciphertext.len(), HEADER_LEN + len + chunks * TAG_LEN);
+            assert_eq!(decrypt(&cipher, &ciphertext).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_uses_header_chunk_size() {
+        let key = EncryptionKey::generate().unwrap();
+        let plaintext = payload(1000);
+        let ciphertext = encrypt(&StreamCipher::new(&key).with_chunk_size(100), &plaintext);
+
+        assert_eq!(
+            decrypt(&StreamCipher::new(&key), &ciphertext).unwrap(),
+            plaintext
+        );
+    }
+
+    #[test]
+    fn test_detects_truncation() {
+        let key = EncryptionKey::generate().unwrap();
+        let cipher = StreamCipher::new(&key).with_chunk_size(CHUNK);
+        let ciphertext = encrypt(&cipher, &payload(3 * CHUNK + 10));
+        let sealed_chunk = CHUNK + TAG_LEN;
+
+        // Dropping the final chunk, cutting inside a chunk and dropping
+        // everything after the header must all fail
+        for len in [
+            HEADER_LEN + 3 * sealed_chunk,
+            HEADER_LEN + 2 * sealed_chunk + 5,
+            HEADER_LEN,
+            HEADER_LEN - 1,
+        ] {
+            assert!(matches!(
+                decrypt(&cipher, &ciphertext[..len]),
+                Err(CryptoError::DecryptionError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_detects_reordering_and_trailing_data() {
+        let key = EncryptionKey::generate().unwrap();
+        let cipher = StreamCipher::new(&key).with_chunk_size(CHUNK);
+        let ciphertext = encrypt(&cipher, &payload(3 * CHUNK + 10));
+        let sealed_chunk = CHUNK + TAG_LEN;
+
+        let mut reordered = ciphertext.clone();
+        let (first, second) = (HEADER_LEN, HEADER_LEN + sealed_chunk);
+        let chunk = reordered[first..second].to_vec();
+        reordered.copy_within(second..second + sealed_chunk, first);
+        reordered[second..second + sealed_chunk].copy_from_slice(&chunk);
+        assert!(decrypt(&cipher, &reordered).is_err());
+
+        let mut extended = ciphertext.clone();
+        extended.extend_from_slice(&ciphertext[HEADER_LEN..HEADER_LEN + sealed_chunk]);
+        assert!(decrypt(&cipher, &extended).is_err());
+    }
+
+    #[test]
+    fn test_rejects_tampering_and_wrong_key() {
+        let key = EncryptionKey::generate().unwrap();
+        let cipher = StreamCipher::new(&key).with_chunk_size(CHUNK);
+        let ciphertext = encrypt(&cipher, &payload(2 * CHUNK));
+
+        let mut tampered = ciphertext.clone();
+        tampered[HEADER_LEN + 3] ^= 0x01;
+        assert!(decrypt(&cipher, &tampered).is_err());
+
+        // Header fields are authenticated with every chunk
+        let mut tampered = ciphertext.clone();
+        tampered[HEADER_LEN - 1] ^= 0x01;
+        assert!(decrypt(&cipher, &tampered).is_err());
+
+        let mut tampered = ciphertext.clone();
+        tampered[1..5].copy_from_slice(&(u32::MAX).to_be_bytes());
+        assert!(decrypt(&cipher, &tampered).is_err());
+
+        let other = StreamCipher::new(&EncryptionKey::generate().unwrap());
+        assert!(decrypt(&other, &ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_chunk_size() {
+        let key = EncryptionKey::generate().unwrap();
+        for chunk_size in [0, MAX_CHUNK_SIZE + 1] {
+            let cipher = StreamCipher::new(&key).with_chunk_size(chunk_size);
+            assert!(matches!(
+                cipher.encrypt(&b"data"[..], Vec::new()),
+                Err(CryptoError::EncryptionError(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_roundtrip() {
+        let key = EncryptionKey::generate().unwrap();
+        let cipher = StreamCipher::new(&key).with_chunk_size(CHUNK);
+        let plaintext = payload(5 * CHUNK + 3);
+
+        let mut ciphertext = Vec::new();
+        let written = cipher
+            .encrypt_async(plaintext.as_slice(), &mut ciphertext)
+            .await
+            .unwrap();
+        assert_eq!(written, plaintext.len() as u64);
+
+        // Async and sync streams share the same format
+        assert_eq!(decrypt(&cipher, &ciphertext).unwrap(), plaintext);
+
+        let mut decrypted = Vec::new();
+        cipher
+            .decrypt_async(ciphertext.as_slice(), &mut decrypted)
+            .await
+            .unwrap();
+        assert_eq!(decrypted, plaintext);
+
+        let truncated = &ciphertext[..ciphertext.len() - TAG_LEN - 3];
+        assert!(cipher
+            .decrypt_async(truncated, &mut Vec::new())
+            .await
+            .is_err());
+    }
+}
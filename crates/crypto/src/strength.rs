@@ -0,0 +1,261 @@
+//! Key validity and strength checks.
+//!
+//! [`KeyStrengthReport`] lists every [`KeyIssue`] found in a key instead of
+//! a bare yes/no answer, so callers can tell a user why a key was rejected.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use curve25519_dalek::edwards::CompressedEdwardsY;
+
+use crate::{KeyAlgorithm, KeyPair, PublicKey};
+
+/// Secrets with fewer distinct byte values than this are considered low entropy
+///
+/// 32 uniformly random bytes contain about 25 distinct values; fewer than 8
+/// happens with negligible probability.
+const MIN_DISTINCT_SECRET_BYTES: usize = 8;
+
+/// A reason a key fails validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyIssue {
+    /// Support for the key's algorithm is not compiled in
+    AlgorithmDisabled(KeyAlgorithm),
+    /// The public key does not belong to the private key
+    MismatchedKeyPair,
+    /// The public key is a point of small order, for which signatures
+    /// prove nothing
+    SmallOrderPublicKey,
+    /// The public key is not the canonical encoding of its point
+    NonCanonicalPublicKey,
+    /// The secret key is all zeros
+    ZeroSecret,
+    /// The secret key is too regular to have been generated randomly
+    LowEntropySecret,
+    /// The algorithm provides fewer bits of security than required
+    InsufficientStrength {
+        /// Bits of security provided by the algorithm
+        security_bits: u32,
+        /// Bits of security required
+        required_bits: u32,
+    },
+}
+
+impl fmt::Display for KeyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlgorithmDisabled(algorithm) => {
+                write!(f, "{} support is not enabled", algorithm)
+            }
+            Self::MismatchedKeyPair => write!(f, "public key does not match private key"),
+            Self::SmallOrderPublicKey => write!(f, "public key is a small-order point"),
+            Self::NonCanonicalPublicKey => write!(f, "public key is not canonically encoded"),
+            Self::ZeroSecret => write!(f, "secret key is all zeros"),
+            Self::LowEntropySecret => write!(f, "secret key has low entropy"),
+            Self::InsufficientStrength {
+                security_bits,
+                required_bits,
+            } => write!(
+                f,
+                "{} bits of security, {} required",
+                security_bits, required_bits
+            ),
+        }
+    }
+}
+
+/// The outcome of validating a key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyStrengthReport {
+    /// The algorithm of the key
+    pub algorithm: KeyAlgorithm,
+    /// Bits of security provided by the algorithm
+    pub security_bits: u32,
+    /// Bits of security required
+    pub required_bits: u32,
+    /// Every problem found, empty if the key is acceptable
+    pub issues: Vec<KeyIssue>,
+}
+
+impl KeyStrengthReport {
+    /// Validate a key pair, including its secret key
+    pub fn for_key_pair(key_pair: &KeyPair, required_bits: u32) -> Self {
+        let mut report = Self::for_public_key(key_pair.public_key(), required_bits);
+        if !key_pair.is_valid() {
+            report.issues.push(KeyIssue::MismatchedKeyPair);
+        }
+        if let Some(issue) = secret_issue(key_pair.private_key().to_bytes()) {
+            report.issues.push(issue);
+        }
+        report
+    }
+
+    /// Validate a public key
+    pub fn for_public_key(public_key: &PublicKey, required_bits: u32) -> Self {
+        let algorithm = public_key.algorithm();
+        let security_bits = algorithm.security_bits();
+        let mut issues = Vec::new();
+
+        if !algorithm.is_enabled() {
+            issues.push(KeyIssue::AlgorithmDisabled(algorithm));
+        }
+        if security_bits < required_bits {
+            issues.push(KeyIssue::InsufficientStrength {
+                security_bits,
+                required_bits,
+            });
+        }
+        if let Ok(key) = public_key.ed25519_verifying_key() {
+            if key.is_weak() {
+                issues.push(KeyIssue::SmallOrderPublicKey);
+            }
+            let encoded = CompressedEdwardsY(key.to_bytes());
+            if encoded.decompress().map(|point| point.compress()) != Some(encoded) {
+                issues.push(KeyIssue::NonCanonicalPublicKey);
+            }
+        }
+
+        Self {
+            algorithm,
+            security_bits,
+            required_bits,
+            issues,
+        }
+    }
+
+    /// Check if no issues were found
+    pub fn is_acceptable(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for KeyStrengthReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.issues.is_empty() {
+            return write!(f, "{} key is acceptable", self.algorithm);
+        }
+        write!(f, "{} key rejected: ", self.algorithm)?;
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check a secret key for values that cannot have come from a random generator
+fn secret_issue(secret: &[u8]) -> Option<KeyIssue> {
+    if secret.iter().all(|byte| *byte == 0) {
+        return Some(KeyIssue::ZeroSecret);
+    }
+
+    let distinct: HashSet<u8> = secret.iter().copied().collect();
+    let step = secret
+        .get(1)
+        .zip(secret.first())
+        .map(|(b, a)| b.wrapping_sub(*a));
+    let arithmetic = secret
+        .windows(2)
+        .all(|pair| Some(pair[1].wrapping_sub(pair[0])) == step);
+
+    if distinct.len() < MIN_DISTINCT_SECRET_BYTES || arithmetic {
+        Some(KeyIssue::LowEntropySecret)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PrivateKey;
+
+    /// The field prime 2^255 - 19, little-endian, plus `offset`
+    fn prime_plus(offset: u8) -> [u8; 32] {
+        let mut bytes = [0xff; 32];
+        bytes[0] = 0xed + offset;
+        bytes[31] = 0x7f;
+        bytes
+    }
+
+    #[test]
+    fn test_generated_key_is_acceptable() {
+        let report = KeyStrengthReport::for_key_pair(&KeyPair::generate().unwrap(), 128);
+        assert!(report.is_acceptable(), "{}", report);
+        assert_eq!(report.security_bits, 128);
+    }
+
+    #[test]
+    fn test_small_order_public_key_is_rejected() {
+        // The identity point, y = 1
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        let public_key = PublicKey::from_bytes(&identity).unwrap();
+
+        let report = KeyStrengthReport::for_public_key(&public_key, 128);
+        assert!(report.issues.contains(&KeyIssue::SmallOrderPublicKey));
+        assert!(!report.is_acceptable());
+    }
+
+    #[test]
+    fn test_non_canonical_public_key_is_rejected() {
+        // y = p + k encodes the same point as y = k; find one that decodes
+        // to a point of large order
+        let public_key = (2..19)
+            .find_map(|offset| PublicKey::from_bytes(&prime_plus(offset)).ok())
+            .expect("a non-canonical encoding of a valid point");
+
+        let report = KeyStrengthReport::for_public_key(&public_key, 128);
+        assert_eq!(report.issues, vec![KeyIssue::NonCanonicalPublicKey]);
+    }
+
+    #[test]
+    fn test_weak_secrets_are_rejected() {
+        let zero = KeyPair::from_private_key(PrivateKey::from_bytes(&[0u8; 32]).unwrap());
+        assert!(KeyStrengthReport::for_key_pair(&zero, 128)
+            .issues
+            .contains(&KeyIssue::ZeroSecret));
+
+        let repeated = KeyPair::from_private_key(PrivateKey::from_bytes(&[7u8; 32]).unwrap());
+        let counting: Vec<u8> = (0..32).collect();
+        let counting = KeyPair::from_private_key(
+            PrivateKey::from_bytes(&counting.try_into().unwrap()).unwrap(),
+        );
+        for key_pair in [repeated, counting] {
+            assert_eq!(
+                KeyStrengthReport::for_key_pair(&key_pair, 128).issues,
+                vec![KeyIssue::LowEntropySecret]
+            );
+        }
+    }
+
+    #[test]
+    fn test_min_strength_is_enforced() {
+        let report = KeyStrengthReport::for_key_pair(&KeyPair::generate().unwrap(), 192);
+        assert_eq!(
+            report.issues,
+            vec![KeyIssue::InsufficientStrength {
+                security_bits: 128,
+                required_bits: 192,
+            }]
+        );
+        assert_eq!(
+            report.to_string(),
+            "Ed25519 key rejected: 128 bits of security, 192 required"
+        );
+    }
+
+    #[test]
+    fn test_mismatched_key_pair_is_rejected() {
+        let first = KeyPair::generate().unwrap();
+        let second = KeyPair::generate().unwrap();
+        let mismatched = KeyPair::new(first.public_key().clone(), second.private_key().clone());
+
+        assert_eq!(
+            KeyStrengthReport::for_key_pair(&mismatched, 128).issues,
+            vec![KeyIssue::MismatchedKeyPair]
+        );
+    }
+}
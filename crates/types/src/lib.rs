@@ -3,6 +3,7 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
@@ -10,11 +11,119 @@ use thiserror::Error;
 use uuid::Uuid;
 
 // AgentId and related types
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+
+/// Prefix of the canonical string form of a [`KeyDigest`]
+pub const KEY_DIGEST_PREFIX: &str = "agentid:";
+
+/// Multihash code of SHA2-256
+const MULTIHASH_SHA2_256: u8 = 0x12;
+
+/// Length of a SHA2-256 digest
+const SHA2_256_LENGTH: u8 = 32;
+
+/// A multihash (SHA2-256) of an agent's inception public key
+///
+/// The canonical string form is `agentid:` followed by the multihash in
+/// multibase base58btc, e.g. `agentid:zQm...`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyDigest([u8; SHA2_256_LENGTH as usize]);
+
+impl KeyDigest {
+    /// Hash the raw bytes of a public key
+    pub fn of_public_key(public_key: &[u8]) -> Self {
+        Self(Sha256::digest(public_key).into())
+    }
+
+    /// Check if this is the digest of the given raw public key
+    pub fn matches(&self, public_key: &[u8]) -> bool {
+        *self == Self::of_public_key(public_key)
+    }
+
+    /// Get the multihash bytes: hash code, digest length and digest
+    pub fn to_multihash(&self) -> Vec<u8> {
+        [&[MULTIHASH_SHA2_256, SHA2_256_LENGTH][..], &self.0].concat()
+    }
+
+    /// Parse multihash bytes
+    pub fn from_multihash(bytes: &[u8]) -> Result<Self> {
+        match bytes {
+            [MULTIHASH_SHA2_256, SHA2_256_LENGTH, digest @ ..] => digest
+                .try_into()
+                .map(Self)
+                .map_err(|_| AgentError::InvalidId("Invalid SHA2-256 digest length".into())),
+            _ => Err(AgentError::InvalidId(
+                "Key digest is not a SHA2-256 multihash".into(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for KeyDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}z{}",
+            KEY_DIGEST_PREFIX,
+            bs58::encode(self.to_multihash()).into_string()
+        )
+    }
+}
+
+impl FromStr for KeyDigest {
+    type Err = AgentError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let encoded = s
+            .strip_prefix(KEY_DIGEST_PREFIX)
+            .and_then(|multibase| multibase.strip_prefix('z'))
+            .ok_or_else(|| AgentError::InvalidId(format!("Not a key digest: {}", s)))?;
+        let bytes = bs58::decode(encoded)
+            .into_vec()
+            .map_err(|e| AgentError::InvalidId(format!("Invalid base58btc key digest: {}", e)))?;
+        Self::from_multihash(&bytes)
+    }
+}
+
+impl Serialize for KeyDigest {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyDigest {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Identifies an agent
+///
+/// IDs created with [`AgentId::new`] are random. IDs created with
+/// [`AgentId::from_public_key`] are self-certifying: they carry the
+/// [`KeyDigest`] of the agent's inception key, so anyone holding the key can
+/// compute the same ID and check that a presented key belongs to it.
+/// Self-certifying IDs are equal when their digests are, whatever their
+/// names and creation times.
+///
+/// The digest is only set by [`AgentId::from_key_digest`], which derives
+/// the UUID from it, and by deserialization, which fails unless the UUID is
+/// the derived one. Equal IDs therefore have the same [`AgentId::id`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "AgentIdFields")]
 pub struct AgentId {
     pub id: Uuid,
     pub name: String,
     pub created_at: DateTime<Utc>,
+    /// Digest of the inception key of a self-certifying ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_digest: Option<KeyDigest>,
 }
 
 impl AgentId {
@@ -23,11 +132,63 @@ impl AgentId {
             id: Uuid::new_v4(),
             name: name.into(),
             created_at: Utc::now(),
+            key_digest: None,
+        }
+    }
+
+    /// Create a self-certifying ID from the raw bytes of the inception public key
+    ///
+    /// The UUID is derived from the key digest, so it is the same on every host.
+    pub fn from_public_key(name: impl Into<String>, public_key: &[u8]) -> Self {
+        Self::from_key_digest(name, KeyDigest::of_public_key(public_key))
+    }
+
+    /// Create a self-certifying ID from the digest of its inception key
+    pub fn from_key_digest(name: impl Into<String>, key_digest: KeyDigest) -> Self {
+        Self {
+            id: Self::uuid_of(&key_digest),
+            name: name.into(),
+            created_at: Utc::now(),
+            key_digest: Some(key_digest),
+        }
+    }
+
+    /// Get the digest of the inception key of a self-certifying ID
+    pub fn key_digest(&self) -> Option<&KeyDigest> {
+        self.key_digest.as_ref()
+    }
+
+    /// Check if this ID is derived from an inception key
+    pub fn is_self_certifying(&self) -> bool {
+        self.key_digest.is_some()
+    }
+
+    /// Check that a presented raw public key is the inception key of this ID
+    ///
+    /// Random IDs are not bound to any key and always fail.
+    pub fn verify_key(&self, public_key: &[u8]) -> Result<()> {
+        match &self.key_digest {
+            Some(digest) if digest.matches(public_key) => Ok(()),
+            Some(_) => Err(AgentError::InvalidId(format!(
+                "Key does not match agent ID {}",
+                self
+            ))),
+            None => Err(AgentError::InvalidId(format!(
+                "Agent ID {} is not self-certifying",
+                self
+            ))),
         }
     }
     pub fn id(&self) -> Uuid {
         self.id
     }
+
+    /// Derive the UUID of a self-certifying ID from its key digest
+    fn uuid_of(key_digest: &KeyDigest) -> Uuid {
+        let mut uuid_bytes = [0u8; 16];
+        uuid_bytes.copy_from_slice(&key_digest.0[..16]);
+        uuid::Builder::from_custom_bytes(uuid_bytes).into_uuid()
+    }
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -36,9 +197,89 @@ impl AgentId {
     }
 }
 
+/// The serialized fields of an [`AgentId`], checked before use
+#[derive(Deserialize)]
+struct AgentIdFields {
+    id: Uuid,
+    name: String,
+    created_at: DateTime<Utc>,
+    #[serde(default)]
+    key_digest: Option<KeyDigest>,
+}
+
+impl TryFrom<AgentIdFields> for AgentId {
+    type Error = AgentError;
+
+    fn try_from(fields: AgentIdFields) -> Result<Self> {
+        if let Some(key_digest) = &fields.key_digest {
+            if fields.id != Self::uuid_of(key_digest) {
+                return Err(AgentError::InvalidId(format!(
+                    "UUID {} is not derived from key digest {}",
+                    fields.id, key_digest
+                )));
+            }
+        }
+        Ok(Self {
+            id: fields.id,
+            name: fields.name,
+            created_at: fields.created_at,
+            key_digest: fields.key_digest,
+        })
+    }
+}
+
+impl PartialEq for AgentId {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.key_digest, &other.key_digest) {
+            (Some(digest), Some(other_digest)) => digest == other_digest,
+            (None, None) => {
+                self.id == other.id
+                    && self.name == other.name
+                    && self.created_at == other.created_at
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for AgentId {}
+
+impl std::hash::Hash for AgentId {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        match &self.key_digest {
+            Some(digest) => digest.hash(state),
+            None => {
+                self.id.hash(state);
+                self.name.hash(state);
+                self.created_at.hash(state);
+            }
+        }
+    }
+}
+
 impl fmt::Display for AgentId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} ({})", self.name, self.id)
+        match &self.key_digest {
+            Some(digest) if self.name.is_empty() => write!(f, "{}", digest),
+            Some(digest) => write!(f, "{} ({})", self.name, digest),
+            None => write!(f, "{} ({})", self.name, self.id),
+        }
+    }
+}
+
+/// Parses self-certifying IDs, either the bare key digest (`agentid:zQm...`)
+/// or as displayed with a name (`shop-agent (agentid:zQm...)`)
+///
+/// Random IDs cannot be parsed, as their string form omits the creation time.
+impl FromStr for AgentId {
+    type Err = AgentError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, digest) = match s.strip_suffix(')').and_then(|s| s.rsplit_once(" (")) {
+            Some((name, digest)) => (name, digest),
+            None => ("", s),
+        };
+        Ok(Self::from_key_digest(name, digest.parse()?))
     }
 }
 
@@ -500,4 +741,70 @@ mod tests {
         assert!(!capabilities.can_verify);
         assert!(!capabilities.can_manage_trust);
     }
+
+    #[test]
+    fn test_self_certifying_agent_id() {
+        let public_key = [7u8; 32];
+        let agent_id = AgentId::from_public_key("shop-agent", &public_key);
+        assert!(agent_id.is_self_certifying());
+        assert!(agent_id.verify_key(&public_key).is_ok());
+        assert!(agent_id.verify_key(&[8u8; 32]).is_err());
+        assert!(AgentId::new("shop-agent").verify_key(&public_key).is_err());
+
+        // Independently derived IDs are equal
+        let again = AgentId::from_public_key("renamed", &public_key);
+        assert_eq!(again, agent_id);
+        assert_eq!(again.id(), agent_id.id());
+        assert_ne!(AgentId::from_public_key("shop-agent", &[8u8; 32]), agent_id);
+    }
+
+    #[test]
+    fn test_agent_id_string_roundtrip() {
+        // SHA2-256 of the 32-byte public key 07 07 ... 07
+        let digest = KeyDigest::of_public_key(&[7u8; 32]);
+        let canonical = digest.to_string();
+        assert!(canonical.starts_with("agentid:zQm"));
+        assert_eq!(canonical.parse::<KeyDigest>().unwrap(), digest);
+
+        let named = AgentId::from_key_digest("shop-agent", digest.clone());
+        let displayed = named.to_string();
+        assert_eq!(displayed, format!("shop-agent ({})", canonical));
+        let parsed: AgentId = displayed.parse().unwrap();
+        assert_eq!(parsed, named);
+        assert_eq!(parsed.name(), "shop-agent");
+        assert_eq!(parsed.to_string(), displayed);
+
+        let bare = AgentId::from_key_digest("", digest);
+        assert_eq!(bare.to_string(), canonical);
+        assert_eq!(canonical.parse::<AgentId>().unwrap(), bare);
+
+        for invalid in [
+            "shop-agent (3f2b1c1e-5d4a-4c3b-9a8f-7e6d5c4b3a29)",
+            "agentid:Qm",
+            "agentid:z111",
+            "zQmYtUc4iTCbbfVSDNKvtQqrfyezPPnFvE33wFmutw9PBBk",
+        ] {
+            assert!(invalid.parse::<AgentId>().is_err(), "accepted {}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_agent_id_serde_compatibility() {
+        let random = AgentId::new("test-agent");
+        let json = serde_json::to_value(&random).unwrap();
+        assert!(json.get("key_digest").is_none());
+        assert_eq!(serde_json::from_value::<AgentId>(json).unwrap(), random);
+
+        let self_certifying = AgentId::from_public_key("test-agent", &[7u8; 32]);
+        let json = serde_json::to_string(&self_certifying).unwrap();
+        let parsed: AgentId = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, self_certifying);
+        assert_eq!(parsed.id(), self_certifying.id());
+        assert!(parsed.verify_key(&[7u8; 32]).is_ok());
+
+        // A self-certifying ID cannot carry an arbitrary UUID
+        let mut json = serde_json::to_value(&self_certifying).unwrap();
+        json["id"] = serde_json::json!(Uuid::new_v4());
+        assert!(serde_json::from_value::<AgentId>(json).is_err());
+    }
 }